@@ -0,0 +1,322 @@
+// Large files opened on the native side
+//
+// The file is memory mapped and wrapped in a file-ops-wasm FileBuffer, so the
+// bytes stay in the page cache. Opening only maps the file and returns; the
+// line index is built on a background thread, and line and search requests
+// wait for it. The webview asks for the lines it shows and for search pages
+// instead of receiving the whole file over IPC.
+//
+// Reading a mapped file that another process truncated faults instead of
+// failing, so every access first checks that the file is still as long as
+// the map and reports an error otherwise.
+use file_ops_wasm::file_buffer::{FileBuffer, SearchMatch};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::HashMap;
+use std::fs::File;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use tauri::State;
+
+// Where a search page stopped; pass it back as `resume` for the next page
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct SearchCursor {
+    pub line: usize,   // 1-based
+    pub column: usize, // Byte index into the line
+}
+
+// One page of matches; `next` is None once the file is searched to the end
+#[derive(Serialize, Debug)]
+pub struct SearchPage {
+    pub matches: Vec<SearchMatch>,
+    pub next: Option<SearchCursor>,
+}
+
+// An open file: its handle and, once indexed, the buffer over its map
+pub struct FileDocument {
+    file: File,
+    size: u64,
+    buffer: Mutex<Option<Result<Arc<FileBuffer>, String>>>, // None while indexing
+    indexed: Condvar,
+}
+
+impl FileDocument {
+    // Map the file and start indexing it on a background thread
+    pub fn open(file_path: &str) -> Result<Arc<FileDocument>, String> {
+        let file = File::open(file_path).map_err(|e| format!("Failed to open file: {}", e))?;
+        // Safety: the map is read-only, and truncation is checked for before
+        // every access (check_size)
+        let map = unsafe { memmap2::Mmap::map(&file) }
+            .map_err(|e| format!("Failed to map file: {}", e))?;
+
+        let document = Arc::new(FileDocument {
+            file,
+            size: map.len() as u64,
+            buffer: Mutex::new(None),
+            indexed: Condvar::new(),
+        });
+
+        let indexing = Arc::clone(&document);
+        std::thread::spawn(move || {
+            let buffer = indexing
+                .check_size()
+                .map(|_| Arc::new(FileBuffer::from_mmap(map)));
+            if let Ok(mut slot) = indexing.buffer.lock() {
+                *slot = Some(buffer);
+            }
+            indexing.indexed.notify_all();
+        });
+        Ok(document)
+    }
+
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    // None until the line index is built
+    pub fn line_count(&self) -> Option<usize> {
+        let buffer = self.buffer.lock().ok()?;
+        match &*buffer {
+            Some(Ok(buffer)) => Some(buffer.line_count()),
+            _ => None,
+        }
+    }
+
+    // Fail if the file shrank since it was mapped
+    fn check_size(&self) -> Result<(), String> {
+        let metadata = self
+            .file
+            .metadata()
+            .map_err(|e| format!("Failed to get file metadata: {}", e))?;
+        if metadata.len() < self.size {
+            return Err("File was truncated on disk since it was opened".to_string());
+        }
+        Ok(())
+    }
+
+    // The indexed buffer, waiting for indexing to finish
+    fn buffer(&self) -> Result<Arc<FileBuffer>, String> {
+        let mut buffer = self.buffer.lock().map_err(|e| e.to_string())?;
+        while buffer.is_none() {
+            buffer = self.indexed.wait(buffer).map_err(|e| e.to_string())?;
+        }
+        let indexed = buffer.as_ref().unwrap().clone()?;
+        drop(buffer);
+
+        self.check_size()?;
+        Ok(indexed)
+    }
+
+    // Lines start_line..=end_line (1-based), newlines included
+    pub fn get_line_range(&self, start_line: usize, end_line: usize) -> Result<String, String> {
+        self.buffer()?
+            .get_line_range(start_line, end_line)
+            .map_err(String::from)
+    }
+
+    // Up to max_results (at least 1) regex matches in file order, from
+    // `resume` or the start of the file
+    pub fn search(
+        &self,
+        pattern: &str,
+        resume: Option<SearchCursor>,
+        max_results: usize,
+    ) -> Result<SearchPage, String> {
+        if max_results == 0 {
+            return Err("max_results must be at least 1".to_string());
+        }
+        let re = Regex::new(pattern).map_err(|e| format!("Invalid regex: {}", e))?;
+        let from = resume.map_or((1, 0), |cursor| (cursor.line.max(1), cursor.column));
+
+        let (matches, rest) = self.buffer()?.search_regex(&re, from, max_results);
+        Ok(SearchPage {
+            matches,
+            next: rest.map(|(line, column)| SearchCursor { line, column }),
+        })
+    }
+}
+
+// Open documents: doc_id -> file
+#[derive(Default)]
+pub struct DocumentState {
+    documents: Mutex<HashMap<u32, Arc<FileDocument>>>,
+    next_id: AtomicU32,
+}
+
+impl DocumentState {
+    fn get(&self, doc_id: u32) -> Result<Arc<FileDocument>, String> {
+        let documents = self.documents.lock().map_err(|e| e.to_string())?;
+        documents
+            .get(&doc_id)
+            .cloned()
+            .ok_or_else(|| format!("Document {} not found", doc_id))
+    }
+}
+
+// Open a file as a document; returns { docId, size } without waiting for
+// the line index (see doc_info)
+#[tauri::command]
+#[cfg_attr(not(target_os = "macos"), allow(unused_variables))]
+pub async fn doc_open(
+    app_handle: tauri::AppHandle,
+    state: State<'_, DocumentState>,
+    file_path: String,
+) -> Result<serde_json::Value, String> {
+    let document = tokio::task::spawn_blocking(move || {
+        #[cfg(target_os = "macos")]
+        let _guard = crate::macos_bookmarks::start_access(&app_handle, &file_path)?;
+
+        let document = FileDocument::open(&file_path)?;
+        println!(
+            "[Documents] Opened {} ({} bytes)",
+            file_path,
+            document.size()
+        );
+        Ok::<_, String>(document)
+    })
+    .await
+    .map_err(|e| format!("Failed to open file: {}", e))??;
+
+    let doc_id = state.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+    let info = json!({
+        "docId": doc_id,
+        "size": document.size()
+    });
+    let mut documents = state.documents.lock().map_err(|e| e.to_string())?;
+    documents.insert(doc_id, document);
+    Ok(info)
+}
+
+// Size and line count of a document; lineCount is null while indexing
+#[tauri::command]
+pub async fn doc_info(
+    state: State<'_, DocumentState>,
+    doc_id: u32,
+) -> Result<serde_json::Value, String> {
+    let document = state.get(doc_id)?;
+    Ok(json!({
+        "size": document.size(),
+        "lineCount": document.line_count()
+    }))
+}
+
+// Lines start_line..=end_line (1-based) of a document
+#[tauri::command]
+pub async fn doc_get_lines(
+    state: State<'_, DocumentState>,
+    doc_id: u32,
+    start_line: usize,
+    end_line: usize,
+) -> Result<String, String> {
+    let document = state.get(doc_id)?;
+    tokio::task::spawn_blocking(move || document.get_line_range(start_line, end_line))
+        .await
+        .map_err(|e| format!("Failed to read document: {}", e))?
+}
+
+// Regex search in a document, one page of at most max_results matches
+// Pass the returned `next` back as `resume` for the following page
+#[tauri::command]
+pub async fn doc_search(
+    state: State<'_, DocumentState>,
+    doc_id: u32,
+    pattern: String,
+    resume: Option<SearchCursor>,
+    max_results: usize,
+) -> Result<SearchPage, String> {
+    let document = state.get(doc_id)?;
+    tokio::task::spawn_blocking(move || document.search(&pattern, resume, max_results))
+        .await
+        .map_err(|e| format!("Failed to search document: {}", e))?
+}
+
+// Close a document, unmapping its file once no search or indexing still
+// uses it
+#[tauri::command]
+pub async fn doc_close(state: State<'_, DocumentState>, doc_id: u32) -> Result<bool, String> {
+    let mut documents = state.documents.lock().map_err(|e| e.to_string())?;
+    Ok(documents.remove(&doc_id).is_some())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn write_temp(name: &str, content: &[u8]) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("tidycode-doc-{}-{}", name, std::process::id()));
+        std::fs::write(&path, content).unwrap();
+        path
+    }
+
+    fn numbered(lines: usize) -> String {
+        (1..=lines).map(|i| format!("line {}\n", i)).collect()
+    }
+
+    #[test]
+    fn test_lines_after_background_indexing() {
+        let content = numbered(300) + "last";
+        let path = write_temp("lines", content.as_bytes());
+        let document = FileDocument::open(path.to_str().unwrap()).unwrap();
+        assert_eq!(document.size(), content.len() as u64);
+
+        assert_eq!(
+            document.get_line_range(64, 66).unwrap(),
+            "line 64\nline 65\nline 66\n"
+        );
+        assert_eq!(document.line_count(), Some(301));
+        assert_eq!(document.get_line_range(300, 301).unwrap(), "line 300\nlast");
+        assert!(document.get_line_range(301, 302).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_search_pages_resume_at_cursor() {
+        let content = numbered(500);
+        let path = write_temp("search", content.as_bytes());
+        let document = FileDocument::open(path.to_str().unwrap()).unwrap();
+        let buffer = FileBuffer::new(content.into_bytes()).unwrap();
+        let expected = buffer.search(r"7\d", 10_000).unwrap();
+
+        let mut found = Vec::new();
+        let mut resume = None;
+        loop {
+            let page = document.search(r"7\d", resume, 7).unwrap();
+            assert!(page.matches.len() <= 7);
+            found.extend(page.matches.iter().map(|m| (m.line, m.column)));
+            match page.next {
+                Some(next) => resume = Some(next),
+                None => break,
+            }
+        }
+        let expected: Vec<_> = expected.iter().map(|m| (m.line, m.column)).collect();
+        assert_eq!(found, expected);
+        assert!(document.search("x", None, 0).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_truncated_file_is_an_error() {
+        let path = write_temp("truncate", numbered(200).as_bytes());
+        let document = FileDocument::open(path.to_str().unwrap()).unwrap();
+        assert!(document.get_line_range(1, 1).is_ok());
+        std::fs::write(&path, "short\n").unwrap();
+
+        let error = document.get_line_range(150, 150).unwrap_err();
+        assert!(error.contains("truncated"), "{}", error);
+        assert!(document.search("line", None, 10).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_empty_file() {
+        let path = write_temp("empty", b"");
+        let document = FileDocument::open(path.to_str().unwrap()).unwrap();
+        let page = document.search("x", None, 10).unwrap();
+        assert!(page.matches.is_empty() && page.next.is_none());
+        assert_eq!(document.line_count(), Some(0));
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,333 @@
+// Follow mode (tail -f) for growing files such as logs
+//
+// The parent directory is watched rather than the file itself so that log
+// rotation (rename + recreate) is noticed. Only newly appended bytes are sent
+// to the webview, which appends them to its WASM buffer. Events carry at most
+// FOLLOW_CHUNK_BYTES, base64-encoded, so a burst of output or a re-read after
+// rotation arrives as a series of bounded events.
+use base64::Engine;
+use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
+use serde_json::json;
+use std::collections::HashMap;
+use std::fs::{File, Metadata};
+use std::io::{ErrorKind, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tauri::{Emitter, State};
+
+// Largest payload of a single follow event
+const FOLLOW_CHUNK_BYTES: u64 = 1024 * 1024;
+
+// Leading bytes remembered to recognize a replaced file where the platform
+// identity cannot (no identity, or Windows reusing the creation time of a
+// file recreated under the same name)
+const HEAD_BYTES: u64 = 64;
+
+// Global state for followed files: path -> directory watcher
+#[derive(Default)]
+pub struct FollowState {
+    watchers: Mutex<HashMap<String, RecommendedWatcher>>,
+}
+
+// Change detected in a followed file
+#[derive(Debug, PartialEq)]
+enum TailUpdate {
+    Appended { offset: u64, data: Vec<u8> },
+    // The file was replaced; `data` is its first chunk, the rest follows
+    // as appends
+    Reset { reason: &'static str, data: Vec<u8> },
+}
+
+// Read position of a followed file
+struct Tailer {
+    path: PathBuf,
+    position: u64,
+    identity: Option<(u64, u64)>,
+    head: Vec<u8>,
+    chunk_bytes: u64,
+}
+
+impl Tailer {
+    fn new(path: PathBuf, position: u64, metadata: &Metadata) -> std::io::Result<Self> {
+        let head = read_range(&path, 0, HEAD_BYTES.min(position))?;
+        Ok(Tailer {
+            path,
+            position,
+            identity: file_identity(metadata),
+            head,
+            chunk_bytes: FOLLOW_CHUNK_BYTES,
+        })
+    }
+
+    // Check the file for new bytes, truncation or rotation
+    // Returns at most one chunk; poll again until None to catch up
+    fn poll(&mut self) -> std::io::Result<Option<TailUpdate>> {
+        let metadata = match std::fs::metadata(&self.path) {
+            Ok(metadata) => metadata,
+            // Rotated away and not recreated yet
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+
+        let identity = file_identity(&metadata);
+        let len = metadata.len();
+        let head = read_range(&self.path, 0, HEAD_BYTES.min(len))?;
+
+        let reset_reason = if identity != self.identity {
+            Some("rotated")
+        } else if len < self.position {
+            Some("truncated")
+        } else if !head.starts_with(&self.head) {
+            Some("rotated")
+        } else {
+            None
+        };
+
+        if let Some(reason) = reset_reason {
+            // Re-read from the start
+            let data = read_range(&self.path, 0, len.min(self.chunk_bytes))?;
+            self.identity = identity;
+            self.head = head;
+            self.position = data.len() as u64;
+            return Ok(Some(TailUpdate::Reset { reason, data }));
+        }
+
+        if len == self.position {
+            return Ok(None);
+        }
+
+        let offset = self.position;
+        let data = read_range(&self.path, offset, len.min(offset + self.chunk_bytes))?;
+        if data.is_empty() {
+            return Ok(None);
+        }
+        self.head = head;
+        self.position += data.len() as u64;
+        Ok(Some(TailUpdate::Appended { offset, data }))
+    }
+}
+
+// Device and inode, used to notice that a path now points at a new file
+#[cfg(unix)]
+fn file_identity(metadata: &Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    Some((metadata.dev(), metadata.ino()))
+}
+
+// Creation time; a rotated log is a newly created file
+#[cfg(windows)]
+fn file_identity(metadata: &Metadata) -> Option<(u64, u64)> {
+    use std::os::windows::fs::MetadataExt;
+    Some((metadata.creation_time(), 0))
+}
+
+#[cfg(not(any(unix, windows)))]
+fn file_identity(_metadata: &Metadata) -> Option<(u64, u64)> {
+    None
+}
+
+fn read_range(path: &Path, start: u64, end: u64) -> std::io::Result<Vec<u8>> {
+    let mut file = File::open(path)?;
+    file.seek(SeekFrom::Start(start))?;
+
+    let mut data = Vec::with_capacity(end.saturating_sub(start) as usize);
+    file.take(end.saturating_sub(start)).read_to_end(&mut data)?;
+    Ok(data)
+}
+
+// Emit updates until the webview has caught up with the file
+fn emit_update(app_handle: &tauri::AppHandle, file_path: &str, tailer: &Mutex<Tailer>) {
+    let mut tailer = match tailer.lock() {
+        Ok(tailer) => tailer,
+        Err(_) => return,
+    };
+
+    loop {
+        match tailer.poll() {
+            Ok(Some(TailUpdate::Appended { offset, data })) => {
+                let _ = app_handle.emit("file-follow-append", json!({
+                    "path": file_path,
+                    "offset": offset,
+                    "size": tailer.position,
+                    "data": base64::engine::general_purpose::STANDARD.encode(data)
+                }));
+            }
+            Ok(Some(TailUpdate::Reset { reason, data })) => {
+                println!("[Follow] {} was {}, re-reading", file_path, reason);
+                let _ = app_handle.emit("file-follow-reset", json!({
+                    "path": file_path,
+                    "reason": reason,
+                    "size": tailer.position,
+                    "data": base64::engine::general_purpose::STANDARD.encode(data)
+                }));
+            }
+            Ok(None) => break,
+            Err(e) => {
+                println!("[Follow] Failed to read {}: {}", file_path, e);
+                break;
+            }
+        }
+    }
+}
+
+// Start following a file
+// `from_offset` is how much of the file the webview already has (defaults to
+// the current size). Returns the offset following starts from.
+#[tauri::command]
+pub async fn follow_file_start(
+    app_handle: tauri::AppHandle,
+    state: State<'_, FollowState>,
+    file_path: String,
+    from_offset: Option<u64>,
+) -> Result<u64, String> {
+    let path = PathBuf::from(&file_path);
+    let metadata = std::fs::metadata(&path)
+        .map_err(|e| format!("Failed to get file metadata: {}", e))?;
+
+    let position = from_offset.unwrap_or(metadata.len()).min(metadata.len());
+    let tailer = Tailer::new(path.clone(), position, &metadata)
+        .map_err(|e| format!("Failed to read {}: {}", file_path, e))?;
+    let tailer = Arc::new(Mutex::new(tailer));
+
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| format!("Not a file path: {}", file_path))?
+        .to_os_string();
+    let watch_dir = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+
+    let handler_app = app_handle.clone();
+    let handler_path = file_path.clone();
+    let handler_tailer = tailer.clone();
+    let mut watcher = notify::recommended_watcher(move |res: notify::Result<Event>| {
+        if let Ok(event) = res {
+            let touches_file = event
+                .paths
+                .iter()
+                .any(|p| p.file_name() == Some(file_name.as_os_str()));
+            if touches_file {
+                emit_update(&handler_app, &handler_path, &handler_tailer);
+            }
+        }
+    })
+    .map_err(|e| format!("Failed to create file watcher: {}", e))?;
+
+    watcher
+        .watch(&watch_dir, RecursiveMode::NonRecursive)
+        .map_err(|e| format!("Failed to watch {}: {}", watch_dir.display(), e))?;
+
+    // Catch up on anything written since the webview read the file
+    if position < metadata.len() {
+        emit_update(&app_handle, &file_path, &tailer);
+    }
+
+    let mut watchers = state.watchers.lock().map_err(|e| e.to_string())?;
+    watchers.insert(file_path.clone(), watcher);
+    println!("[Follow] Following {} from offset {}", file_path, position);
+
+    Ok(position)
+}
+
+// Stop following a file
+#[tauri::command]
+pub async fn follow_file_stop(
+    state: State<'_, FollowState>,
+    file_path: String,
+) -> Result<bool, String> {
+    let mut watchers = state.watchers.lock().map_err(|e| e.to_string())?;
+    Ok(watchers.remove(&file_path).is_some())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn temp_log(name: &str, content: &[u8]) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("tidycode-follow-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("app.log");
+        std::fs::write(&path, content).unwrap();
+        path
+    }
+
+    fn tailer(path: &Path, chunk_bytes: u64) -> Tailer {
+        let metadata = std::fs::metadata(path).unwrap();
+        let mut tailer = Tailer::new(path.to_path_buf(), metadata.len(), &metadata).unwrap();
+        tailer.chunk_bytes = chunk_bytes;
+        tailer
+    }
+
+    fn append(path: &Path, data: &[u8]) {
+        let mut file = std::fs::OpenOptions::new().append(true).open(path).unwrap();
+        file.write_all(data).unwrap();
+    }
+
+    #[test]
+    fn test_appends_arrive_in_bounded_chunks() {
+        let path = temp_log("append", b"first line\n");
+        let mut tailer = tailer(&path, 4);
+        assert_eq!(tailer.poll().unwrap(), None);
+
+        append(&path, b"second\n");
+        let mut received = Vec::new();
+        while let Some(update) = tailer.poll().unwrap() {
+            let TailUpdate::Appended { offset, data } = update else {
+                panic!("unexpected reset");
+            };
+            assert_eq!(offset, 11 + received.len() as u64);
+            assert!(data.len() <= 4);
+            received.extend(data);
+        }
+        assert_eq!(received, b"second\n");
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_truncation_and_rotation_reset() {
+        let path = temp_log("reset", b"2024-01-01 old entry\n");
+        let mut tailer = tailer(&path, 1024);
+
+        // copytruncate: same file, shorter
+        std::fs::write(&path, b"x\n").unwrap();
+        let reset = tailer.poll().unwrap();
+        assert_eq!(
+            reset,
+            Some(TailUpdate::Reset {
+                reason: "truncated",
+                data: b"x\n".to_vec()
+            })
+        );
+        assert_eq!(tailer.position, 2);
+
+        // Rename + recreate with at least as many bytes as already read
+        let rotated = path.with_extension("log.1");
+        std::fs::rename(&path, &rotated).unwrap();
+        std::fs::write(&path, b"new file, longer\n").unwrap();
+        assert_eq!(
+            tailer.poll().unwrap(),
+            Some(TailUpdate::Reset {
+                reason: "rotated",
+                data: b"new file, longer\n".to_vec()
+            })
+        );
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_rewritten_head_is_a_rotation() {
+        // Same identity and a size past the read position, but new content
+        let path = temp_log("head", b"aaaa\n");
+        let mut tailer = tailer(&path, 1024);
+        let mut file = std::fs::OpenOptions::new().write(true).open(&path).unwrap();
+        file.write_all(b"bbbb\nbbbb\n").unwrap();
+
+        let reset = tailer.poll().unwrap();
+        assert!(matches!(reset, Some(TailUpdate::Reset { reason: "rotated", .. })));
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+}
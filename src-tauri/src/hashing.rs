@@ -0,0 +1,151 @@
+// Checksums of files on disk
+//
+// Files are streamed through the shared hashers from file-ops-wasm so that
+// multi-gigabyte downloads never have to be loaded into a webview buffer.
+// All requested algorithms are computed in a single read of the file.
+use file_ops_wasm::hashing::{self, HashAlgorithm, HashResult, MultiHasher, VerifyResult};
+use serde_json::json;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use tauri::Emitter;
+
+const HASH_CHUNK_SIZE: usize = 1024 * 1024;
+
+// Sidecar extensions tried next to a file, in order of preference
+const SIDECAR_EXTENSIONS: &[&str] = &["sha256", "sha512", "sha1", "md5", "xxh64", "xxh3", "crc32"];
+
+fn parse_algorithms(algorithms: &[String]) -> Result<Vec<HashAlgorithm>, String> {
+    if algorithms.is_empty() {
+        return Err("No hash algorithm requested".to_string());
+    }
+    algorithms.iter().map(|a| a.parse()).collect()
+}
+
+// Stream a file through the hashers, emitting "file-hash-progress" per chunk
+fn hash_path(
+    app_handle: &tauri::AppHandle,
+    file_path: &str,
+    algorithms: &[HashAlgorithm],
+) -> Result<Vec<HashResult>, String> {
+    let mut file = File::open(file_path).map_err(|e| format!("Failed to open file: {}", e))?;
+    let total_bytes = file
+        .metadata()
+        .map_err(|e| format!("Failed to get file metadata: {}", e))?
+        .len();
+
+    let mut hasher = MultiHasher::new(algorithms);
+    let mut chunk = vec![0u8; HASH_CHUNK_SIZE];
+    let mut bytes_read = 0u64;
+    loop {
+        let n = file
+            .read(&mut chunk)
+            .map_err(|e| format!("Failed to read chunk: {}", e))?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&chunk[..n]);
+        bytes_read += n as u64;
+
+        let _ = app_handle.emit("file-hash-progress", json!({
+            "path": file_path,
+            "bytesRead": bytes_read,
+            "totalBytes": total_bytes,
+            "progress": (bytes_read as f64 / total_bytes.max(1) as f64 * 100.0) as u32
+        }));
+    }
+
+    Ok(hasher.finish())
+}
+
+// First existing `<file>.<ext>` sidecar, with the algorithm its extension names
+fn find_sidecar(path: &Path) -> Option<(PathBuf, HashAlgorithm)> {
+    SIDECAR_EXTENSIONS.iter().find_map(|ext| {
+        let mut sidecar = path.as_os_str().to_os_string();
+        sidecar.push(".");
+        sidecar.push(ext);
+        let sidecar = PathBuf::from(sidecar);
+        let algorithm = ext.parse().ok()?;
+        sidecar.is_file().then_some((sidecar, algorithm))
+    })
+}
+
+// Hash a file with each of md5, sha1, sha256, sha512, crc32, xxh64 or xxh3
+#[tauri::command]
+pub async fn hash_file(
+    app_handle: tauri::AppHandle,
+    file_path: String,
+    algorithms: Vec<String>,
+) -> Result<Vec<HashResult>, String> {
+    let algorithms = parse_algorithms(&algorithms)?;
+
+    tokio::task::spawn_blocking(move || {
+        #[cfg(target_os = "macos")]
+        let _guard = crate::macos_bookmarks::start_access(&app_handle, &file_path)?;
+
+        let results = hash_path(&app_handle, &file_path, &algorithms)?;
+        println!("[Hash] Hashed {} ({} algorithms)", file_path, results.len());
+        Ok(results)
+    })
+    .await
+    .map_err(|e| format!("Failed to hash file: {}", e))?
+}
+
+// Verify a file against an expected digest
+// The digest comes from `expected`, else from `sidecar_path`, else from the
+// first `<file>.sha256` / `.sha512` / `.sha1` / `.md5` ... found next to it
+#[tauri::command]
+pub async fn verify_file_hash(
+    app_handle: tauri::AppHandle,
+    file_path: String,
+    sidecar_path: Option<String>,
+    expected: Option<String>,
+    algorithm: Option<String>,
+) -> Result<VerifyResult, String> {
+    let mut hint = algorithm.map(|a| a.parse::<HashAlgorithm>()).transpose()?;
+
+    let sidecar = match (expected, sidecar_path) {
+        (Some(expected), _) => expected,
+        (None, Some(sidecar_path)) => {
+            if hint.is_none() {
+                hint = Path::new(&sidecar_path)
+                    .extension()
+                    .and_then(|ext| ext.to_str()?.parse().ok());
+            }
+            std::fs::read_to_string(&sidecar_path)
+                .map_err(|e| format!("Failed to read {}: {}", sidecar_path, e))?
+        }
+        (None, None) => {
+            let (sidecar_path, algorithm) = find_sidecar(Path::new(&file_path))
+                .ok_or_else(|| format!("No checksum file found next to {}", file_path))?;
+            hint = hint.or(Some(algorithm));
+            std::fs::read_to_string(&sidecar_path)
+                .map_err(|e| format!("Failed to read {}: {}", sidecar_path.display(), e))?
+        }
+    };
+
+    let file_name = Path::new(&file_path)
+        .file_name()
+        .and_then(|name| name.to_str())
+        .map(str::to_string);
+    let expected = hashing::parse_sidecar(&sidecar, file_name.as_deref(), hint)?;
+
+    tokio::task::spawn_blocking(move || {
+        #[cfg(target_os = "macos")]
+        let _guard = crate::macos_bookmarks::start_access(&app_handle, &file_path)?;
+
+        let actual = hash_path(&app_handle, &file_path, &[expected.algorithm])?
+            .remove(0)
+            .hex;
+        let result = hashing::verify(&expected, actual);
+        println!(
+            "[Hash] {} {} check {}",
+            file_path,
+            result.algorithm,
+            if result.matches { "passed" } else { "FAILED" }
+        );
+        Ok(result)
+    })
+    .await
+    .map_err(|e| format!("Failed to hash file: {}", e))?
+}
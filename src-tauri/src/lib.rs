@@ -12,8 +12,14 @@ use tauri::{Emitter, Manager, State};
 use tauri_plugin_dialog::{DialogExt, MessageDialogButtons, MessageDialogKind};
 use walkdir::WalkDir;
 use portable_pty::{native_pty_system, CommandBuilder, PtySize};
+use file_ops_wasm::secrets::{self, Policy, Redaction};
 use std::sync::Arc;
 
+mod documents;
+mod follow;
+mod hashing;
+mod saving;
+
 // PDF Print Options
 #[derive(Debug, Serialize, Deserialize)]
 struct PrintPdfOptions {
@@ -1447,17 +1453,29 @@ async fn check_model_available(model: String) -> Result<bool, String> {
 }
 
 // Save file content to a specific path
+// Written atomically (temp file + rename); see saving.rs. Pass the
+// `modified` (nanoseconds, a string) / `hash` of the last save to refuse
+// overwriting a file changed on disk since, which fails with a
+// saving::CONFLICT_ERROR message. Read-only files are refused.
+// Returns { path, size, modified, hash, backup }
 #[tauri::command]
-async fn save_file_to_path(file_path: String, content: String) -> Result<String, String> {
-    let path = Path::new(&file_path);
+async fn save_file_to_path(
+    file_path: String,
+    content: String,
+    expected_modified: Option<String>,
+    expected_hash: Option<String>,
+    backup: Option<bool>,
+) -> Result<serde_json::Value, String> {
+    let options = saving::SaveOptions {
+        expected_modified,
+        expected_hash,
+        backup: backup.unwrap_or(false),
+    };
 
-    // Use async file I/O to prevent blocking the event loop
-    // This is especially important on Windows for large files
-    tokio::fs::write(path, content)
+    // Blocking I/O off the event loop; fsync can be slow, especially on Windows
+    tokio::task::spawn_blocking(move || saving::save_file(&file_path, content.as_bytes(), &options))
         .await
-        .map_err(|e| format!("Failed to write file: {}", e))?;
-
-    Ok(format!("Successfully saved to {}", file_path))
+        .map_err(|e| format!("Failed to write file: {}", e))?
 }
 
 #[tauri::command]
@@ -1495,6 +1513,26 @@ async fn read_file_from_path(app_handle: tauri::AppHandle, file_path: String) ->
     }
 }
 
+// Unified diff of unsaved content against the file's on-disk version
+#[tauri::command]
+async fn diff_with_disk_version(
+    app_handle: tauri::AppHandle,
+    file_path: String,
+    content: String,
+    context_lines: Option<usize>,
+) -> Result<String, String> {
+    let on_disk = read_file_from_path(app_handle, file_path.clone()).await?;
+    let on_disk = String::from_utf8_lossy(&on_disk);
+
+    Ok(file_ops_wasm::patch::unified_diff(
+        &on_disk,
+        &content,
+        &file_path,
+        &file_path,
+        context_lines.unwrap_or(3),
+    ))
+}
+
 // Read large file in chunks with progress updates
 #[tauri::command]
 async fn read_large_file_chunked(
@@ -1774,6 +1812,7 @@ async fn fix_with_claude(
     error_details: String,
     api_key: String,
     model: String,
+    privacy_policy: Option<String>,
 ) -> Result<String, String> {
     let details: ErrorDetails = serde_json::from_str(&error_details)
         .map_err(|e| format!("Failed to parse error details: {}", e))?;
@@ -1819,6 +1858,9 @@ Fixed {}:"#,
         details.error_type
     );
 
+    // Redact or block secrets before anything leaves the machine
+    let redaction = guard_outbound(&prompt, privacy_policy)?;
+
     let client = reqwest::Client::builder()
         .timeout(std::time::Duration::from_secs(60))
         .build()
@@ -1831,7 +1873,7 @@ Fixed {}:"#,
         "messages": [
             {
                 "role": "user",
-                "content": prompt
+                "content": redaction.text
             }
         ],
         "temperature": 0.1
@@ -1870,6 +1912,8 @@ Fixed {}:"#,
         .unwrap_or("")
         .to_string();
 
+    fixed = redaction.restore(&fixed);
+
     // Remove markdown code block markers
     if fixed.contains("```") {
         let mut in_code_block = false;
@@ -1907,6 +1951,7 @@ async fn fix_with_groq(
     error_details: String,
     api_key: String,
     model: String,
+    privacy_policy: Option<String>,
 ) -> Result<String, String> {
     let details: ErrorDetails = serde_json::from_str(&error_details)
         .map_err(|e| format!("Failed to parse error details: {}", e))?;
@@ -1952,6 +1997,9 @@ Fixed {}:"#,
         details.error_type
     );
 
+    // Redact or block secrets before anything leaves the machine
+    let redaction = guard_outbound(&prompt, privacy_policy)?;
+
     let client = reqwest::Client::builder()
         .timeout(std::time::Duration::from_secs(60))
         .build()
@@ -1966,7 +2014,7 @@ Fixed {}:"#,
             },
             {
                 "role": "user",
-                "content": prompt
+                "content": redaction.text
             }
         ],
         "temperature": 0.1,
@@ -2004,6 +2052,8 @@ Fixed {}:"#,
         .unwrap_or("")
         .to_string();
 
+    fixed = redaction.restore(&fixed);
+
     // Remove markdown code block markers
     if fixed.contains("```") {
         let mut in_code_block = false;
@@ -2041,6 +2091,7 @@ async fn fix_with_openai(
     error_details: String,
     api_key: String,
     model: String,
+    privacy_policy: Option<String>,
 ) -> Result<String, String> {
     let details: ErrorDetails = serde_json::from_str(&error_details)
         .map_err(|e| format!("Failed to parse error details: {}", e))?;
@@ -2086,6 +2137,9 @@ Fixed {}:"#,
         details.error_type
     );
 
+    // Redact or block secrets before anything leaves the machine
+    let redaction = guard_outbound(&prompt, privacy_policy)?;
+
     let client = reqwest::Client::builder()
         .timeout(std::time::Duration::from_secs(60))
         .build()
@@ -2100,7 +2154,7 @@ Fixed {}:"#,
             },
             {
                 "role": "user",
-                "content": prompt
+                "content": redaction.text
             }
         ],
         "temperature": 0.1,
@@ -2138,6 +2192,8 @@ Fixed {}:"#,
         .unwrap_or("")
         .to_string();
 
+    fixed = redaction.restore(&fixed);
+
     // Remove markdown code block markers
     if fixed.contains("```") {
         let mut in_code_block = false;
@@ -2174,7 +2230,10 @@ async fn get_claude_completion(
     prompt: String,
     api_key: String,
     model: String,
+    privacy_policy: Option<String>,
 ) -> Result<String, String> {
+    let redaction = guard_outbound(&prompt, privacy_policy)?;
+
     let client = reqwest::Client::builder()
         .timeout(std::time::Duration::from_secs(10))
         .build()
@@ -2187,7 +2246,7 @@ async fn get_claude_completion(
         "messages": [
             {
                 "role": "user",
-                "content": prompt
+                "content": redaction.text
             }
         ],
         "temperature": 0.2
@@ -2227,7 +2286,21 @@ async fn get_claude_completion(
         .trim()
         .to_string();
 
-    Ok(completion)
+    Ok(redaction.restore(&completion))
+}
+
+// Apply the secret scanner to text bound for a remote AI provider
+// `policy` is "redact" (default), "block" or "off"
+fn guard_outbound(text: &str, policy: Option<String>) -> Result<Redaction, String> {
+    let policy: Policy = policy.as_deref().unwrap_or("redact").parse()?;
+    let redaction = secrets::guard(text, policy)?;
+    if !redaction.findings.is_empty() {
+        println!(
+            "[Privacy] Redacted {} sensitive value(s) before sending",
+            redaction.findings.len()
+        );
+    }
+    Ok(redaction)
 }
 
 // Helper function to extract JSON/XML content
@@ -2922,6 +2995,8 @@ pub fn run() {
             shells: Mutex::new(HashMap::new()),
         })
         .manage(FileOpenState::default())
+        .manage(follow::FollowState::default())
+        .manage(documents::DocumentState::default())
         .invoke_handler(tauri::generate_handler![
             check_ollama_status,
             pull_ollama_model,
@@ -2934,7 +3009,17 @@ pub fn run() {
             save_file_to_path,
             store_security_bookmark,
             read_file_from_path,
+            diff_with_disk_version,
             read_large_file_chunked,
+            documents::doc_open,
+            documents::doc_info,
+            documents::doc_get_lines,
+            documents::doc_search,
+            documents::doc_close,
+            follow::follow_file_start,
+            follow::follow_file_stop,
+            hashing::hash_file,
+            hashing::verify_file_hash,
             get_cli_args,
             canonicalize_path,
             get_home_directory,
@@ -0,0 +1,458 @@
+// Atomic, conflict-aware saving
+//
+// Content is written to a temporary file next to the target, synced and
+// renamed over it, so a crash mid-save leaves either the old or the new file,
+// never a truncated one. The old file's mode and owner are copied to the new
+// one and symlinks are saved through to their target. A file the user may
+// not write to is refused, as a rename would replace it regardless.
+//
+// When the webview passes what it last saw on disk (modified time and/or a
+// digest), a save over a file that changed since fails with an error that
+// starts with CONFLICT_ERROR instead of silently overwriting it.
+use file_ops_wasm::hashing::{self, HashAlgorithm};
+use serde_json::json;
+use std::fs::{self, File, Metadata, OpenOptions};
+use std::io::{ErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+/// Prefix of the error returned when the file changed on disk
+pub const CONFLICT_ERROR: &str = "FILE_CHANGED_ON_DISK";
+
+// Digest returned after a save, to pass back as `expected_hash` next time
+const SAVE_HASH: HashAlgorithm = HashAlgorithm::Sha256;
+
+// What the caller last saw on disk
+#[derive(Default)]
+pub struct SaveOptions {
+    pub expected_modified: Option<String>, // Nanoseconds, as save_file reports
+    pub expected_hash: Option<String>,     // Bare hex digest; algorithm from its length
+    pub backup: bool,                      // Keep the previous version as `<file>.bak`
+}
+
+// Nanoseconds since the epoch, as a string: JS numbers cannot hold them, and
+// whole seconds miss a change made within the second of the last save
+fn modified_nanos(metadata: &Metadata) -> Option<String> {
+    metadata
+        .modified()
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_nanos().to_string())
+}
+
+fn conflict(path: &Path, reason: &str) -> String {
+    format!(
+        "{}: {} {} since it was opened",
+        CONFLICT_ERROR,
+        path.display(),
+        reason
+    )
+}
+
+// Fail if the file on disk is not the one the caller expects to replace
+fn check_conflict(
+    path: &Path,
+    existing: Option<&Metadata>,
+    options: &SaveOptions,
+) -> Result<(), String> {
+    if options.expected_modified.is_none() && options.expected_hash.is_none() {
+        return Ok(());
+    }
+    let Some(metadata) = existing else {
+        return Err(conflict(path, "was deleted"));
+    };
+
+    if let Some(expected) = &options.expected_modified {
+        if modified_nanos(metadata).as_ref() != Some(expected) {
+            return Err(conflict(path, "was modified"));
+        }
+    }
+
+    if let Some(expected) = &options.expected_hash {
+        let expected = hashing::parse_sidecar(expected, None, None)
+            .map_err(|_| format!("Invalid expected hash '{}'", expected))?;
+        let mut file = File::open(path).map_err(|e| format!("Failed to open file: {}", e))?;
+        let mut hasher = hashing::Hasher::new(expected.algorithm);
+        let mut chunk = vec![0u8; 1024 * 1024];
+        loop {
+            let n = file
+                .read(&mut chunk)
+                .map_err(|e| format!("Failed to read file: {}", e))?;
+            if n == 0 {
+                break;
+            }
+            hasher.update(&chunk[..n]);
+        }
+        if !hashing::verify(&expected, hasher.finish()).matches {
+            return Err(conflict(path, "was modified"));
+        }
+    }
+    Ok(())
+}
+
+// `<dir>/.<name>.<pid>-<n>.tmp`, created exclusively
+// When it replaces an existing file it starts owner-only on Unix, so the new
+// content is never readable with wider permissions than the old file had
+#[cfg_attr(not(unix), allow(unused_variables))]
+fn create_temp(path: &Path, replacing: bool) -> std::io::Result<(PathBuf, File)> {
+    let dir = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    for attempt in 0..100 {
+        let temp = dir.join(format!(".{}.{}-{}.tmp", name, std::process::id(), attempt));
+        let mut options = OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        if replacing {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        match options.open(&temp) {
+            Ok(file) => return Ok((temp, file)),
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Err(std::io::Error::new(
+        ErrorKind::AlreadyExists,
+        "No free temporary file name",
+    ))
+}
+
+// Give the new file the old one's permissions and, on Unix, owner
+// Returns false when the owner cannot be kept (another user's file)
+fn copy_attributes(file: &File, metadata: &Metadata) -> std::io::Result<bool> {
+    file.set_permissions(metadata.permissions())?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        let current = file.metadata()?;
+        if (current.uid(), current.gid()) != (metadata.uid(), metadata.gid())
+            && std::os::unix::fs::fchown(file, Some(metadata.uid()), Some(metadata.gid())).is_err()
+        {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+// Hard links would be split by a rename: saving must keep sharing the inode
+#[cfg(unix)]
+fn has_hard_links(metadata: &Metadata) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    metadata.nlink() > 1
+}
+
+#[cfg(not(unix))]
+fn has_hard_links(_metadata: &Metadata) -> bool {
+    false
+}
+
+// Fill and sync the temporary file, then rename it over `path`
+fn write_and_rename(
+    mut file: File,
+    temp: &Path,
+    path: &Path,
+    content: &[u8],
+    existing: Option<&Metadata>,
+) -> std::io::Result<bool> {
+    // Mode and owner first, so the content is never exposed more widely
+    if let Some(metadata) = existing {
+        if !copy_attributes(&file, metadata)? {
+            return Ok(false);
+        }
+    }
+    file.write_all(content)?;
+    file.sync_all()?;
+    drop(file);
+    fs::rename(temp, path)?;
+
+    // Persist the rename itself
+    #[cfg(unix)]
+    if let Some(dir) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        let _ = File::open(dir).and_then(|d| d.sync_all());
+    }
+    Ok(true)
+}
+
+// Replace `path` through a temporary file
+// Returns Ok(false) without touching `path` when an atomic replace would lose
+// the owner, or the directory cannot hold a temporary file (e.g. sandboxed
+// access to a single file)
+fn replace_atomically(
+    path: &Path,
+    content: &[u8],
+    existing: Option<&Metadata>,
+) -> Result<bool, String> {
+    let (temp, file) = match create_temp(path, existing.is_some()) {
+        Ok(temp) => temp,
+        Err(e) if e.kind() == ErrorKind::PermissionDenied => return Ok(false),
+        Err(e) => return Err(format!("Failed to create temporary file: {}", e)),
+    };
+
+    match write_and_rename(file, &temp, path, content, existing) {
+        Ok(true) => Ok(true),
+        Ok(false) => {
+            let _ = fs::remove_file(&temp);
+            Ok(false)
+        }
+        Err(e) => {
+            let _ = fs::remove_file(&temp);
+            Err(format!("Failed to write file: {}", e))
+        }
+    }
+}
+
+// Fail if the user may not write to the existing file
+// The read-only flag is checked as well, as opening succeeds for root
+fn check_writable(path: &Path, metadata: &Metadata) -> Result<(), String> {
+    let denied = || format!("{} is read-only", path.display());
+    if metadata.permissions().readonly() {
+        return Err(denied());
+    }
+    match OpenOptions::new().write(true).open(path) {
+        Ok(_) => Ok(()),
+        Err(e) if e.kind() == ErrorKind::PermissionDenied => Err(denied()),
+        Err(e) => Err(format!("Failed to open file for writing: {}", e)),
+    }
+}
+
+// Overwrite in place, for files an atomic replace cannot preserve
+fn write_in_place(path: &Path, content: &[u8]) -> Result<(), String> {
+    let mut file = OpenOptions::new()
+        .write(true)
+        .truncate(true)
+        .create(true)
+        .open(path)
+        .map_err(|e| format!("Failed to write file: {}", e))?;
+    file.write_all(content)
+        .and_then(|_| file.sync_all())
+        .map_err(|e| format!("Failed to write file: {}", e))
+}
+
+// Save `content` to `file_path`; returns { path, size, modified, hash, backup }
+pub fn save_file(
+    file_path: &str,
+    content: &[u8],
+    options: &SaveOptions,
+) -> Result<serde_json::Value, String> {
+    // Save through symlinks so the link itself stays a link
+    let path = match fs::symlink_metadata(file_path) {
+        Ok(metadata) if metadata.file_type().is_symlink() => fs::canonicalize(file_path)
+            .map_err(|e| format!("Failed to resolve symlink {}: {}", file_path, e))?,
+        _ => PathBuf::from(file_path),
+    };
+
+    let existing = match fs::metadata(&path) {
+        Ok(metadata) => Some(metadata),
+        Err(e) if e.kind() == ErrorKind::NotFound => None,
+        Err(e) => return Err(format!("Failed to get file metadata: {}", e)),
+    };
+    check_conflict(&path, existing.as_ref(), options)?;
+    if let Some(metadata) = &existing {
+        check_writable(&path, metadata)?;
+    }
+
+    let backup = match &existing {
+        Some(_) if options.backup => {
+            let mut backup = path.clone().into_os_string();
+            backup.push(".bak");
+            let backup = PathBuf::from(backup);
+            fs::copy(&path, &backup).map_err(|e| format!("Failed to write backup: {}", e))?;
+            Some(backup)
+        }
+        _ => None,
+    };
+
+    let atomic = match &existing {
+        Some(metadata) if has_hard_links(metadata) => false,
+        _ => replace_atomically(&path, content, existing.as_ref())?,
+    };
+    if !atomic {
+        write_in_place(&path, content)?;
+    }
+    println!(
+        "[Save] Saved {} ({} bytes, {})",
+        path.display(),
+        content.len(),
+        if atomic { "atomic" } else { "in place" }
+    );
+
+    let modified = fs::metadata(&path).ok().as_ref().and_then(modified_nanos);
+    let hash = hashing::hash_bytes(&[SAVE_HASH], content).remove(0).hex;
+    Ok(json!({
+        "path": path,
+        "size": content.len(),
+        "modified": modified,
+        "hash": hash,
+        "backup": backup
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("tidycode-save-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_save_replaces_file_and_keeps_backup() {
+        let dir = temp_dir("replace");
+        let path = dir.join("notes.txt");
+        fs::write(&path, "old").unwrap();
+
+        let options = SaveOptions {
+            backup: true,
+            ..Default::default()
+        };
+        let result = save_file(path.to_str().unwrap(), b"new", &options).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new");
+        assert_eq!(
+            fs::read_to_string(dir.join("notes.txt.bak")).unwrap(),
+            "old"
+        );
+        assert_eq!(result["size"], 3);
+
+        // Only the file and its backup; no temporary file left behind
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 2);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_conflicting_save_is_refused() {
+        let dir = temp_dir("conflict");
+        let path = dir.join("data.json");
+        let file_path = path.to_str().unwrap();
+        let saved = save_file(file_path, b"{}", &SaveOptions::default()).unwrap();
+        let hash = saved["hash"].as_str().unwrap().to_string();
+
+        fs::write(&path, "{\"changed\": true}").unwrap();
+        let options = SaveOptions {
+            expected_hash: Some(hash),
+            ..Default::default()
+        };
+        let error = save_file(file_path, b"[]", &options).unwrap_err();
+        assert!(error.starts_with(CONFLICT_ERROR));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "{\"changed\": true}");
+
+        fs::remove_file(&path).unwrap();
+        let options = SaveOptions {
+            expected_modified: Some("0".to_string()),
+            ..Default::default()
+        };
+        assert!(save_file(file_path, b"[]", &options)
+            .unwrap_err()
+            .contains("was deleted"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_modified_in_the_same_second_is_a_conflict() {
+        use std::time::Duration;
+        let dir = temp_dir("same-second");
+        let path = dir.join("log.txt");
+        let file_path = path.to_str().unwrap();
+        fs::write(&path, "one").unwrap();
+        let file = File::options().write(true).open(&path).unwrap();
+        file.set_modified(UNIX_EPOCH + Duration::new(1_700_000_000, 100))
+            .unwrap();
+        let opened = modified_nanos(&fs::metadata(&path).unwrap()).unwrap();
+
+        // Changed again later within the same second
+        fs::write(&path, "two").unwrap();
+        file.set_modified(UNIX_EPOCH + Duration::new(1_700_000_000, 900_000))
+            .unwrap();
+        let options = SaveOptions {
+            expected_modified: Some(opened),
+            ..Default::default()
+        };
+        assert!(save_file(file_path, b"three", &options)
+            .unwrap_err()
+            .starts_with(CONFLICT_ERROR));
+
+        let current = modified_nanos(&fs::metadata(&path).unwrap()).unwrap();
+        let options = SaveOptions {
+            expected_modified: Some(current),
+            ..Default::default()
+        };
+        let saved = save_file(file_path, b"three", &options).unwrap();
+        assert!(saved["modified"].is_string());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_read_only_file_is_refused() {
+        let dir = temp_dir("readonly");
+        let path = dir.join("locked.txt");
+        fs::write(&path, "old").unwrap();
+        let mut permissions = fs::metadata(&path).unwrap().permissions();
+        permissions.set_readonly(true);
+        fs::set_permissions(&path, permissions.clone()).unwrap();
+
+        let error = save_file(path.to_str().unwrap(), b"new", &SaveOptions::default()).unwrap_err();
+        assert!(error.contains("read-only"), "{}", error);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "old");
+
+        #[allow(clippy::permissions_set_readonly_false)]
+        permissions.set_readonly(false);
+        fs::set_permissions(&path, permissions).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_save_keeps_mode_and_symlink() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = temp_dir("symlink");
+        let target = dir.join("script.sh");
+        let link = dir.join("link.sh");
+        fs::write(&target, "echo old").unwrap();
+        fs::set_permissions(&target, fs::Permissions::from_mode(0o754)).unwrap();
+        std::os::unix::fs::symlink(&target, &link).unwrap();
+
+        save_file(link.to_str().unwrap(), b"echo new", &SaveOptions::default()).unwrap();
+        assert!(fs::symlink_metadata(&link)
+            .unwrap()
+            .file_type()
+            .is_symlink());
+        assert_eq!(fs::read_to_string(&target).unwrap(), "echo new");
+        assert_eq!(
+            fs::metadata(&target).unwrap().permissions().mode() & 0o777,
+            0o754
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_temp_file_starts_private() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = temp_dir("private");
+        let path = dir.join("secret.env");
+
+        let (temp, _file) = create_temp(&path, true).unwrap();
+        assert_eq!(
+            fs::metadata(&temp).unwrap().permissions().mode() & 0o777,
+            0o600
+        );
+
+        // Saving keeps an owner-only file owner-only
+        fs::write(&path, "KEY=old").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o600)).unwrap();
+        save_file(path.to_str().unwrap(), b"KEY=new", &SaveOptions::default()).unwrap();
+        assert_eq!(
+            fs::metadata(&path).unwrap().permissions().mode() & 0o777,
+            0o600
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,376 @@
+use crate::file_buffer::FileBuffer;
+use crate::log_index::{parse_iso_prefix, parse_syslog_prefix};
+use crate::segments::is_char_start;
+use serde::{Deserialize, Serialize};
+
+/// Upper bounds (in characters) of the line-length histogram buckets
+const LENGTH_BUCKETS: [usize; 8] = [0, 40, 80, 120, 200, 500, 1000, 10000];
+
+/// Number of leading non-blank lines used to guess the format
+const FORMAT_SAMPLE_LINES: usize = 200;
+
+/// Whole-document JSON is only validated up to this size
+const JSON_VALIDATE_LIMIT: usize = 16 * 1024 * 1024;
+
+/// Text statistics for the status bar and large-file warnings
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct TextAnalysis {
+    pub size: usize,
+    pub line_count: usize,
+    pub char_count: usize,
+    pub word_count: usize,
+    pub non_blank_lines: usize,
+    pub longest_line: usize, // Line number (1-based), 0 for an empty file
+    pub longest_line_length: usize, // In characters, line ending excluded
+    pub line_length_histogram: Vec<LengthBucket>,
+    pub indentation: Indentation,
+    pub trailing_whitespace_lines: usize,
+    pub crlf_lines: usize,
+    pub format: String,
+}
+
+/// Lines whose length falls in `min..=max` (no max for the last bucket)
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct LengthBucket {
+    pub min: usize,
+    pub max: Option<usize>,
+    pub count: usize,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum IndentStyle {
+    None,
+    Tabs,
+    Spaces,
+    Mixed,
+}
+
+/// Detected indentation; `width` is the usual step for space indentation
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Indentation {
+    pub style: IndentStyle,
+    pub width: Option<usize>,
+    pub tab_lines: usize,
+    pub space_lines: usize,
+}
+
+/// Accumulates indentation evidence from non-blank lines
+#[derive(Default)]
+pub struct IndentDetector {
+    tab_lines: usize,
+    space_lines: usize,
+    steps: [usize; 9], // Count of indent increases by 1..=8 spaces
+    previous: usize,
+}
+
+impl IndentDetector {
+    /// Record a non-blank line (without its line ending)
+    pub fn observe(&mut self, line: &[u8]) {
+        if line.first() == Some(&b'\t') {
+            self.tab_lines += 1;
+            return;
+        }
+
+        let indent = line.iter().take_while(|&&b| b == b' ').count();
+        if indent > 0 {
+            self.space_lines += 1;
+            if indent > self.previous && indent - self.previous <= 8 {
+                self.steps[indent - self.previous] += 1;
+            }
+        }
+        self.previous = indent;
+    }
+
+    pub fn finish(self) -> Indentation {
+        let style = match (self.tab_lines, self.space_lines) {
+            (0, 0) => IndentStyle::None,
+            (t, s) if s * 9 <= t => IndentStyle::Tabs,
+            (t, s) if t * 9 <= s => IndentStyle::Spaces,
+            _ => IndentStyle::Mixed,
+        };
+        let width = match style {
+            IndentStyle::Spaces | IndentStyle::Mixed => self
+                .steps
+                .iter()
+                .enumerate()
+                .skip(1)
+                .filter(|(_, &count)| count > 0)
+                .max_by_key(|(step, &count)| (count, *step))
+                .map(|(step, _)| step),
+            _ => None,
+        };
+
+        Indentation {
+            style,
+            width,
+            tab_lines: self.tab_lines,
+            space_lines: self.space_lines,
+        }
+    }
+}
+
+fn histogram_buckets() -> Vec<LengthBucket> {
+    let mut buckets = Vec::with_capacity(LENGTH_BUCKETS.len() + 1);
+    let mut min = 0;
+    for &max in &LENGTH_BUCKETS {
+        buckets.push(LengthBucket {
+            min,
+            max: Some(max),
+            count: 0,
+        });
+        min = max + 1;
+    }
+    buckets.push(LengthBucket {
+        min,
+        max: None,
+        count: 0,
+    });
+    buckets
+}
+
+/// Compute text statistics in a single pass over the buffer
+pub fn analyze(buffer: &FileBuffer) -> TextAnalysis {
+    let content = &buffer.content;
+    let line_count = buffer.line_count();
+
+    let mut histogram = histogram_buckets();
+    let mut char_count = 0;
+    let mut word_count = 0;
+    let mut non_blank_lines = 0;
+    let mut longest = (0, 0);
+    let mut trailing_whitespace_lines = 0;
+    let mut crlf_lines = 0;
+
+    let mut indentation = IndentDetector::default();
+
+    for line_num in 1..=line_count {
+        let (start, end) = buffer.get_line_byte_range(line_num).unwrap_or((0, 0));
+        let raw = &content[start..end];
+        char_count += raw.iter().filter(|&&b| is_char_start(b)).count();
+
+        let mut line = raw.strip_suffix(b"\n").unwrap_or(raw);
+        if let Some(stripped) = line.strip_suffix(b"\r") {
+            line = stripped;
+            crlf_lines += 1;
+        }
+
+        let length = line.iter().filter(|&&b| is_char_start(b)).count();
+        if length > longest.1 || longest.0 == 0 {
+            longest = (line_num, length);
+        }
+        let bucket = LENGTH_BUCKETS
+            .iter()
+            .position(|&max| length <= max)
+            .unwrap_or(LENGTH_BUCKETS.len());
+        histogram[bucket].count += 1;
+
+        let mut in_word = false;
+        for &b in line {
+            let space = b.is_ascii_whitespace();
+            if !space && !in_word {
+                word_count += 1;
+            }
+            in_word = !space;
+        }
+
+        if line.iter().all(|b| b.is_ascii_whitespace()) {
+            continue;
+        }
+        non_blank_lines += 1;
+
+        if line.last().is_some_and(|b| *b == b' ' || *b == b'\t') {
+            trailing_whitespace_lines += 1;
+        }
+
+        indentation.observe(line);
+    }
+
+    TextAnalysis {
+        size: content.len(),
+        line_count,
+        char_count,
+        word_count,
+        non_blank_lines,
+        longest_line: longest.0,
+        longest_line_length: longest.1,
+        line_length_histogram: histogram,
+        indentation: indentation.finish(),
+        trailing_whitespace_lines,
+        crlf_lines,
+        format: detect_format(buffer).to_string(),
+    }
+}
+
+/// Guess the document format from its content
+/// Returns one of json, jsonl, xml, log, yaml, toml, csv, tsv, markdown,
+/// binary or text
+pub fn detect_format(buffer: &FileBuffer) -> &'static str {
+    let content = &buffer.content;
+    let head = &content[..content.len().min(64 * 1024)];
+    if head.contains(&0) {
+        return "binary";
+    }
+
+    let sample: Vec<&[u8]> = (1..=buffer.line_count())
+        .filter_map(|n| buffer.get_line_byte_range(n).ok())
+        .map(|(start, end)| trim(&content[start..end]))
+        .filter(|line| !line.is_empty())
+        .take(FORMAT_SAMPLE_LINES)
+        .collect();
+    let Some(first) = sample.first() else {
+        return "text";
+    };
+    let share = |matches: &dyn Fn(&[u8]) -> bool| {
+        sample.iter().filter(|l| matches(l)).count() * 10 >= sample.len() * 8
+    };
+
+    if first.starts_with(b"{") || first.starts_with(b"[") {
+        let whole_document = content.len() <= JSON_VALIDATE_LIMIT
+            && serde_json::from_slice::<serde_json::Value>(content).is_ok();
+        if whole_document {
+            return "json";
+        }
+        if share(&|l| l.starts_with(b"{") && serde_json::from_slice::<serde_json::Value>(l).is_ok())
+        {
+            return "jsonl";
+        }
+        if content.len() > JSON_VALIDATE_LIMIT {
+            return "json";
+        }
+    }
+
+    if first.starts_with(b"<?xml") || (first.starts_with(b"<") && head.contains(&b'>')) {
+        return "xml";
+    }
+
+    let log_lines = sample
+        .iter()
+        .filter(|l| parse_iso_prefix(l).is_some() || parse_syslog_prefix(l, 1970).is_some())
+        .count();
+    if log_lines * 2 >= sample.len() {
+        return "log";
+    }
+
+    if share(&|l| {
+        l.starts_with(b"#") || (l.starts_with(b"[") && l.ends_with(b"]")) || is_toml_assignment(l)
+    }) && sample.iter().any(|l| is_toml_assignment(l))
+    {
+        return "toml";
+    }
+
+    if first.starts_with(b"---")
+        || share(&|l| l.starts_with(b"#") || l.starts_with(b"- ") || is_yaml_key(l))
+    {
+        return "yaml";
+    }
+
+    if sample.len() >= 2 {
+        for (delimiter, name) in [(b'\t', "tsv"), (b',', "csv")] {
+            let count = |l: &[u8]| l.iter().filter(|&&b| b == delimiter).count();
+            let columns = count(first);
+            if columns > 0 && sample.iter().all(|l| count(l) == columns) {
+                return name;
+            }
+        }
+    }
+
+    if sample
+        .iter()
+        .any(|l| l.starts_with(b"# ") || l.starts_with(b"## ") || l.starts_with(b"```"))
+    {
+        return "markdown";
+    }
+
+    "text"
+}
+
+fn trim(bytes: &[u8]) -> &[u8] {
+    let start = bytes
+        .iter()
+        .position(|b| !b.is_ascii_whitespace())
+        .unwrap_or(bytes.len());
+    let end = bytes
+        .iter()
+        .rposition(|b| !b.is_ascii_whitespace())
+        .map_or(start, |i| i + 1);
+    &bytes[start..end]
+}
+
+/// `key = value` with a bare or quoted key
+fn is_toml_assignment(line: &[u8]) -> bool {
+    let Some(eq) = line.iter().position(|&b| b == b'=') else {
+        return false;
+    };
+    let key = trim(&line[..eq]);
+    !key.is_empty()
+        && key
+            .iter()
+            .all(|&b| b.is_ascii_alphanumeric() || matches!(b, b'_' | b'-' | b'.' | b'"'))
+}
+
+/// `key:` or `key: value`
+fn is_yaml_key(line: &[u8]) -> bool {
+    let Some(colon) = line.iter().position(|&b| b == b':') else {
+        return false;
+    };
+    let key = &line[..colon];
+    !key.is_empty()
+        && key
+            .iter()
+            .all(|&b| b.is_ascii_alphanumeric() || matches!(b, b'_' | b'-' | b'.' | b'"' | b'\''))
+        && matches!(line.get(colon + 1), None | Some(b' '))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn analyze_text(text: &str) -> TextAnalysis {
+        analyze(&FileBuffer::new(text.as_bytes().to_vec()).unwrap())
+    }
+
+    #[test]
+    fn test_counts_and_indentation() {
+        let stats =
+            analyze_text("fn main() {\n    let x = 1; \n\n    if x {\n        go();\n    }\n}\n");
+
+        assert_eq!(stats.line_count, 7);
+        assert_eq!(stats.non_blank_lines, 6);
+        assert_eq!(stats.word_count, 13);
+        assert_eq!(stats.trailing_whitespace_lines, 1);
+        assert_eq!((stats.longest_line, stats.longest_line_length), (2, 15));
+        assert_eq!(stats.indentation.style, IndentStyle::Spaces);
+        assert_eq!(stats.indentation.width, Some(4));
+        assert_eq!(stats.line_length_histogram[1].count, 6);
+        assert_eq!(stats.line_length_histogram[0].count, 1);
+    }
+
+    #[test]
+    fn test_tabs_crlf_and_chars() {
+        let stats = analyze_text("a\r\n\tné\r\n\tz\r\n");
+
+        assert_eq!(stats.char_count, 12);
+        assert_eq!(stats.crlf_lines, 3);
+        assert_eq!(stats.indentation.style, IndentStyle::Tabs);
+        assert_eq!(stats.indentation.width, None);
+    }
+
+    #[test]
+    fn test_detect_format() {
+        let format = |text: &str| analyze_text(text).format;
+
+        assert_eq!(format("{\"a\": [1, 2]}"), "json");
+        assert_eq!(format("{\"a\": 1}\n{\"a\": 2}\n"), "jsonl");
+        assert_eq!(format("<?xml version=\"1.0\"?>\n<root/>"), "xml");
+        assert_eq!(
+            format("2024-01-02T03:04:05Z INFO start\n2024-01-02T03:04:06Z WARN slow\n"),
+            "log"
+        );
+        assert_eq!(format("[server]\nport = 80\nhost = \"x\"\n"), "toml");
+        assert_eq!(format("server:\n  port: 80\n"), "yaml");
+        assert_eq!(format("a,b,c\n1,2,3\n"), "csv");
+        assert_eq!(format("# Title\n\nSome text.\n"), "markdown");
+        assert_eq!(format("just some words\n"), "text");
+    }
+}
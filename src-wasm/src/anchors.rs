@@ -0,0 +1,182 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+/// Which side of an insertion exactly at the anchor it stays on
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Gravity {
+    Left,  // Stays before text inserted at its offset
+    Right, // Moves after text inserted at its offset
+}
+
+impl FromStr for Gravity {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "left" => Ok(Gravity::Left),
+            "right" => Ok(Gravity::Right),
+            _ => Err(format!("Unknown gravity '{}' (expected left or right)", s)),
+        }
+    }
+}
+
+/// A byte offset that follows edits; also the session restore format
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Anchor {
+    pub id: u32,
+    pub offset: usize,
+    pub gravity: Gravity,
+}
+
+/// Anchors of one buffer, kept across edits (unlike the derived indexes)
+#[derive(Default)]
+pub struct AnchorSet {
+    anchors: BTreeMap<u32, Anchor>,
+    deleted: Vec<u32>, // Removed by edits since the last take_deleted
+    next_id: u32,
+}
+
+impl AnchorSet {
+    /// Add an anchor at an offset already known to be in range
+    pub fn create(&mut self, offset: usize, gravity: Gravity) -> u32 {
+        self.next_id += 1;
+        let id = self.next_id;
+        self.anchors.insert(
+            id,
+            Anchor {
+                id,
+                offset,
+                gravity,
+            },
+        );
+        id
+    }
+
+    pub fn get(&self, id: u32) -> Option<&Anchor> {
+        self.anchors.get(&id)
+    }
+
+    pub fn remove(&mut self, id: u32) -> bool {
+        self.anchors.remove(&id).is_some()
+    }
+
+    /// All anchors in creation order
+    pub fn list(&self) -> Vec<Anchor> {
+        self.anchors.values().cloned().collect()
+    }
+
+    /// Replace every anchor with a saved set, keeping their IDs
+    pub fn restore(&mut self, anchors: Vec<Anchor>) {
+        self.anchors = anchors.into_iter().map(|a| (a.id, a)).collect();
+        self.deleted.clear();
+        self.next_id = self.anchors.keys().max().copied().unwrap_or(0);
+    }
+
+    /// Drop every anchor, reporting them as deleted
+    /// Used when the content is replaced wholesale
+    pub fn clear(&mut self) {
+        let ids = std::mem::take(&mut self.anchors).into_keys();
+        self.deleted.extend(ids);
+    }
+
+    /// IDs of anchors removed by edits since the last call
+    pub fn take_deleted(&mut self) -> Vec<u32> {
+        std::mem::take(&mut self.deleted)
+    }
+
+    /// Follow `start..end` being replaced by `inserted` bytes:
+    /// anchors before the edit (or at the start of a replacement) stay,
+    /// anchors at or after `end` shift, and anchors strictly inside the
+    /// removed range are deleted. An insertion exactly at an anchor is
+    /// resolved by its gravity. Undo does not bring deleted anchors back.
+    pub fn adjust(&mut self, start: usize, end: usize, inserted: usize) {
+        let deleted = &mut self.deleted;
+        self.anchors.retain(|&id, anchor| {
+            let offset = anchor.offset;
+            if offset < start
+                || (offset == start && (start < end || anchor.gravity == Gravity::Left))
+            {
+                true
+            } else if offset >= end {
+                anchor.offset = offset - end + start + inserted;
+                true
+            } else {
+                deleted.push(id);
+                false
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file_buffer::FileBuffer;
+
+    #[test]
+    fn test_gravity_on_insert() {
+        let mut set = AnchorSet::default();
+        let left = set.create(5, Gravity::Left);
+        let right = set.create(5, Gravity::Right);
+        let before = set.create(2, Gravity::Right);
+
+        set.adjust(5, 5, 3);
+        assert_eq!(set.get(left).unwrap().offset, 5);
+        assert_eq!(set.get(right).unwrap().offset, 8);
+        assert_eq!(set.get(before).unwrap().offset, 2);
+        assert!(set.take_deleted().is_empty());
+    }
+
+    #[test]
+    fn test_replacement_deletes_inner_anchors() {
+        let mut buffer = FileBuffer::new(b"one two three\n".to_vec()).unwrap();
+        let at_start = buffer.anchors.create(4, Gravity::Right);
+        let inside = buffer.anchors.create(5, Gravity::Left);
+        let at_end = buffer.anchors.create(7, Gravity::Left);
+        let after = buffer.anchors.create(8, Gravity::Left);
+
+        // "two" -> "2"
+        buffer.replace_range(4, 7, b"2").unwrap();
+        assert_eq!(buffer.anchors.get(at_start).unwrap().offset, 4);
+        assert!(buffer.anchors.get(inside).is_none());
+        assert_eq!(buffer.anchors.get(at_end).unwrap().offset, 5);
+        assert_eq!(buffer.anchors.get(after).unwrap().offset, 6);
+        assert_eq!(buffer.anchors.take_deleted(), vec![inside]);
+
+        // Undo shifts the survivors back
+        buffer.undo();
+        assert_eq!(buffer.anchors.get(after).unwrap().offset, 8);
+    }
+
+    #[test]
+    fn test_replace_content_reports_deleted_anchors() {
+        // Patches and conflict resolutions rewrite buffers this way
+        let mut buffer = FileBuffer::new(b"keep\nold line\nkeep\n".to_vec()).unwrap();
+        let before = buffer.anchors.create(2, Gravity::Left);
+        let inside = buffer.anchors.create(9, Gravity::Left);
+        let after = buffer.anchors.create(16, Gravity::Left);
+
+        buffer.replace_content(b"keep\nnew\nkeep\n").unwrap();
+        assert_eq!(buffer.anchors.get(before).unwrap().offset, 2);
+        assert_eq!(buffer.anchors.get(after).unwrap().offset, 11);
+        assert_eq!(buffer.anchors.take_deleted(), vec![inside]);
+    }
+
+    #[test]
+    fn test_restore_keeps_ids() {
+        let mut set = AnchorSet::default();
+        set.create(1, Gravity::Left);
+        let saved = serde_json::to_string(&set.list()).unwrap();
+        assert_eq!(saved, r#"[{"id":1,"offset":1,"gravity":"left"}]"#);
+
+        let mut restored = AnchorSet::default();
+        restored.restore(serde_json::from_str(&saved).unwrap());
+        assert_eq!(restored.list(), set.list());
+        assert_eq!(restored.create(0, Gravity::Right), 2);
+
+        restored.clear();
+        assert_eq!(restored.take_deleted(), vec![1, 2]);
+    }
+}
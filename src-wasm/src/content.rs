@@ -0,0 +1,79 @@
+use std::fmt;
+use std::ops::Deref;
+
+/// Bytes of a buffer: owned, or a read-only memory map of a file (native
+/// `mmap` feature). A mapped buffer is copied into memory on its first edit.
+pub enum Content {
+    Owned(Vec<u8>),
+    #[cfg(feature = "mmap")]
+    Mapped(memmap2::Mmap),
+}
+
+impl Content {
+    /// The owned bytes, copying a mapped file first
+    pub fn to_mut(&mut self) -> &mut Vec<u8> {
+        #[cfg(feature = "mmap")]
+        if let Content::Mapped(map) = self {
+            *self = Content::Owned(map.to_vec());
+        }
+        match self {
+            Content::Owned(bytes) => bytes,
+            #[cfg(feature = "mmap")]
+            Content::Mapped(_) => unreachable!(),
+        }
+    }
+
+    pub fn into_vec(self) -> Vec<u8> {
+        match self {
+            Content::Owned(bytes) => bytes,
+            #[cfg(feature = "mmap")]
+            Content::Mapped(map) => map.to_vec(),
+        }
+    }
+
+    pub fn is_mapped(&self) -> bool {
+        !matches!(self, Content::Owned(_))
+    }
+}
+
+impl Default for Content {
+    fn default() -> Self {
+        Content::Owned(Vec::new())
+    }
+}
+
+impl From<Vec<u8>> for Content {
+    fn from(bytes: Vec<u8>) -> Self {
+        Content::Owned(bytes)
+    }
+}
+
+impl Deref for Content {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            Content::Owned(bytes) => bytes,
+            #[cfg(feature = "mmap")]
+            Content::Mapped(map) => map,
+        }
+    }
+}
+
+impl AsRef<[u8]> for Content {
+    fn as_ref(&self) -> &[u8] {
+        self
+    }
+}
+
+impl<T: AsRef<[u8]> + ?Sized> PartialEq<T> for Content {
+    fn eq(&self, other: &T) -> bool {
+        **self == *other.as_ref()
+    }
+}
+
+impl fmt::Debug for Content {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
@@ -0,0 +1,387 @@
+use crate::anchors::Gravity;
+use crate::errors::{to_js, ApiError};
+use crate::file_buffer::{EditResult, FileBuffer, FileInfo};
+use crate::hashing::{self, HashAlgorithm, HashResult};
+use crate::positions::Position;
+use crate::secrets::{self, SecretKind};
+use crate::snapshot;
+use crate::{
+    analysis, ensure_initialized, remove_buffer, store_buffer, with_stored_buffer, FILE_BUFFERS,
+};
+use std::str::FromStr;
+use wasm_bindgen::prelude::*;
+
+// Operations shared by Document and the ID-based functions in lib.rs
+
+pub(crate) fn file_info(buffer: &FileBuffer) -> FileInfo {
+    let stats = buffer.get_stats();
+    FileInfo {
+        size: stats.size,
+        line_count: stats.line_count,
+        encoding: "UTF-8".to_string(),
+        index_size: stats.index_size,
+    }
+}
+
+pub(crate) fn edit(
+    buffer: &mut FileBuffer,
+    start_byte: usize,
+    end_byte: usize,
+    text: &[u8],
+) -> Result<EditResult, ApiError> {
+    buffer.check_range(start_byte, end_byte)?;
+    buffer
+        .replace_range(start_byte, end_byte, text)
+        .map_err(ApiError::invalid_range)?;
+    Ok(EditResult {
+        first_changed_line: buffer.line_at_offset(start_byte).unwrap_or(1),
+        line_count: buffer.line_count(),
+        size: buffer.content.len(),
+    })
+}
+
+pub(crate) fn append(buffer: &mut FileBuffer, bytes: &[u8]) -> EditResult {
+    EditResult {
+        first_changed_line: buffer.append(bytes),
+        line_count: buffer.line_count(),
+        size: buffer.content.len(),
+    }
+}
+
+/// Parse option names such as hash algorithms or secret kinds
+pub(crate) fn parse_names<T: FromStr<Err = String>>(names: &[String]) -> Result<Vec<T>, ApiError> {
+    names
+        .iter()
+        .map(|name| name.parse())
+        .collect::<Result<Vec<T>, String>>()
+        .map_err(ApiError::invalid_argument)
+}
+
+pub(crate) fn hash(
+    buffer: &FileBuffer,
+    algorithms: &[String],
+    start_byte: Option<usize>,
+    end_byte: Option<usize>,
+) -> Result<Vec<HashResult>, ApiError> {
+    let algorithms: Vec<HashAlgorithm> = parse_names(algorithms)?;
+    let start = start_byte.unwrap_or(0);
+    let end = end_byte.unwrap_or(buffer.content.len());
+    buffer.check_range(start, end)?;
+    Ok(hashing::hash_bytes(
+        &algorithms,
+        &buffer.content[start..end],
+    ))
+}
+
+pub(crate) fn create_anchor(
+    buffer: &mut FileBuffer,
+    byte_offset: usize,
+    gravity: &str,
+) -> Result<u32, ApiError> {
+    let gravity: Gravity = gravity.parse().map_err(ApiError::invalid_argument)?;
+    if byte_offset > buffer.content.len() {
+        return Err(ApiError::invalid_range(format!(
+            "Byte offset {} out of range (file has {} bytes)",
+            byte_offset,
+            buffer.content.len()
+        )));
+    }
+    Ok(buffer.anchors.create(byte_offset, gravity))
+}
+
+pub(crate) fn position_at_byte(
+    buffer: &mut FileBuffer,
+    byte_offset: usize,
+) -> Result<Position, ApiError> {
+    buffer.char_index();
+    let index = buffer.char_index.as_ref().unwrap();
+    index
+        .at_byte(buffer, byte_offset)
+        .map_err(ApiError::invalid_range)
+}
+
+pub(crate) fn anchor_position(
+    buffer: &mut FileBuffer,
+    anchor_id: u32,
+) -> Result<Position, ApiError> {
+    let offset = buffer
+        .anchors
+        .get(anchor_id)
+        .map(|anchor| anchor.offset)
+        .ok_or_else(|| ApiError::not_found(format!("Anchor {} not found", anchor_id)))?;
+    position_at_byte(buffer, offset)
+}
+
+/// An open text document: a typed handle over a buffer in the ID registry
+///
+/// The object-style alternative to file IDs: errors are thrown as `Error`
+/// objects with a `code` (NotFound, InvalidRange, Utf8, Regex, Parse,
+/// InvalidArgument, Internal), and `free()` closes the buffer. The buffer
+/// lives in the registry like any other, so the memory budget, memory stats
+/// and search_all see it, and every function taking a file ID accepts
+/// `fileId`.
+#[wasm_bindgen]
+pub struct Document {
+    file_id: u32,
+}
+
+#[wasm_bindgen]
+impl Document {
+    #[wasm_bindgen(constructor)]
+    pub fn new(content: &[u8]) -> Result<Document, JsValue> {
+        let buffer = FileBuffer::new(content.to_vec()).map_err(ApiError::parse)?;
+        Ok(Document::store(buffer))
+    }
+
+    /// Restore a document from a snapshot made by `serialize`
+    pub fn restore(bytes: &[u8]) -> Result<Document, JsValue> {
+        let snapshot = snapshot::deserialize(bytes).map_err(ApiError::parse)?;
+        Ok(Document::store(snapshot.buffer))
+    }
+
+    /// Versioned binary snapshot (see serialize_buffer)
+    pub fn serialize(&self) -> Result<Vec<u8>, JsValue> {
+        Ok(self.with(|buffer| snapshot::serialize(buffer).map_err(ApiError::invalid_argument))?)
+    }
+
+    /// Take over a buffer of the ID registry, e.g. to migrate an open tab
+    /// The file ID stays valid until the document is freed, which closes it
+    pub fn from_file_id(file_id: u32) -> Result<Document, JsValue> {
+        ensure_initialized();
+
+        let buffers = FILE_BUFFERS.lock().unwrap();
+        if !buffers
+            .as_ref()
+            .is_some_and(|map| map.contains_key(&file_id))
+        {
+            return Err(ApiError::not_found(format!("File {} not found", file_id)).into());
+        }
+        Ok(Document { file_id })
+    }
+
+    /// Give the buffer back to the ID registry: it stays open after the
+    /// document is consumed, until free_file_buffer
+    pub fn into_file_id(self) -> u32 {
+        let file_id = self.file_id;
+        std::mem::forget(self);
+        file_id
+    }
+
+    /// ID of the buffer, for functions that take file IDs (merge, patch,
+    /// grep views, ...)
+    #[wasm_bindgen(getter)]
+    pub fn file_id(&self) -> u32 {
+        self.file_id
+    }
+
+    /// Size, line count, encoding and index size
+    pub fn info(&self) -> Result<JsValue, JsValue> {
+        Ok(self.with(|buffer| to_js(&file_info(buffer)))?)
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn line_count(&self) -> Result<usize, JsValue> {
+        Ok(self.with(|buffer| Ok(buffer.line_count()))?)
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn size(&self) -> Result<usize, JsValue> {
+        Ok(self.with(|buffer| Ok(buffer.content.len()))?)
+    }
+
+    /// Lines `start_line..=end_line` (1-based)
+    pub fn get_line_range(&self, start_line: usize, end_line: usize) -> Result<String, JsValue> {
+        Ok(self.with(|buffer| buffer.get_line_range(start_line, end_line))?)
+    }
+
+    pub fn get_content(&self) -> Result<String, JsValue> {
+        Ok(self.with(|buffer| buffer.content_string())?)
+    }
+
+    /// Regex search; up to `max_results` matches
+    pub fn search(&self, pattern: &str, max_results: usize) -> Result<JsValue, JsValue> {
+        Ok(self.with(|buffer| to_js(&buffer.search(pattern, max_results)?))?)
+    }
+
+    /// Replace `start_byte..end_byte` with `text` as one undoable edit
+    pub fn edit(
+        &mut self,
+        start_byte: usize,
+        end_byte: usize,
+        text: &str,
+    ) -> Result<JsValue, JsValue> {
+        Ok(self.with(|buffer| to_js(&edit(buffer, start_byte, end_byte, text.as_bytes())?))?)
+    }
+
+    pub fn append(&mut self, bytes: &[u8]) -> Result<JsValue, JsValue> {
+        Ok(self.with(|buffer| to_js(&append(buffer, bytes)))?)
+    }
+
+    /// Revert the last edit; null when there is nothing to undo
+    pub fn undo(&mut self) -> Result<JsValue, JsValue> {
+        Ok(self.with(|buffer| {
+            let result = buffer.undo().map(|line| edit_result(buffer, line));
+            to_js(&result)
+        })?)
+    }
+
+    /// Re-apply the last undone edit; null when there is nothing to redo
+    pub fn redo(&mut self) -> Result<JsValue, JsValue> {
+        Ok(self.with(|buffer| {
+            let result = buffer.redo().map(|line| edit_result(buffer, line));
+            to_js(&result)
+        })?)
+    }
+
+    pub fn validate_json(&self) -> Result<bool, JsValue> {
+        Ok(self.with(|buffer| buffer.validate_json().map(|_| true))?)
+    }
+
+    pub fn format_json(&self, indent: usize) -> Result<String, JsValue> {
+        Ok(self.with(|buffer| buffer.format_json(indent))?)
+    }
+
+    /// Text statistics and detected format
+    pub fn analyze(&self) -> Result<JsValue, JsValue> {
+        Ok(self.with(|buffer| to_js(&analysis::analyze(buffer)))?)
+    }
+
+    /// Line, column and char/UTF-16 offsets at a byte offset
+    pub fn position_at_byte(&mut self, byte_offset: usize) -> Result<JsValue, JsValue> {
+        Ok(self.with(|buffer| to_js(&position_at_byte(buffer, byte_offset)?))?)
+    }
+
+    /// Digests of the whole document or of `start_byte..end_byte`
+    pub fn hash(
+        &self,
+        algorithms: Vec<String>,
+        start_byte: Option<usize>,
+        end_byte: Option<usize>,
+    ) -> Result<JsValue, JsValue> {
+        Ok(self.with(|buffer| to_js(&hash(buffer, &algorithms, start_byte, end_byte)?))?)
+    }
+
+    /// Secrets and personal data, optionally limited to some kinds
+    pub fn scan_secrets(&self, kinds: Vec<String>) -> Result<JsValue, JsValue> {
+        let kinds: Vec<SecretKind> = parse_names(&kinds)?;
+        Ok(self.with(|buffer| to_js(&secrets::scan(&buffer.content, &kinds)))?)
+    }
+
+    /// Anchor at a byte offset that follows edits ("left" or "right" gravity)
+    pub fn create_anchor(&mut self, byte_offset: usize, gravity: &str) -> Result<u32, JsValue> {
+        Ok(self.with(|buffer| create_anchor(buffer, byte_offset, gravity))?)
+    }
+
+    pub fn anchor_position(&mut self, anchor_id: u32) -> Result<JsValue, JsValue> {
+        Ok(self.with(|buffer| to_js(&anchor_position(buffer, anchor_id)?))?)
+    }
+
+    pub fn delete_anchor(&mut self, anchor_id: u32) -> Result<bool, JsValue> {
+        Ok(self.with(|buffer| Ok(buffer.anchors.remove(anchor_id)))?)
+    }
+
+    /// IDs of anchors deleted by edits since the last call
+    pub fn take_deleted_anchors(&mut self) -> Result<Vec<u32>, JsValue> {
+        Ok(self.with(|buffer| Ok(buffer.anchors.take_deleted()))?)
+    }
+}
+
+impl Document {
+    fn store(buffer: FileBuffer) -> Document {
+        ensure_initialized();
+        Document {
+            file_id: store_buffer(buffer),
+        }
+    }
+
+    /// Run `op` on the buffer; NotFound once it was closed by file ID
+    fn with<T>(
+        &self,
+        op: impl FnOnce(&mut FileBuffer) -> Result<T, ApiError>,
+    ) -> Result<T, ApiError> {
+        with_stored_buffer(self.file_id, op)
+    }
+}
+
+/// Closes the buffer when JS calls `free()` (or the document is collected)
+impl Drop for Document {
+    fn drop(&mut self) {
+        remove_buffer(self.file_id);
+    }
+}
+
+fn edit_result(buffer: &FileBuffer, first_changed_line: usize) -> EditResult {
+    EditResult {
+        first_changed_line,
+        line_count: buffer.line_count(),
+        size: buffer.content.len(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::ErrorCode;
+
+    #[test]
+    fn test_error_codes() {
+        let mut buffer = FileBuffer::new(b"{\"a\": 1}\nline 2\n".to_vec()).unwrap();
+
+        let code = |result: Result<(), ApiError>| result.unwrap_err().code;
+        assert_eq!(
+            code(buffer.get_line_range(0, 1).map(drop)),
+            ErrorCode::InvalidRange
+        );
+        assert_eq!(code(buffer.search("(", 1).map(drop)), ErrorCode::Regex);
+        assert_eq!(code(buffer.validate_json()), ErrorCode::Parse);
+        assert_eq!(
+            code(edit(&mut buffer, 5, 99, b"x").map(drop)),
+            ErrorCode::InvalidRange
+        );
+        assert_eq!(
+            code(hash(&buffer, &["md4".to_string()], None, None).map(drop)),
+            ErrorCode::InvalidArgument
+        );
+        assert_eq!(
+            code(anchor_position(&mut buffer, 3).map(drop)),
+            ErrorCode::NotFound
+        );
+
+        let invalid = FileBuffer::new(vec![b'a', 0xff, b'\n']).unwrap();
+        assert_eq!(code(invalid.content_string().map(drop)), ErrorCode::Utf8);
+    }
+
+    #[test]
+    fn test_shared_operations() {
+        let mut buffer = FileBuffer::new(b"one\ntwo\n".to_vec()).unwrap();
+        let result = edit(&mut buffer, 4, 7, b"2\nthree").unwrap();
+        assert_eq!(result.first_changed_line, 2);
+        assert_eq!(result.line_count, 3);
+        assert_eq!(file_info(&buffer).size, 12);
+
+        let anchor = create_anchor(&mut buffer, 4, "left").unwrap();
+        append(&mut buffer, b"four\n");
+        assert_eq!(anchor_position(&mut buffer, anchor).unwrap().line, 2);
+    }
+
+    #[test]
+    fn test_document_lives_in_registry() {
+        let document = Document::new(b"one\ntwo\n").unwrap();
+        let file_id = document.file_id();
+        let stats = |id: u32| {
+            let buffers = FILE_BUFFERS.lock().unwrap();
+            let stats = buffers.as_ref().unwrap().stats();
+            stats.buffers.into_iter().find(|m| m.file_id == id)
+        };
+        assert_eq!(stats(file_id).unwrap().size, 8);
+        assert_eq!(document.line_count().unwrap(), 2);
+
+        // Freeing the document closes the buffer; into_file_id keeps it open
+        drop(document);
+        assert!(stats(file_id).is_none());
+        let file_id = Document::new(b"x").unwrap().into_file_id();
+        assert!(stats(file_id).is_some());
+        assert_eq!(Document::from_file_id(file_id).unwrap().size().unwrap(), 1);
+        assert!(stats(file_id).is_none());
+    }
+}
@@ -0,0 +1,426 @@
+use base64::alphabet;
+use base64::engine::{DecodePaddingMode, GeneralPurpose, GeneralPurposeConfig};
+use base64::Engine;
+use flate2::read::{GzDecoder, ZlibDecoder};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+use std::io::{Read, Write};
+use std::str::FromStr;
+
+/// Quoted-printable lines are wrapped before this many characters
+const QP_LINE_LIMIT: usize = 76;
+
+/// Largest output a gzip/zlib stream may decompress to, so a small
+/// decompression bomb cannot exhaust the wasm heap
+const MAX_INFLATED_BYTES: u64 = 256 * 1024 * 1024;
+
+/// Reversible text encodings
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Codec {
+    Base64,
+    Base64Url,
+    Hex,
+    Url,  // Percent-encoding of everything but unreserved characters
+    Html, // Entities
+    Json, // String escapes, without surrounding quotes
+    QuotedPrintable,
+    GzipBase64, // Base64 of a gzip (or zlib) stream
+}
+
+impl FromStr for Codec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "base64" => Ok(Codec::Base64),
+            "base64url" => Ok(Codec::Base64Url),
+            "hex" => Ok(Codec::Hex),
+            "url" => Ok(Codec::Url),
+            "html" => Ok(Codec::Html),
+            "json" => Ok(Codec::Json),
+            "quoted-printable" | "qp" => Ok(Codec::QuotedPrintable),
+            "gzip-base64" => Ok(Codec::GzipBase64),
+            _ => Err(format!("Unknown encoding '{}'", s)),
+        }
+    }
+}
+
+/// Where a transformed range ended up, so the editor can reselect it
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct TransformResult {
+    pub start_byte: usize,
+    pub end_byte: usize,
+    pub line_count: usize,
+    pub size: usize,
+}
+
+/// Base64 engine that accepts input with or without padding
+fn base64_engine(url_safe: bool) -> GeneralPurpose {
+    let config = GeneralPurposeConfig::new()
+        .with_encode_padding(!url_safe)
+        .with_decode_padding_mode(DecodePaddingMode::Indifferent);
+    let alphabet = if url_safe {
+        &alphabet::URL_SAFE
+    } else {
+        &alphabet::STANDARD
+    };
+    GeneralPurpose::new(alphabet, config)
+}
+
+fn without_whitespace(input: &[u8]) -> Vec<u8> {
+    input
+        .iter()
+        .copied()
+        .filter(|b| !b.is_ascii_whitespace())
+        .collect()
+}
+
+fn hex_value(b: u8) -> Option<u8> {
+    (b as char).to_digit(16).map(|d| d as u8)
+}
+
+fn as_utf8(input: &[u8]) -> Result<&str, String> {
+    std::str::from_utf8(input).map_err(|e| format!("Selection is not valid UTF-8: {}", e))
+}
+
+/// Encode `input`
+pub fn encode(codec: Codec, input: &[u8]) -> Result<Vec<u8>, String> {
+    let encoded = match codec {
+        Codec::Base64 => base64_engine(false).encode(input).into_bytes(),
+        Codec::Base64Url => base64_engine(true).encode(input).into_bytes(),
+        Codec::Hex => input
+            .iter()
+            .flat_map(|b| format!("{:02x}", b).into_bytes())
+            .collect(),
+        Codec::Url => {
+            let mut out = Vec::with_capacity(input.len());
+            for &b in input {
+                if b.is_ascii_alphanumeric() || matches!(b, b'-' | b'_' | b'.' | b'~') {
+                    out.push(b);
+                } else {
+                    out.extend_from_slice(format!("%{:02X}", b).as_bytes());
+                }
+            }
+            out
+        }
+        Codec::Html => {
+            let mut out = Vec::with_capacity(input.len());
+            for &b in input {
+                match b {
+                    b'&' => out.extend_from_slice(b"&amp;"),
+                    b'<' => out.extend_from_slice(b"&lt;"),
+                    b'>' => out.extend_from_slice(b"&gt;"),
+                    b'"' => out.extend_from_slice(b"&quot;"),
+                    b'\'' => out.extend_from_slice(b"&#39;"),
+                    _ => out.push(b),
+                }
+            }
+            out
+        }
+        Codec::Json => {
+            let quoted = serde_json::to_string(as_utf8(input)?)
+                .map_err(|e| format!("JSON serialization error: {}", e))?;
+            quoted.as_bytes()[1..quoted.len() - 1].to_vec()
+        }
+        Codec::QuotedPrintable => encode_quoted_printable(input),
+        Codec::GzipBase64 => {
+            let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+            encoder
+                .write_all(input)
+                .and_then(|_| encoder.finish())
+                .map(|gz| base64_engine(false).encode(gz).into_bytes())
+                .map_err(|e| format!("Compression error: {}", e))?
+        }
+    };
+    Ok(encoded)
+}
+
+/// Decode `input`; whitespace inside Base64 and hex input is ignored
+pub fn decode(codec: Codec, input: &[u8]) -> Result<Vec<u8>, String> {
+    match codec {
+        Codec::Base64 | Codec::Base64Url => base64_engine(codec == Codec::Base64Url)
+            .decode(without_whitespace(input))
+            .map_err(|e| format!("Invalid Base64: {}", e)),
+        Codec::Hex => {
+            let digits = without_whitespace(input);
+            if digits.len() % 2 == 1 {
+                return Err("Invalid hex: odd number of digits".to_string());
+            }
+            digits
+                .chunks(2)
+                .map(|pair| Some(hex_value(pair[0])? << 4 | hex_value(pair[1])?))
+                .collect::<Option<Vec<u8>>>()
+                .ok_or_else(|| "Invalid hex: unexpected character".to_string())
+        }
+        Codec::Url => {
+            let mut out = Vec::with_capacity(input.len());
+            let mut i = 0;
+            while i < input.len() {
+                let escaped = (input[i] == b'%')
+                    .then(|| {
+                        Some(hex_value(*input.get(i + 1)?)? << 4 | hex_value(*input.get(i + 2)?)?)
+                    })
+                    .flatten();
+                match escaped {
+                    Some(b) => {
+                        out.push(b);
+                        i += 3;
+                    }
+                    None => {
+                        out.push(input[i]);
+                        i += 1;
+                    }
+                }
+            }
+            Ok(out)
+        }
+        Codec::Html => Ok(decode_html(as_utf8(input)?).into_bytes()),
+        Codec::Json => {
+            let text = as_utf8(input)?;
+            let quoted = if text.len() >= 2 && text.starts_with('"') && text.ends_with('"') {
+                text.to_string()
+            } else {
+                format!("\"{}\"", text)
+            };
+            serde_json::from_str::<String>(&quoted)
+                .map(String::into_bytes)
+                .map_err(|e| format!("Invalid JSON string: {}", e))
+        }
+        Codec::QuotedPrintable => Ok(decode_quoted_printable(input)),
+        Codec::GzipBase64 => {
+            let compressed =
+                decode(Codec::Base64, input).or_else(|_| decode(Codec::Base64Url, input))?;
+            inflate(&compressed, MAX_INFLATED_BYTES)
+        }
+    }
+}
+
+/// Decompress a gzip or zlib stream of at most `limit` bytes
+fn inflate(compressed: &[u8], limit: u64) -> Result<Vec<u8>, String> {
+    let mut out = Vec::new();
+    let result = if compressed.starts_with(&[0x1f, 0x8b]) {
+        GzDecoder::new(compressed)
+            .take(limit + 1)
+            .read_to_end(&mut out)
+    } else {
+        ZlibDecoder::new(compressed)
+            .take(limit + 1)
+            .read_to_end(&mut out)
+    };
+    result.map_err(|e| format!("Not a gzip/zlib stream: {}", e))?;
+    if out.len() as u64 > limit {
+        return Err(format!(
+            "Decompressed data is larger than {} MB",
+            limit / (1024 * 1024)
+        ));
+    }
+    Ok(out)
+}
+
+fn encode_quoted_printable(input: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(input.len());
+    let mut line_len = 0;
+
+    for (i, &b) in input.iter().enumerate() {
+        if b == b'\n' || (b == b'\r' && input.get(i + 1) == Some(&b'\n')) {
+            out.push(b);
+            line_len = 0;
+            continue;
+        }
+
+        // Whitespace is only literal when not at the end of a line
+        let at_line_end = matches!(input.get(i + 1), None | Some(b'\r') | Some(b'\n'));
+        let literal =
+            matches!(b, 33..=60 | 62..=126) || (matches!(b, b' ' | b'\t') && !at_line_end);
+        let piece = if literal {
+            vec![b]
+        } else {
+            format!("={:02X}", b).into_bytes()
+        };
+
+        // Soft line break, leaving room for the trailing "="
+        if line_len + piece.len() > QP_LINE_LIMIT - 1 {
+            out.extend_from_slice(b"=\n");
+            line_len = 0;
+        }
+        line_len += piece.len();
+        out.extend_from_slice(&piece);
+    }
+
+    out
+}
+
+fn decode_quoted_printable(input: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(input.len());
+    let mut i = 0;
+
+    while i < input.len() {
+        if input[i] != b'=' {
+            out.push(input[i]);
+            i += 1;
+            continue;
+        }
+
+        match (input.get(i + 1), input.get(i + 2)) {
+            // Soft line breaks
+            (Some(b'\r'), Some(b'\n')) => i += 3,
+            (Some(b'\n'), _) => i += 2,
+            (Some(&hi), Some(&lo)) if hex_value(hi).is_some() && hex_value(lo).is_some() => {
+                out.push(hex_value(hi).unwrap() << 4 | hex_value(lo).unwrap());
+                i += 3;
+            }
+            // Malformed escape: keep it as is
+            _ => {
+                out.push(b'=');
+                i += 1;
+            }
+        }
+    }
+
+    out
+}
+
+fn named_entity(name: &str) -> Option<char> {
+    let c = match name {
+        "amp" => '&',
+        "lt" => '<',
+        "gt" => '>',
+        "quot" => '"',
+        "apos" => '\'',
+        "nbsp" => '\u{00A0}',
+        "copy" => '©',
+        "reg" => '®',
+        "trade" => '™',
+        "hellip" => '…',
+        "mdash" => '—',
+        "ndash" => '–',
+        "lsquo" => '‘',
+        "rsquo" => '’',
+        "ldquo" => '“',
+        "rdquo" => '”',
+        "euro" => '€',
+        "deg" => '°',
+        _ => return None,
+    };
+    Some(c)
+}
+
+/// Decode named and numeric entities; unknown ones are left untouched
+fn decode_html(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(amp) = rest.find('&') {
+        out.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+
+        let decoded = rest[1..]
+            .find(';')
+            .filter(|&end| end <= 32)
+            .and_then(|end| {
+                let entity = &rest[1..end + 1];
+                let c = match entity.strip_prefix('#') {
+                    Some(num) => match num.strip_prefix(['x', 'X']) {
+                        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+                        None => num.parse().ok(),
+                    }
+                    .and_then(char::from_u32),
+                    None => named_entity(entity),
+                }?;
+                Some((c, end + 2))
+            });
+
+        match decoded {
+            Some((c, consumed)) => {
+                out.push(c);
+                rest = &rest[consumed..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+
+    out.push_str(rest);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn roundtrip(codec: Codec, input: &[u8]) -> Vec<u8> {
+        let encoded = encode(codec, input).unwrap();
+        assert_eq!(decode(codec, &encoded).unwrap(), input, "{:?}", codec);
+        encoded
+    }
+
+    #[test]
+    fn test_roundtrips() {
+        let text = "héllo <world> & \"friends\"/?=+\n\tsecond line ".as_bytes();
+        for codec in [
+            Codec::Base64,
+            Codec::Base64Url,
+            Codec::Hex,
+            Codec::Url,
+            Codec::Html,
+            Codec::Json,
+            Codec::QuotedPrintable,
+            Codec::GzipBase64,
+        ] {
+            roundtrip(codec, text);
+        }
+
+        assert_eq!(roundtrip(Codec::Base64Url, b"\xfb\xff"), b"-_8");
+        assert_eq!(roundtrip(Codec::Url, b"a b/c"), b"a%20b%2Fc");
+        assert_eq!(roundtrip(Codec::Json, b"a\"b\n"), b"a\\\"b\\n");
+    }
+
+    #[test]
+    fn test_lenient_decoding() {
+        assert_eq!(decode(Codec::Base64, b"aGVs\nbG8").unwrap(), b"hello");
+        assert_eq!(decode(Codec::Hex, b"68 65").unwrap(), b"he");
+        assert!(decode(Codec::Hex, b"6").is_err());
+        assert_eq!(decode(Codec::Url, b"100%25 %zz").unwrap(), b"100% %zz");
+        assert_eq!(
+            decode(Codec::Html, b"&lt;&#x41;&#66;&bogus; &amp").unwrap(),
+            b"<AB&bogus; &amp"
+        );
+        assert_eq!(decode(Codec::Json, b"\"\\u00e9\"").unwrap(), "é".as_bytes());
+        assert_eq!(
+            decode(Codec::QuotedPrintable, b"caf=C3=A9 =\nsoft=3D").unwrap(),
+            "café soft=".as_bytes()
+        );
+    }
+
+    #[test]
+    fn test_quoted_printable_wrapping() {
+        let long = vec![b'x'; 200];
+        let encoded = encode(Codec::QuotedPrintable, &long).unwrap();
+        assert!(encoded
+            .split(|&b| b == b'\n')
+            .all(|l| l.len() <= QP_LINE_LIMIT));
+        assert_eq!(decode(Codec::QuotedPrintable, &encoded).unwrap(), long);
+
+        assert_eq!(
+            encode(Codec::QuotedPrintable, b"a \nb").unwrap(),
+            b"a=20\nb"
+        );
+    }
+
+    #[test]
+    fn test_inflate_limit() {
+        let encoded = encode(Codec::GzipBase64, &vec![0u8; 4 * 1024 * 1024]).unwrap();
+        let compressed = decode(Codec::Base64, &encoded).unwrap();
+        assert_eq!(
+            inflate(&compressed, 4 * 1024 * 1024).unwrap().len(),
+            4 * 1024 * 1024
+        );
+        assert_eq!(
+            inflate(&compressed, 1024 * 1024).unwrap_err(),
+            "Decompressed data is larger than 1 MB"
+        );
+    }
+}
@@ -0,0 +1,95 @@
+use std::fmt;
+use wasm_bindgen::prelude::*;
+
+/// Machine-readable reason for a failed call, exposed to JS as `error.code`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ErrorCode {
+    NotFound,
+    InvalidRange,
+    Utf8,
+    Regex,
+    Parse,
+    InvalidArgument, // Unknown option name, bad enum string, ...
+    Internal,        // Serialization of a result failed
+}
+
+impl ErrorCode {
+    pub fn name(self) -> &'static str {
+        match self {
+            ErrorCode::NotFound => "NotFound",
+            ErrorCode::InvalidRange => "InvalidRange",
+            ErrorCode::Utf8 => "Utf8",
+            ErrorCode::Regex => "Regex",
+            ErrorCode::Parse => "Parse",
+            ErrorCode::InvalidArgument => "InvalidArgument",
+            ErrorCode::Internal => "Internal",
+        }
+    }
+}
+
+/// Error of the object-style API
+/// Thrown to JS as an `Error` with `code` set; the ID-based functions throw
+/// only the message string, as they always have
+#[derive(Clone, Debug, PartialEq)]
+pub struct ApiError {
+    pub code: ErrorCode,
+    pub message: String,
+}
+
+impl ApiError {
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        ApiError {
+            code,
+            message: message.into(),
+        }
+    }
+
+    pub fn not_found(message: impl Into<String>) -> Self {
+        Self::new(ErrorCode::NotFound, message)
+    }
+
+    pub fn invalid_range(message: impl Into<String>) -> Self {
+        Self::new(ErrorCode::InvalidRange, message)
+    }
+
+    pub fn invalid_argument(message: impl Into<String>) -> Self {
+        Self::new(ErrorCode::InvalidArgument, message)
+    }
+
+    pub fn parse(message: impl Into<String>) -> Self {
+        Self::new(ErrorCode::Parse, message)
+    }
+
+    /// Plain string error of the ID-based functions
+    pub fn legacy(self) -> JsValue {
+        JsValue::from_str(&self.message)
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+/// Lets modules that report `String` errors call typed FileBuffer methods
+impl From<ApiError> for String {
+    fn from(error: ApiError) -> Self {
+        error.message
+    }
+}
+
+impl From<ApiError> for JsValue {
+    fn from(error: ApiError) -> Self {
+        let js_error = js_sys::Error::new(&error.message);
+        js_error.set_name("DocumentError");
+        let _ = js_sys::Reflect::set(&js_error, &"code".into(), &error.code.name().into());
+        js_error.into()
+    }
+}
+
+/// Serialize a result for JS, reporting failure as an Internal error
+pub fn to_js<T: serde::Serialize + ?Sized>(value: &T) -> Result<JsValue, ApiError> {
+    serde_wasm_bindgen::to_value(value)
+        .map_err(|e| ApiError::new(ErrorCode::Internal, format!("Serialization error: {}", e)))
+}
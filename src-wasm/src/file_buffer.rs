@@ -1,11 +1,28 @@
+use crate::anchors::AnchorSet;
+use crate::content::Content;
+use crate::errors::{ApiError, ErrorCode};
+use crate::folding::FoldIndex;
+use crate::history::{Edit, EditHistory};
+use crate::log_index::LogIndex;
+use crate::outline::Outline;
+use crate::positions::CharIndex;
+use crate::segments::SegmentIndex;
 use regex::Regex;
 use serde::{Deserialize, Serialize};
 
 /// Core file buffer structure
 /// Stores file content as raw bytes and maintains a line offset index
 pub struct FileBuffer {
-    pub content: Vec<u8>,       // Raw UTF-8 bytes
-    pub line_offsets: Vec<u32>, // Byte offset of each line start
+    pub content: Content,               // Raw UTF-8 bytes
+    pub line_offsets: Vec<usize>,       // Byte offset of each line start
+    pub log_index: Option<LogIndex>,    // Built on demand by index_log
+    pub fold_index: Option<FoldIndex>,  // Built on demand, dropped on edit
+    pub outline: Option<Outline>,       // Built on demand, dropped on edit
+    pub segments: Option<SegmentIndex>, // Last long line segmented, dropped on edit
+    pub char_index: Option<CharIndex>,  // Built on demand, dropped on edit
+    pub history: EditHistory,           // Undo/redo of replace_range edits
+    pub anchors: AnchorSet,             // Offsets that follow edits
+    pub revision: u64,                  // Bumped by every content change
 }
 
 impl FileBuffer {
@@ -13,43 +30,83 @@ impl FileBuffer {
     /// This is very fast in WASM - typically 8x faster than JavaScript
     pub fn new(content: Vec<u8>) -> Result<Self, String> {
         let line_offsets = Self::index_lines(&content);
+        Ok(Self::with_index(content, line_offsets))
+    }
+
+    /// Create a buffer from content and its already built line index
+    pub fn with_index(content: Vec<u8>, line_offsets: Vec<usize>) -> Self {
+        Self::with_content(content.into(), line_offsets)
+    }
 
-        Ok(FileBuffer {
+    /// Create a buffer over a read-only memory map of a file
+    /// Only the line index is built; the bytes stay in the page cache until
+    /// the first edit copies them
+    #[cfg(feature = "mmap")]
+    pub fn from_mmap(map: memmap2::Mmap) -> Self {
+        let line_offsets = Self::index_lines(&map);
+        Self::with_content(Content::Mapped(map), line_offsets)
+    }
+
+    fn with_content(content: Content, line_offsets: Vec<usize>) -> Self {
+        FileBuffer {
             content,
             line_offsets,
-        })
+            log_index: None,
+            fold_index: None,
+            outline: None,
+            segments: None,
+            char_index: None,
+            history: EditHistory::default(),
+            anchors: AnchorSet::default(),
+            revision: 0,
+        }
     }
 
     /// Index all line positions
     /// Returns a vector of byte offsets where each line starts
     /// Line 1 starts at offset 0, line 2 starts after first \n, etc.
-    fn index_lines(content: &[u8]) -> Vec<u32> {
-        let mut offsets = vec![0u32]; // Line 1 starts at byte 0
+    fn index_lines(content: &[u8]) -> Vec<usize> {
+        #[cfg(feature = "parallel")]
+        if content.len() >= crate::parallel::MIN_PARALLEL_BYTES {
+            return crate::parallel::index_lines(content);
+        }
+
+        let mut offsets = vec![0]; // Line 1 starts at byte 0
 
         for (i, &byte) in content.iter().enumerate() {
             if byte == b'\n' {
                 // Next line starts after the newline
-                offsets.push((i + 1) as u32);
+                offsets.push(i + 1);
             }
         }
 
         offsets
     }
 
+    /// Number of lines in the buffer
+    /// A trailing newline terminates the last line rather than starting a new one
+    pub fn line_count(&self) -> usize {
+        if self.content.is_empty() || self.content.ends_with(b"\n") {
+            self.line_offsets.len() - 1
+        } else {
+            self.line_offsets.len()
+        }
+    }
+
     /// Get byte range for a single line
     /// Returns (start_byte, end_byte) inclusive of newline
-    fn get_line_byte_range(&self, line_num: usize) -> Result<(usize, usize), String> {
-        if line_num == 0 || line_num >= self.line_offsets.len() {
+    pub fn get_line_byte_range(&self, line_num: usize) -> Result<(usize, usize), String> {
+        if line_num == 0 || line_num > self.line_count() {
             return Err(format!(
                 "Line {} out of range (file has {} lines)",
                 line_num,
-                self.line_offsets.len() - 1
+                self.line_count()
             ));
         }
 
-        let start = self.line_offsets[line_num - 1] as usize;
-        let end = if line_num < self.line_offsets.len() - 1 {
-            self.line_offsets[line_num] as usize
+        let start = self.line_offsets[line_num - 1];
+        let end = if line_num < self.line_offsets.len() {
+            self.line_offsets[line_num]
         } else {
             self.content.len()
         };
@@ -57,104 +114,427 @@ impl FileBuffer {
         Ok((start, end))
     }
 
+    /// Append bytes to the end of the buffer, indexing only the new lines
+    /// Returns the first line touched by the append (1-based): the previous
+    /// last line if it had no trailing newline, otherwise the first new line
+    pub fn append(&mut self, bytes: &[u8]) -> usize {
+        let first_changed = if self.content.is_empty() || self.content.ends_with(b"\n") {
+            self.line_count() + 1
+        } else {
+            self.line_count()
+        };
+
+        let base = self.content.len();
+        self.content.to_mut().extend_from_slice(bytes);
+        self.anchors.adjust(base, base, bytes.len());
+        for (i, &byte) in bytes.iter().enumerate() {
+            if byte == b'\n' {
+                self.line_offsets.push(base + i + 1);
+            }
+        }
+
+        self.invalidate_caches();
+
+        first_changed
+    }
+
+    /// Check that `start..end` is a byte range of the content
+    pub fn check_range(&self, start: usize, end: usize) -> Result<(), ApiError> {
+        if start > end || end > self.content.len() {
+            return Err(ApiError::invalid_range(format!(
+                "Invalid byte range {}-{} (buffer has {} bytes)",
+                start,
+                end,
+                self.content.len()
+            )));
+        }
+        Ok(())
+    }
+
+    /// Replace the bytes in `start..end` with `text` as one undoable edit
+    pub fn replace_range(&mut self, start: usize, end: usize, text: &[u8]) -> Result<(), String> {
+        self.check_range(start, end)?;
+
+        let removed = self.content[start..end].to_vec();
+        self.splice(start, end, text);
+        self.history.record(Edit {
+            start,
+            removed,
+            inserted: text.to_vec(),
+        });
+        Ok(())
+    }
+
+    /// Replace the whole content with `new_content` as one undoable edit
+    /// Only the span between the first and last differing byte is replaced,
+    /// which keeps the undo history small for sparse changes.
+    /// Returns false (and records nothing) when the content is unchanged.
+    pub fn replace_content(&mut self, new_content: &[u8]) -> Result<bool, String> {
+        if *self.content == *new_content {
+            return Ok(false);
+        }
+
+        let (start, end, new_end) = changed_span(&self.content, new_content);
+        self.replace_range(start, end, &new_content[start..new_end])?;
+        Ok(true)
+    }
+
+    /// Revert the most recent edit
+    /// Returns the first changed line, or None if there is nothing to undo
+    pub fn undo(&mut self) -> Option<usize> {
+        let edit = self.history.undo()?;
+        let (start, end) = (edit.start, edit.start + edit.removed.len());
+        let text = edit.inserted.clone();
+        self.splice(start, end, &text);
+        Some(self.line_of(start))
+    }
+
+    /// Re-apply the most recently undone edit
+    /// Returns the first changed line, or None if there is nothing to redo
+    pub fn redo(&mut self) -> Option<usize> {
+        let edit = self.history.redo()?;
+        let (start, end) = (edit.start, edit.start + edit.removed.len());
+        let text = edit.inserted.clone();
+        self.splice(start, end, &text);
+        Some(self.line_of(start))
+    }
+
+    /// Line (1-based) of a byte offset already known to be in range
+    fn line_of(&self, byte_offset: usize) -> usize {
+        self.line_offsets
+            .partition_point(|&o| o <= byte_offset)
+            .max(1)
+    }
+
+    /// Replace `start..end` (already validated) with `text`
+    /// The line index is patched around the edit instead of rebuilt
+    fn splice(&mut self, start: usize, end: usize, text: &[u8]) {
+        self.content
+            .to_mut()
+            .splice(start..end, text.iter().copied());
+        self.anchors.adjust(start, end, text.len());
+
+        // Line starts inside the replaced range go away, later ones shift
+        let first = self.line_offsets.partition_point(|&o| o <= start);
+        let last = self.line_offsets.partition_point(|&o| o <= end);
+
+        let inserted = text
+            .iter()
+            .enumerate()
+            .filter(|(_, &b)| b == b'\n')
+            .map(|(i, _)| start + i + 1);
+        let shifted: Vec<usize> = self.line_offsets[last..]
+            .iter()
+            .map(|&o| o - end + start + text.len())
+            .collect();
+
+        self.line_offsets.truncate(first);
+        self.line_offsets.extend(inserted);
+        self.line_offsets.extend(shifted);
+
+        self.invalidate_caches();
+    }
+
+    /// Drop indexes derived from the content after it changed
+    fn invalidate_caches(&mut self) {
+        self.revision += 1;
+        self.log_index = None;
+        self.fold_index = None;
+        self.outline = None;
+        self.segments = None;
+        self.char_index = None;
+    }
+
+    /// Move the content out while the buffer is stored compressed
+    /// Undo history and anchors are kept; the line index is rebuilt after
+    /// restore, the other indexes when next needed (the log index by calling
+    /// index_log again)
+    pub fn take_content(&mut self) -> Vec<u8> {
+        self.log_index = None;
+        self.fold_index = None;
+        self.outline = None;
+        self.segments = None;
+        self.char_index = None;
+        self.line_offsets = vec![0];
+        std::mem::take(&mut self.content).into_vec()
+    }
+
+    /// Put back content taken with take_content
+    pub fn restore_content(&mut self, content: Vec<u8>) {
+        self.line_offsets = Self::index_lines(&content);
+        self.content = content.into();
+    }
+
+    /// Folding/bracket index for a language, rebuilt if missing or stale
+    pub fn fold_index(&mut self, language: &str) -> &FoldIndex {
+        let stale = match &self.fold_index {
+            Some(index) => index.language != language,
+            None => true,
+        };
+        if stale {
+            let index = FoldIndex::build(&self.content, &self.line_offsets, language);
+            self.fold_index = Some(index);
+        }
+        self.fold_index.as_ref().unwrap()
+    }
+
+    /// Symbol outline for a language, reparsed if missing or stale
+    pub fn outline(&mut self, language: &str) -> Result<&Outline, String> {
+        let stale = match &self.outline {
+            Some(outline) => outline.language != language,
+            None => true,
+        };
+        if stale {
+            self.outline = Some(Outline::build(&self.content, language)?);
+        }
+        Ok(self.outline.as_ref().unwrap())
+    }
+
+    /// Per-line character/UTF-16 index, built on first use
+    pub fn char_index(&mut self) -> &CharIndex {
+        if self.char_index.is_none() {
+            self.char_index = Some(CharIndex::build(&self.content, &self.line_offsets));
+        }
+        self.char_index.as_ref().unwrap()
+    }
+
+    /// Segment index for a line, rebuilt when the line or width changes
+    pub fn segment_index(
+        &mut self,
+        line_num: usize,
+        width: usize,
+    ) -> Result<&SegmentIndex, String> {
+        let stale = match &self.segments {
+            Some(index) => index.line != line_num || index.width != width.max(1),
+            None => true,
+        };
+        if stale {
+            let (start, end) = self.get_line_byte_range(line_num)?;
+            let index = SegmentIndex::build(&self.content, line_num, start, end, width);
+            self.segments = Some(index);
+        }
+        Ok(self.segments.as_ref().unwrap())
+    }
+
+    /// Line (1-based) containing a byte offset
+    pub fn line_at_offset(&self, byte_offset: usize) -> Result<usize, String> {
+        if byte_offset > self.content.len() {
+            return Err(format!(
+                "Byte offset {} out of range (file has {} bytes)",
+                byte_offset,
+                self.content.len()
+            ));
+        }
+
+        Ok(self.line_of(byte_offset).min(self.line_count().max(1)))
+    }
+
     /// Get a range of lines as UTF-8 string
     /// This is the main function used by CodeMirror for virtual scrolling
-    pub fn get_line_range(&self, start_line: usize, end_line: usize) -> Result<String, String> {
+    pub fn get_line_range(&self, start_line: usize, end_line: usize) -> Result<String, ApiError> {
         if start_line == 0 {
-            return Err("Line numbers are 1-indexed".to_string());
+            return Err(ApiError::invalid_range("Line numbers are 1-indexed"));
         }
 
         if start_line > end_line {
-            return Err(format!(
+            return Err(ApiError::invalid_range(format!(
                 "Invalid range: start {} > end {}",
                 start_line, end_line
-            ));
+            )));
         }
 
-        let (start_byte, _) = self.get_line_byte_range(start_line)?;
-        let (_, end_byte) = self.get_line_byte_range(end_line)?;
+        let (start_byte, _) = self
+            .get_line_byte_range(start_line)
+            .map_err(ApiError::invalid_range)?;
+        let (_, end_byte) = self
+            .get_line_byte_range(end_line)
+            .map_err(ApiError::invalid_range)?;
 
         // Convert byte slice to UTF-8 string
-        String::from_utf8(self.content[start_byte..end_byte].to_vec())
-            .map_err(|e| format!("UTF-8 error at byte range {}-{}: {}", start_byte, end_byte, e))
+        String::from_utf8(self.content[start_byte..end_byte].to_vec()).map_err(|e| {
+            ApiError::new(
+                ErrorCode::Utf8,
+                format!(
+                    "UTF-8 error at byte range {}-{}: {}",
+                    start_byte, end_byte, e
+                ),
+            )
+        })
+    }
+
+    /// Whole content as a string
+    pub fn content_string(&self) -> Result<String, ApiError> {
+        String::from_utf8(self.content.to_vec())
+            .map_err(|e| ApiError::new(ErrorCode::Utf8, format!("UTF-8 decode error: {}", e)))
     }
 
     /// Search for pattern using regex
     /// Returns up to max_results matches with line number, column, and text
-    pub fn search(&self, pattern: &str, max_results: usize) -> Result<Vec<SearchMatch>, String> {
-        let re = Regex::new(pattern).map_err(|e| format!("Invalid regex: {}", e))?;
+    pub fn search(&self, pattern: &str, max_results: usize) -> Result<Vec<SearchMatch>, ApiError> {
+        let re = Regex::new(pattern)
+            .map_err(|e| ApiError::new(ErrorCode::Regex, format!("Invalid regex: {}", e)))?;
 
-        let mut results = Vec::new();
-        let content_str = String::from_utf8_lossy(&self.content);
+        Ok(self.search_regex(&re, (1, 0), max_results).0)
+    }
 
-        for (line_num, line_content) in content_str.lines().enumerate() {
-            // Find all matches in this line
-            for mat in re.find_iter(line_content) {
-                results.push(SearchMatch {
-                    line: line_num + 1,
-                    column: mat.start(),
-                    text: line_content.to_string(),
-                });
-
-                if results.len() >= max_results {
-                    return Ok(results);
-                }
-            }
+    /// Matches of `re` at or after `from` (1-based line, byte column)
+    /// Stops after max_results and also returns where the next match starts,
+    /// so a later call can continue from there
+    pub fn search_regex(
+        &self,
+        re: &Regex,
+        from: (usize, usize),
+        max_results: usize,
+    ) -> (Vec<SearchMatch>, Option<(usize, usize)>) {
+        #[cfg(feature = "parallel")]
+        if self.content.len() >= crate::parallel::MIN_PARALLEL_BYTES {
+            return crate::parallel::search_regex(self, re, from, max_results);
         }
 
-        Ok(results)
+        // Start at the line of `from` instead of skipping lines before it
+        let first_line = from.0.max(1);
+        if first_line > self.line_count() {
+            return (Vec::new(), None);
+        }
+        let start = self.line_offsets[first_line - 1];
+        let content_str = String::from_utf8_lossy(&self.content[start..]);
+        search_text(&content_str, first_line, re, from, max_results)
     }
 
     /// Get file statistics
     pub fn get_stats(&self) -> FileStats {
         FileStats {
             size: self.content.len(),
-            line_count: self.line_offsets.len().saturating_sub(1),
-            index_size: self.line_offsets.len() * std::mem::size_of::<u32>(),
+            line_count: self.line_count(),
+            index_size: self.line_offsets.len() * std::mem::size_of::<usize>(),
+            cache_size: self.cache_size(),
         }
     }
 
+    /// Bytes held by the cached indexes and the undo history
+    pub fn cache_size(&self) -> usize {
+        self.log_index.as_ref().map_or(0, LogIndex::heap_size)
+            + self.fold_index.as_ref().map_or(0, FoldIndex::heap_size)
+            + self.outline.as_ref().map_or(0, Outline::heap_size)
+            + self.segments.as_ref().map_or(0, SegmentIndex::heap_size)
+            + self.char_index.as_ref().map_or(0, CharIndex::heap_size)
+            + self.history.bytes()
+    }
+
+    /// Build (or rebuild) the log index for this buffer
+    pub fn index_log(&mut self, default_year: i32) -> &LogIndex {
+        let index = LogIndex::build(
+            &self.content,
+            &self.line_offsets,
+            self.line_count(),
+            default_year,
+        );
+        self.log_index.insert(index)
+    }
+
     /// Validate JSON content
-    pub fn validate_json(&self) -> Result<(), String> {
+    pub fn validate_json(&self) -> Result<(), ApiError> {
         let content_str = String::from_utf8_lossy(&self.content);
         serde_json::from_str::<serde_json::Value>(&content_str)
             .map(|_| ())
-            .map_err(|e| format!("JSON validation error: {}", e))
+            .map_err(|e| ApiError::parse(format!("JSON validation error: {}", e)))
     }
 
     /// Format JSON content with indentation
-    pub fn format_json(&self, indent: usize) -> Result<String, String> {
+    pub fn format_json(&self, indent: usize) -> Result<String, ApiError> {
         let content_str = String::from_utf8_lossy(&self.content);
         let value: serde_json::Value = serde_json::from_str(&content_str)
-            .map_err(|e| format!("JSON parse error: {}", e))?;
+            .map_err(|e| ApiError::parse(format!("JSON parse error: {}", e)))?;
 
         let indent_vec = vec![b' '; indent];
         let formatter = serde_json::ser::PrettyFormatter::with_indent(&indent_vec);
         let mut buf = Vec::new();
         let mut ser = serde_json::Serializer::with_formatter(&mut buf, formatter);
 
-        value
-            .serialize(&mut ser)
-            .map_err(|e| format!("JSON serialization error: {}", e))?;
+        value.serialize(&mut ser).map_err(|e| {
+            ApiError::new(
+                ErrorCode::Internal,
+                format!("JSON serialization error: {}", e),
+            )
+        })?;
 
-        String::from_utf8(buf).map_err(|e| format!("UTF-8 error: {}", e))
+        String::from_utf8(buf)
+            .map_err(|e| ApiError::new(ErrorCode::Utf8, format!("UTF-8 error: {}", e)))
     }
 }
 
 /// Search result structure
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct SearchMatch {
     pub line: usize,
-    pub column: usize,
+    pub column: usize,       // Byte index into the line
+    pub utf16_column: usize, // Same position in UTF-16 units, for CodeMirror
     pub text: String,
 }
 
+/// The span that differs between `old` and `new`, after their common prefix
+/// and suffix: (start, end in old, end in new)
+fn changed_span(old: &[u8], new: &[u8]) -> (usize, usize, usize) {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    (prefix, old.len() - suffix, new.len() - suffix)
+}
+
+/// FileBuffer::search_regex over `text`, whose first line is `first_line`
+pub(crate) fn search_text(
+    text: &str,
+    first_line: usize,
+    re: &Regex,
+    from: (usize, usize),
+    max_results: usize,
+) -> (Vec<SearchMatch>, Option<(usize, usize)>) {
+    let mut results = Vec::new();
+
+    let skip = from.0.saturating_sub(first_line);
+    for (i, line_content) in text.lines().enumerate().skip(skip) {
+        let line_num = first_line + i;
+        let start = if line_num == from.0 { from.1 } else { 0 };
+        // Find all matches in this line
+        for mat in re.find_iter(line_content) {
+            if mat.start() < start {
+                continue;
+            }
+            if results.len() >= max_results {
+                return (results, Some((line_num, mat.start())));
+            }
+
+            results.push(SearchMatch {
+                line: line_num,
+                column: mat.start(),
+                utf16_column: line_content[..mat.start()].encode_utf16().count(),
+                text: line_content.to_string(),
+            });
+        }
+    }
+
+    (results, None)
+}
+
+/// Result of an edit that changed lines, for the editor to refresh from
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct EditResult {
+    pub first_changed_line: usize,
+    pub line_count: usize,
+    pub size: usize,
+}
+
 /// File statistics
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct FileStats {
     pub size: usize,
     pub line_count: usize,
-    pub index_size: usize,
+    pub index_size: usize, // Line index
+    pub cache_size: usize, // Other indexes and the undo history
 }
 
 /// File metadata returned to JavaScript
@@ -191,6 +571,62 @@ mod tests {
         assert_eq!(result, "line2\nline3");
     }
 
+    #[test]
+    fn test_append() {
+        let mut buffer = FileBuffer::new(b"line1\nli".to_vec()).unwrap();
+
+        let first_changed = buffer.append(b"ne2\nline3\n");
+        assert_eq!(first_changed, 2);
+        assert_eq!(buffer.line_offsets, vec![0, 6, 12, 18]);
+        assert_eq!(buffer.line_count(), 3);
+        assert_eq!(buffer.get_line_range(2, 3).unwrap(), "line2\nline3\n");
+
+        assert_eq!(buffer.append(b"line4"), 4);
+        assert_eq!(buffer.line_count(), 4);
+    }
+
+    #[test]
+    fn test_replace_range() {
+        let mut buffer = FileBuffer::new(b"one\ntwo\nthree\n".to_vec()).unwrap();
+
+        // Replace "two\nthre" with "2\n2\n3"
+        buffer.replace_range(4, 12, b"2\n2\n3").unwrap();
+        assert_eq!(buffer.content, b"one\n2\n2\n3e\n");
+        assert_eq!(
+            buffer.line_offsets,
+            FileBuffer::index_lines(&buffer.content)
+        );
+
+        buffer.replace_range(0, buffer.content.len(), b"").unwrap();
+        assert_eq!(buffer.line_offsets, vec![0]);
+        assert_eq!(buffer.line_count(), 0);
+
+        assert!(buffer.replace_range(1, 2, b"x").is_err());
+    }
+
+    #[test]
+    fn test_undo_redo() {
+        let mut buffer = FileBuffer::new(b"one\ntwo\n".to_vec()).unwrap();
+        buffer.replace_range(4, 7, b"2\n2").unwrap();
+        buffer.replace_range(0, 0, b"zero\n").unwrap();
+
+        assert_eq!(buffer.undo(), Some(1));
+        assert_eq!(buffer.undo(), Some(2));
+        assert_eq!(buffer.content, b"one\ntwo\n");
+        assert_eq!(buffer.undo(), None);
+
+        assert_eq!(buffer.redo(), Some(2));
+        assert_eq!(buffer.content, b"one\n2\n2\n");
+        assert_eq!(
+            buffer.line_offsets,
+            FileBuffer::index_lines(&buffer.content)
+        );
+
+        // A new edit discards the redo stack
+        buffer.replace_range(0, 3, b"1").unwrap();
+        assert_eq!(buffer.redo(), None);
+    }
+
     #[test]
     fn test_search() {
         let content = b"foo\nbar\nfoo bar\nbaz".to_vec();
@@ -212,4 +648,25 @@ mod tests {
         let buffer = FileBuffer::new(invalid_json).unwrap();
         assert!(buffer.validate_json().is_err());
     }
+
+    #[cfg(feature = "mmap")]
+    #[test]
+    fn test_mapped_buffer_copies_on_first_edit() {
+        let path = std::env::temp_dir().join(format!("tidy-mmap-{}.txt", std::process::id()));
+        std::fs::write(&path, b"one\ntwo\nthree").unwrap();
+        let file = std::fs::File::open(&path).unwrap();
+        let map = unsafe { memmap2::Mmap::map(&file) }.unwrap();
+        let mut buffer = FileBuffer::from_mmap(map);
+        std::fs::remove_file(&path).ok();
+
+        assert!(buffer.content.is_mapped());
+        assert_eq!(buffer.line_count(), 3);
+        assert_eq!(buffer.get_line_range(2, 3).unwrap(), "two\nthree");
+
+        buffer.replace_range(4, 7, b"TWO").unwrap();
+        assert!(!buffer.content.is_mapped());
+        assert_eq!(buffer.content, b"one\nTWO\nthree");
+        buffer.undo();
+        assert_eq!(buffer.content, b"one\ntwo\nthree");
+    }
 }
@@ -0,0 +1,491 @@
+use serde::{Deserialize, Serialize};
+
+/// Columns a tab counts for when comparing indentation
+const TAB_WIDTH: usize = 4;
+
+/// Lexical rules needed to skip brackets inside strings and comments
+struct Syntax {
+    line_comments: &'static [&'static str],
+    block_comment: Option<(&'static str, &'static str)>,
+    quotes: &'static [u8],           // String delimiters that end at a newline
+    multiline_quotes: &'static [u8], // String delimiters that may span lines
+    triple_quotes: bool,             // Python-style """ and ''' strings
+    char_literals: bool,             // ' only starts a literal like 'x' or '\n' (Rust lifetimes)
+    indent_folding: bool,            // Fold by indentation instead of brackets
+}
+
+impl Syntax {
+    fn for_language(language: &str) -> Self {
+        const C_LIKE: Syntax = Syntax {
+            line_comments: &["//"],
+            block_comment: Some(("/*", "*/")),
+            quotes: b"\"'",
+            multiline_quotes: b"",
+            triple_quotes: false,
+            char_literals: false,
+            indent_folding: false,
+        };
+
+        match language.to_ascii_lowercase().as_str() {
+            "javascript" | "js" | "jsx" | "typescript" | "ts" | "tsx" | "mjs" | "cjs" => Syntax {
+                multiline_quotes: b"`",
+                ..C_LIKE
+            },
+            "go" => Syntax {
+                multiline_quotes: b"`",
+                ..C_LIKE
+            },
+            "rust" | "rs" => Syntax {
+                quotes: b"",
+                multiline_quotes: b"\"",
+                char_literals: true,
+                ..C_LIKE
+            },
+            "c" | "cpp" | "c++" | "h" | "hpp" | "java" | "csharp" | "cs" | "kotlin" | "kt"
+            | "swift" | "scala" | "php" | "dart" => C_LIKE,
+            "json" => Syntax {
+                line_comments: &[],
+                block_comment: None,
+                quotes: b"\"",
+                ..C_LIKE
+            },
+            "jsonc" | "json5" => Syntax {
+                quotes: b"\"",
+                ..C_LIKE
+            },
+            "css" => Syntax {
+                line_comments: &[],
+                ..C_LIKE
+            },
+            "scss" | "less" => C_LIKE,
+            "sql" => Syntax {
+                line_comments: &["--"],
+                quotes: b"'\"",
+                ..C_LIKE
+            },
+            "shell" | "sh" | "bash" | "zsh" | "toml" | "ruby" | "rb" | "perl" | "r" => Syntax {
+                line_comments: &["#"],
+                block_comment: None,
+                ..C_LIKE
+            },
+            "python" | "py" => Syntax {
+                line_comments: &["#"],
+                block_comment: None,
+                triple_quotes: true,
+                indent_folding: true,
+                ..C_LIKE
+            },
+            "yaml" | "yml" => Syntax {
+                line_comments: &["#"],
+                block_comment: None,
+                quotes: b"",
+                indent_folding: true,
+                ..C_LIKE
+            },
+            "html" | "xml" | "svg" | "vue" => Syntax {
+                line_comments: &[],
+                block_comment: Some(("<!--", "-->")),
+                quotes: b"",
+                indent_folding: true,
+                ..C_LIKE
+            },
+            // Plain text, markdown and anything unknown: prose is full of
+            // apostrophes, so don't track strings at all
+            _ => Syntax {
+                line_comments: &[],
+                block_comment: None,
+                quotes: b"",
+                indent_folding: true,
+                ..C_LIKE
+            },
+        }
+    }
+}
+
+/// What a folding range was derived from
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum FoldKind {
+    Bracket,
+    Indent,
+    Comment,
+}
+
+/// A foldable line range (1-based, inclusive)
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct FoldRange {
+    pub start_line: usize,
+    pub end_line: usize,
+    pub kind: FoldKind,
+}
+
+/// A matched bracket pair (byte offsets of the bracket characters)
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct BracketMatch {
+    pub open: usize,
+    pub close: usize,
+    pub open_line: usize,
+    pub close_line: usize,
+}
+
+/// Bracket pairs and folding ranges of a whole buffer
+/// Built in one pass that skips strings and comments; cached on the buffer
+/// until the next edit
+pub struct FoldIndex {
+    pub language: String,
+    pairs_by_open: Vec<(u32, u32)>,
+    pairs_by_close: Vec<(u32, u32)>,
+    folds: Vec<FoldRange>, // Sorted by start line, at most one per start line
+}
+
+/// Line (1-based) containing a byte offset
+fn line_of(line_offsets: &[usize], offset: usize) -> usize {
+    line_offsets.partition_point(|&o| o <= offset)
+}
+
+impl FoldIndex {
+    pub fn build(content: &[u8], line_offsets: &[usize], language: &str) -> Self {
+        let syntax = Syntax::for_language(language);
+        let Scan {
+            pairs: pairs_by_close,
+            comments,
+        } = scan_brackets(content, &syntax);
+
+        let mut pairs_by_open = pairs_by_close.clone();
+        pairs_by_open.sort_unstable();
+
+        let mut folds: Vec<FoldRange> = Vec::new();
+        if syntax.indent_folding {
+            folds.extend(indent_folds(content, line_offsets));
+        } else {
+            folds.extend(pairs_by_open.iter().map(|&(open, close)| FoldRange {
+                start_line: line_of(line_offsets, open as usize),
+                end_line: line_of(line_offsets, close as usize),
+                kind: FoldKind::Bracket,
+            }));
+        }
+        folds.extend(comments.iter().map(|&(start, end)| FoldRange {
+            start_line: line_of(line_offsets, start),
+            end_line: line_of(line_offsets, end),
+            kind: FoldKind::Comment,
+        }));
+
+        // Keep only multi-line ranges, and the widest one per start line
+        folds.retain(|f| f.end_line > f.start_line);
+        folds.sort_by(|a, b| {
+            a.start_line
+                .cmp(&b.start_line)
+                .then(b.end_line.cmp(&a.end_line))
+        });
+        folds.dedup_by_key(|f| f.start_line);
+
+        FoldIndex {
+            language: language.to_string(),
+            pairs_by_open,
+            pairs_by_close,
+            folds,
+        }
+    }
+
+    /// Approximate heap bytes held by the index
+    pub fn heap_size(&self) -> usize {
+        std::mem::size_of_val(self.pairs_by_open.as_slice())
+            + std::mem::size_of_val(self.pairs_by_close.as_slice())
+            + std::mem::size_of_val(self.folds.as_slice())
+    }
+
+    /// Folding ranges that start within `start_line..=end_line`
+    pub fn folds_in_range(&self, start_line: usize, end_line: usize) -> &[FoldRange] {
+        let from = self.folds.partition_point(|f| f.start_line < start_line);
+        let to = self.folds.partition_point(|f| f.start_line <= end_line);
+        &self.folds[from..to.max(from)]
+    }
+
+    /// Find the bracket matching the one at `offset`, or the one just before
+    /// it (the usual cursor-after-bracket case)
+    pub fn match_bracket(&self, line_offsets: &[usize], offset: usize) -> Option<BracketMatch> {
+        let find = |offset: usize| {
+            let offset = u32::try_from(offset).ok()?;
+            let by_open = self
+                .pairs_by_open
+                .binary_search_by_key(&offset, |&(open, _)| open)
+                .ok()
+                .map(|i| self.pairs_by_open[i]);
+            by_open.or_else(|| {
+                self.pairs_by_close
+                    .binary_search_by_key(&offset, |&(_, close)| close)
+                    .ok()
+                    .map(|i| self.pairs_by_close[i])
+            })
+        };
+
+        let (open, close) = find(offset).or_else(|| find(offset.checked_sub(1)?))?;
+        Some(BracketMatch {
+            open: open as usize,
+            close: close as usize,
+            open_line: line_of(line_offsets, open as usize),
+            close_line: line_of(line_offsets, close as usize),
+        })
+    }
+}
+
+fn closing(open: u8) -> u8 {
+    match open {
+        b'(' => b')',
+        b'[' => b']',
+        _ => b'}',
+    }
+}
+
+/// Bracket pairs and block comments found by scan_brackets
+struct Scan {
+    pairs: Vec<(u32, u32)>,        // (open, close), sorted by close offset
+    comments: Vec<(usize, usize)>, // Block comment spans, inclusive
+}
+
+/// Scan for bracket pairs outside strings and comments
+fn scan_brackets(content: &[u8], syntax: &Syntax) -> Scan {
+    let mut pairs = Vec::new();
+    let mut comments = Vec::new();
+    let mut stack: Vec<(u8, u32)> = Vec::new();
+    let mut i = 0;
+
+    while i < content.len() {
+        let rest = &content[i..];
+        let byte = content[i];
+
+        if syntax
+            .line_comments
+            .iter()
+            .any(|c| rest.starts_with(c.as_bytes()))
+        {
+            i += rest.iter().position(|&b| b == b'\n').unwrap_or(rest.len());
+            continue;
+        }
+
+        if let Some((start, end)) = syntax.block_comment {
+            if rest.starts_with(start.as_bytes()) {
+                let body = &rest[start.len()..];
+                let len =
+                    find(body, end.as_bytes()).map_or(rest.len(), |p| start.len() + p + end.len());
+                comments.push((i, i + len - 1));
+                i += len;
+                continue;
+            }
+        }
+
+        if syntax.triple_quotes && (rest.starts_with(b"\"\"\"") || rest.starts_with(b"'''")) {
+            let body = &rest[3..];
+            i += find(body, &rest[..3]).map_or(rest.len(), |p| p + 6);
+            continue;
+        }
+
+        if byte == b'\'' && syntax.char_literals {
+            // 'x' or '\n' is a char literal; anything else is a lifetime
+            let len = match rest.get(1) {
+                Some(b'\\') => rest.iter().skip(2).position(|&b| b == b'\'').map(|p| p + 3),
+                Some(&first) => {
+                    let width = match first {
+                        0xF0.. => 4,
+                        0xE0.. => 3,
+                        0x80.. => 2,
+                        _ => 1,
+                    };
+                    (rest.get(1 + width) == Some(&b'\'')).then_some(width + 2)
+                }
+                None => None,
+            };
+            i += len.unwrap_or(1);
+            continue;
+        }
+
+        let multiline = syntax.multiline_quotes.contains(&byte);
+        if multiline || syntax.quotes.contains(&byte) {
+            i += string_length(rest, multiline);
+            continue;
+        }
+
+        match byte {
+            b'(' | b'[' | b'{' => stack.push((byte, i as u32)),
+            b')' | b']' | b'}' => {
+                // Recover from stray brackets by closing the nearest match
+                if let Some(depth) = stack.iter().rposition(|&(open, _)| closing(open) == byte) {
+                    let (_, open) = stack[depth];
+                    stack.truncate(depth);
+                    pairs.push((open, i as u32));
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+
+    Scan { pairs, comments }
+}
+
+/// Length of a quoted string starting at `rest[0]`, including both quotes
+fn string_length(rest: &[u8], multiline: bool) -> usize {
+    let quote = rest[0];
+    let mut j = 1;
+    while j < rest.len() {
+        match rest[j] {
+            b'\\' => j += 2,
+            b'\n' if !multiline => return j,
+            b if b == quote => return j + 1,
+            _ => j += 1,
+        }
+    }
+    rest.len()
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+/// Fold every line followed by more deeply indented lines
+fn indent_folds(content: &[u8], line_offsets: &[usize]) -> Vec<FoldRange> {
+    let mut folds = Vec::new();
+    let mut stack: Vec<(usize, usize)> = Vec::new(); // (line, indent)
+    let mut last_non_blank = 0;
+
+    let line_count = if content.is_empty() || content.ends_with(b"\n") {
+        line_offsets.len() - 1
+    } else {
+        line_offsets.len()
+    };
+
+    for line in 1..=line_count {
+        let start = line_offsets[line - 1];
+        let end = line_offsets.get(line).map_or(content.len(), |&o| o);
+
+        let mut indent = 0;
+        let mut blank = true;
+        for &b in &content[start..end] {
+            match b {
+                b' ' => indent += 1,
+                b'\t' => indent += TAB_WIDTH - indent % TAB_WIDTH,
+                b'\n' | b'\r' => {}
+                _ => {
+                    blank = false;
+                    break;
+                }
+            }
+        }
+        if blank {
+            continue;
+        }
+
+        while let Some(&(top_line, top_indent)) = stack.last() {
+            if top_indent < indent {
+                break;
+            }
+            stack.pop();
+            folds.push(FoldRange {
+                start_line: top_line,
+                end_line: last_non_blank,
+                kind: FoldKind::Indent,
+            });
+        }
+        stack.push((line, indent));
+        last_non_blank = line;
+    }
+
+    for (line, _) in stack {
+        folds.push(FoldRange {
+            start_line: line,
+            end_line: last_non_blank,
+            kind: FoldKind::Indent,
+        });
+    }
+
+    folds
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file_buffer::FileBuffer;
+
+    fn index(text: &str, language: &str) -> (FileBuffer, FoldIndex) {
+        let buffer = FileBuffer::new(text.as_bytes().to_vec()).unwrap();
+        let index = FoldIndex::build(&buffer.content, &buffer.line_offsets, language);
+        (buffer, index)
+    }
+
+    #[test]
+    fn test_bracket_folds_skip_strings_and_comments() {
+        let (_, index) = index(
+            "function f() {\n  const s = \"{\";\n  // }\n  /* {\n  */\n  return [\n    1,\n  ];\n}\n",
+            "javascript",
+        );
+
+        assert_eq!(
+            index.folds_in_range(1, 100),
+            &[
+                FoldRange {
+                    start_line: 1,
+                    end_line: 9,
+                    kind: FoldKind::Bracket
+                },
+                FoldRange {
+                    start_line: 4,
+                    end_line: 5,
+                    kind: FoldKind::Comment
+                },
+                FoldRange {
+                    start_line: 6,
+                    end_line: 8,
+                    kind: FoldKind::Bracket
+                },
+            ]
+        );
+        assert_eq!(index.folds_in_range(2, 5).len(), 1);
+    }
+
+    #[test]
+    fn test_match_bracket() {
+        let (buffer, index) = index("fn a<'a>(x: &'a str) {\n    let c = '{';\n}\n", "rust");
+
+        let open = buffer.content.iter().position(|&b| b == b'{').unwrap();
+        let found = index.match_bracket(&buffer.line_offsets, open).unwrap();
+        assert_eq!(found.close, buffer.content.len() - 2);
+        assert_eq!((found.open_line, found.close_line), (1, 3));
+
+        // Cursor right after the closing bracket
+        let found = index
+            .match_bracket(&buffer.line_offsets, buffer.content.len() - 1)
+            .unwrap();
+        assert_eq!(found.open, open);
+
+        assert!(index.match_bracket(&buffer.line_offsets, 1).is_none());
+    }
+
+    #[test]
+    fn test_indent_folds() {
+        let (_, index) = index(
+            "class A:\n    def f(self):\n        return 1\n\n    def g(self):\n        pass\nx = 1\n",
+            "python",
+        );
+
+        assert_eq!(
+            index.folds_in_range(1, 100),
+            &[
+                FoldRange {
+                    start_line: 1,
+                    end_line: 6,
+                    kind: FoldKind::Indent
+                },
+                FoldRange {
+                    start_line: 2,
+                    end_line: 3,
+                    kind: FoldKind::Indent
+                },
+                FoldRange {
+                    start_line: 5,
+                    end_line: 6,
+                    kind: FoldKind::Indent
+                },
+            ]
+        );
+    }
+}
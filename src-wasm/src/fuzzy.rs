@@ -0,0 +1,382 @@
+use crate::file_buffer::FileBuffer;
+use crate::outline::{Symbol, SymbolKind};
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+// Scores follow fzf's v1 algorithm
+const SCORE_MATCH: i32 = 16;
+const SCORE_GAP_START: i32 = -3;
+const SCORE_GAP_EXTENSION: i32 = -1;
+const BONUS_BOUNDARY: i32 = SCORE_MATCH / 2; // After a space or punctuation
+const BONUS_NON_WORD: i32 = SCORE_MATCH / 2;
+const BONUS_CAMEL_123: i32 = BONUS_BOUNDARY + SCORE_GAP_EXTENSION; // fooBar, foo123
+const BONUS_CONSECUTIVE: i32 = -(SCORE_GAP_START + SCORE_GAP_EXTENSION);
+const BONUS_FIRST_CHAR_MULTIPLIER: i32 = 2;
+
+/// A ranked fuzzy match of a line or list entry
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct FuzzyMatch {
+    pub index: usize, // 1-based line number, or index into the candidate list
+    pub score: i32,
+    pub positions: Vec<usize>, // UTF-16 indices of the matched characters
+    pub text: String,
+}
+
+/// A ranked fuzzy match of an outline symbol name
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SymbolMatch {
+    pub name: String,
+    pub kind: SymbolKind,
+    pub start_line: usize,
+    pub end_line: usize,
+    pub score: i32,
+    pub positions: Vec<usize>, // UTF-16 indices into the name
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum CharClass {
+    White,
+    NonWord,
+    Lower,
+    Upper,
+    Letter, // Caseless or non-ASCII letter
+    Number,
+}
+
+fn char_class(c: char) -> CharClass {
+    if c.is_ascii_lowercase() {
+        CharClass::Lower
+    } else if c.is_ascii_uppercase() {
+        CharClass::Upper
+    } else if c.is_ascii_digit() {
+        CharClass::Number
+    } else if c.is_whitespace() {
+        CharClass::White
+    } else if c.is_ascii() {
+        CharClass::NonWord
+    } else if c.is_lowercase() {
+        CharClass::Lower
+    } else if c.is_uppercase() {
+        CharClass::Upper
+    } else if c.is_alphabetic() {
+        CharClass::Letter
+    } else if c.is_numeric() {
+        CharClass::Number
+    } else {
+        CharClass::NonWord
+    }
+}
+
+fn bonus(prev: CharClass, class: CharClass) -> i32 {
+    use CharClass::*;
+    match (prev, class) {
+        (White | NonWord, Lower | Upper | Letter | Number) => BONUS_BOUNDARY,
+        (Lower, Upper) | (Lower | Upper | Letter, Number) => BONUS_CAMEL_123,
+        (_, NonWord) => BONUS_NON_WORD,
+        _ => 0,
+    }
+}
+
+fn fold(c: char) -> char {
+    if c.is_ascii() {
+        c.to_ascii_lowercase()
+    } else {
+        c.to_lowercase().next().unwrap_or(c)
+    }
+}
+
+/// A query prepared once and matched against many candidates
+/// Smart case as in fzf: matching is case-sensitive only when the query
+/// contains an uppercase letter
+pub struct FuzzyQuery {
+    chars: Vec<char>,
+    ascii: Option<Vec<u8>>, // The query as bytes, for the fast prefilter
+    case_sensitive: bool,
+}
+
+impl FuzzyQuery {
+    pub fn new(query: &str) -> Self {
+        let query = query.trim();
+        let case_sensitive = query.chars().any(char::is_uppercase);
+        FuzzyQuery {
+            chars: query.chars().collect(),
+            ascii: query.is_ascii().then(|| query.as_bytes().to_vec()),
+            case_sensitive,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chars.is_empty()
+    }
+
+    fn eq(&self, text: char, query: char) -> bool {
+        if self.case_sensitive {
+            text == query
+        } else {
+            fold(text) == query
+        }
+    }
+
+    /// Cheap check that rejects most non-matching lines before decoding them
+    fn prefilter(&self, bytes: &[u8]) -> bool {
+        let Some(ascii) = &self.ascii else {
+            return true;
+        };
+        let mut rest = bytes;
+        for &q in ascii {
+            let found = if self.case_sensitive || !q.is_ascii_alphabetic() {
+                rest.iter().position(|&b| b == q)
+            } else {
+                let upper = q.to_ascii_uppercase();
+                rest.iter().position(|&b| b == q || b == upper)
+            };
+            match found {
+                Some(i) => rest = &rest[i + 1..],
+                None => return false,
+            }
+        }
+        true
+    }
+
+    /// Score `text`; None when the query is not a subsequence of it
+    /// Matched byte offsets are pushed to `positions` if given
+    pub fn score(&self, text: &str, mut positions: Option<&mut Vec<usize>>) -> Option<i32> {
+        if self.is_empty() {
+            return Some(0);
+        }
+
+        // Forward: end of the first occurrence of the whole query
+        let mut qi = 0;
+        let mut end = None;
+        for (i, c) in text.char_indices() {
+            if self.eq(c, self.chars[qi]) {
+                qi += 1;
+                if qi == self.chars.len() {
+                    end = Some(i + c.len_utf8());
+                    break;
+                }
+            }
+        }
+        let end = end?;
+
+        // Backward: the shortest window ending there
+        let mut qi = self.chars.len();
+        let mut start = 0;
+        for (i, c) in text[..end].char_indices().rev() {
+            if self.eq(c, self.chars[qi - 1]) {
+                qi -= 1;
+                if qi == 0 {
+                    start = i;
+                    break;
+                }
+            }
+        }
+
+        let mut prev = text[..start]
+            .chars()
+            .next_back()
+            .map_or(CharClass::White, char_class);
+        let (mut score, mut qi, mut consecutive, mut first_bonus) = (0, 0, 0, 0);
+        let mut in_gap = false;
+        for (i, c) in text[start..end].char_indices() {
+            let class = char_class(c);
+            if qi < self.chars.len() && self.eq(c, self.chars[qi]) {
+                if let Some(positions) = positions.as_deref_mut() {
+                    positions.push(start + i);
+                }
+                let mut b = bonus(prev, class);
+                if consecutive == 0 {
+                    first_bonus = b;
+                } else {
+                    if b >= BONUS_BOUNDARY && b > first_bonus {
+                        first_bonus = b;
+                    }
+                    b = b.max(first_bonus).max(BONUS_CONSECUTIVE);
+                }
+                score += SCORE_MATCH
+                    + if qi == 0 {
+                        b * BONUS_FIRST_CHAR_MULTIPLIER
+                    } else {
+                        b
+                    };
+                in_gap = false;
+                consecutive += 1;
+                qi += 1;
+            } else {
+                score += if in_gap {
+                    SCORE_GAP_EXTENSION
+                } else {
+                    SCORE_GAP_START
+                };
+                in_gap = true;
+                consecutive = 0;
+                first_bonus = 0;
+            }
+            prev = class;
+        }
+        Some(score)
+    }
+
+    /// Score and UTF-16 positions of the matched characters
+    pub fn positions(&self, text: &str) -> Option<(i32, Vec<usize>)> {
+        let mut positions = Vec::new();
+        let score = self.score(text, Some(&mut positions))?;
+        let mut utf16 = 0;
+        let mut last = 0;
+        for position in positions.iter_mut() {
+            utf16 += text[last..*position].encode_utf16().count();
+            last = *position;
+            *position = utf16;
+        }
+        Some((score, positions))
+    }
+}
+
+/// Best `max_results` of `candidates` (index, text bytes), best first
+/// Ties go to the shorter candidate, then the lower index. Only the winners
+/// get their match positions computed.
+pub fn rank<'a>(
+    query: &FuzzyQuery,
+    candidates: impl Iterator<Item = (usize, &'a [u8])>,
+    max_results: usize,
+) -> Vec<FuzzyMatch> {
+    if max_results == 0 {
+        return Vec::new();
+    }
+
+    // Min-heap of the best matches so far: (score, shorter, earlier)
+    let mut best = BinaryHeap::with_capacity(max_results + 1);
+    for (index, bytes) in candidates {
+        if !query.prefilter(bytes) {
+            continue;
+        }
+        let text = String::from_utf8_lossy(bytes);
+        let Some(score) = query.score(&text, None) else {
+            continue;
+        };
+        best.push(Reverse((score, Reverse(bytes.len()), Reverse(index), text)));
+        if best.len() > max_results {
+            best.pop();
+        }
+    }
+
+    best.into_sorted_vec()
+        .into_iter()
+        .map(|Reverse((score, _, Reverse(index), text))| FuzzyMatch {
+            index,
+            score,
+            positions: query.positions(&text).map(|(_, p)| p).unwrap_or_default(),
+            text: text.into_owned(),
+        })
+        .collect()
+}
+
+/// Rank the lines of a buffer ("go to line containing...")
+pub fn find_lines(buffer: &FileBuffer, query: &str, max_results: usize) -> Vec<FuzzyMatch> {
+    let query = FuzzyQuery::new(query);
+    let lines = (1..=buffer.line_count()).map(|line| {
+        let (start, end) = buffer.get_line_byte_range(line).unwrap();
+        let mut bytes = &buffer.content[start..end];
+        while let [rest @ .., b'\n' | b'\r'] = bytes {
+            bytes = rest;
+        }
+        (line, bytes)
+    });
+    rank(&query, lines, max_results)
+}
+
+/// Rank a list of strings, e.g. file paths for quick-open
+pub fn filter(candidates: &[String], query: &str, max_results: usize) -> Vec<FuzzyMatch> {
+    let query = FuzzyQuery::new(query);
+    let items = candidates
+        .iter()
+        .enumerate()
+        .map(|(i, text)| (i, text.as_bytes()));
+    rank(&query, items, max_results)
+}
+
+/// Rank outline symbols by name, nested ones included
+pub fn find_symbols(symbols: &[Symbol], query: &str, max_results: usize) -> Vec<SymbolMatch> {
+    fn flatten<'a>(symbols: &'a [Symbol], out: &mut Vec<&'a Symbol>) {
+        for symbol in symbols {
+            out.push(symbol);
+            flatten(&symbol.children, out);
+        }
+    }
+    let mut flat = Vec::new();
+    flatten(symbols, &mut flat);
+
+    let query = FuzzyQuery::new(query);
+    let names = flat
+        .iter()
+        .enumerate()
+        .map(|(i, symbol)| (i, symbol.name.as_bytes()));
+    rank(&query, names, max_results)
+        .into_iter()
+        .map(|m| {
+            let symbol = flat[m.index];
+            SymbolMatch {
+                name: m.text,
+                kind: symbol.kind,
+                start_line: symbol.start_line,
+                end_line: symbol.end_line,
+                score: m.score,
+                positions: m.positions,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn list(items: &[&str]) -> Vec<String> {
+        items.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_ranks_boundaries_and_consecutive_matches_first() {
+        let paths = list(&[
+            "src/fabric/bundle.rs",
+            "src/file_buffer.rs",
+            "docs/FILE-BUFFER.md",
+            "src/lib.rs",
+        ]);
+        let ranked: Vec<usize> = filter(&paths, "fbu", 10).iter().map(|m| m.index).collect();
+        assert_eq!(ranked, [1, 2, 0]);
+
+        // Smart case: an uppercase letter makes the query case-sensitive
+        let ranked: Vec<usize> = filter(&paths, "FB", 10).iter().map(|m| m.index).collect();
+        assert_eq!(ranked, [2]);
+    }
+
+    #[test]
+    fn test_positions_are_utf16_indices() {
+        let query = FuzzyQuery::new("ñé");
+        let (_, positions) = query.positions("😀 Ñandú café").unwrap();
+        // "😀" is two UTF-16 units, so Ñ is at 3 and é at 12
+        assert_eq!(positions, [3, 12]);
+        assert!(query.positions("nothing").is_none());
+    }
+
+    #[test]
+    fn test_find_lines_keeps_best_and_trims_line_endings() {
+        let mut content = String::new();
+        for i in 0..1000 {
+            content.push_str(&format!("let value_{} = compute({});\r\n", i, i));
+        }
+        content.push_str("fn parse_config() {\r\n");
+        let buffer = FileBuffer::new(content.into_bytes()).unwrap();
+
+        let matches = find_lines(&buffer, "pcfg", 3);
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].index, 1001);
+        assert_eq!(matches[0].text, "fn parse_config() {");
+
+        let matches = find_lines(&buffer, "value_99", 2);
+        let lines: Vec<usize> = matches.iter().map(|m| m.index).collect();
+        assert_eq!(lines, [100, 991]);
+    }
+}
@@ -0,0 +1,146 @@
+use crate::file_buffer::FileBuffer;
+use regex::{RegexSet, RegexSetBuilder};
+use serde::{Deserialize, Serialize};
+
+/// Mapping from the lines of a filtered view back to its source buffer
+/// The view's own text lives in a regular FileBuffer so it pages through
+/// get_line_range like any other file
+pub struct GrepView {
+    pub source_id: u32,
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+    pub case_insensitive: bool,
+    pub line_map: Vec<u32>, // Source line (1-based) for each view line
+}
+
+impl GrepView {
+    /// Source line for a view line (both 1-based)
+    pub fn source_line(&self, view_line: usize) -> Result<u32, String> {
+        view_line
+            .checked_sub(1)
+            .and_then(|i| self.line_map.get(i))
+            .copied()
+            .ok_or_else(|| {
+                format!(
+                    "Line {} out of range (view has {} lines)",
+                    view_line,
+                    self.line_map.len()
+                )
+            })
+    }
+
+    /// View line showing `source_line`, or the next one after it
+    /// Returns None when no view line is at or below `source_line`
+    pub fn view_line(&self, source_line: u32) -> Option<u32> {
+        let i = self.line_map.partition_point(|&l| l < source_line);
+        (i < self.line_map.len()).then_some(i as u32 + 1)
+    }
+
+    /// Source lines for a range of view lines (1-based, inclusive)
+    pub fn source_lines(&self, start_line: usize, end_line: usize) -> Result<&[u32], String> {
+        if start_line == 0 || start_line > end_line || end_line > self.line_map.len() {
+            return Err(format!(
+                "Invalid range {}-{} (view has {} lines)",
+                start_line,
+                end_line,
+                self.line_map.len()
+            ));
+        }
+
+        Ok(&self.line_map[start_line - 1..end_line])
+    }
+}
+
+/// View metadata returned to JavaScript
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct GrepViewInfo {
+    pub file_id: u32,
+    pub source_id: u32,
+    pub line_count: usize,
+    pub source_line_count: usize,
+}
+
+fn build_set(patterns: &[String], case_insensitive: bool) -> Result<RegexSet, String> {
+    RegexSetBuilder::new(patterns)
+        .case_insensitive(case_insensitive)
+        .build()
+        .map_err(|e| format!("Invalid regex: {}", e))
+}
+
+/// Collect the lines of `source` that match any `include` pattern (every line
+/// if there are none) and no `exclude` pattern
+/// Returns the view text and, for each view line, its source line number
+pub fn filter_lines(
+    source: &FileBuffer,
+    include: &[String],
+    exclude: &[String],
+    case_insensitive: bool,
+) -> Result<(Vec<u8>, Vec<u32>), String> {
+    let include = build_set(include, case_insensitive)?;
+    let exclude = build_set(exclude, case_insensitive)?;
+
+    let mut content = Vec::new();
+    let mut line_map = Vec::new();
+
+    for line_num in 1..=source.line_count() {
+        let (start, end) = source.get_line_byte_range(line_num)?;
+        let line = &source.content[start..end];
+        let text = String::from_utf8_lossy(line);
+        let text = text.trim_end_matches(['\n', '\r']);
+
+        let included = include.is_empty() || include.is_match(text);
+        if included && !exclude.is_match(text) {
+            content.extend_from_slice(line);
+            if !line.ends_with(b"\n") {
+                content.push(b'\n');
+            }
+            line_map.push(line_num as u32);
+        }
+    }
+
+    Ok((content, line_map))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(patterns: &[&str]) -> Vec<String> {
+        patterns.iter().map(|p| p.to_string()).collect()
+    }
+
+    #[test]
+    fn test_include_and_exclude() {
+        let source =
+            FileBuffer::new(b"INFO a\nERROR b\nWARN c\nERROR healthcheck\nERROR d".to_vec())
+                .unwrap();
+
+        let (content, line_map) = filter_lines(
+            &source,
+            &strings(&["error", "warn"]),
+            &strings(&["healthcheck"]),
+            true,
+        )
+        .unwrap();
+
+        assert_eq!(content, b"ERROR b\nWARN c\nERROR d\n");
+        assert_eq!(line_map, vec![2, 3, 5]);
+    }
+
+    #[test]
+    fn test_line_mapping() {
+        let view = GrepView {
+            source_id: 1,
+            include: Vec::new(),
+            exclude: Vec::new(),
+            case_insensitive: false,
+            line_map: vec![2, 3, 5],
+        };
+
+        assert_eq!(view.source_line(3).unwrap(), 5);
+        assert!(view.source_line(4).is_err());
+        assert_eq!(view.view_line(4), Some(3));
+        assert_eq!(view.view_line(6), None);
+        assert_eq!(view.source_lines(2, 3).unwrap(), &[3, 5]);
+    }
+}
@@ -0,0 +1,357 @@
+use md5::Md5;
+use serde::{Deserialize, Serialize};
+use sha1::Sha1;
+use sha2::{Digest, Sha256, Sha512};
+use std::fmt::Write;
+use std::str::FromStr;
+use xxhash_rust::xxh3::Xxh3;
+use xxhash_rust::xxh64::Xxh64;
+
+/// Supported checksum algorithms
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HashAlgorithm {
+    Md5,
+    Sha1,
+    Sha256,
+    Sha512,
+    Crc32,
+    Xxh64,
+    Xxh3, // 64-bit XXH3
+}
+
+impl FromStr for HashAlgorithm {
+    type Err = String;
+
+    /// Also accepts the BSD tags and sidecar extensions ("SHA256", "sha-256", "md5")
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().replace('-', "").as_str() {
+            "md5" => Ok(HashAlgorithm::Md5),
+            "sha1" => Ok(HashAlgorithm::Sha1),
+            "sha256" => Ok(HashAlgorithm::Sha256),
+            "sha512" => Ok(HashAlgorithm::Sha512),
+            "crc32" => Ok(HashAlgorithm::Crc32),
+            "xxh64" | "xxhash" | "xxhash64" => Ok(HashAlgorithm::Xxh64),
+            "xxh3" => Ok(HashAlgorithm::Xxh3),
+            _ => Err(format!("Unknown hash algorithm '{}'", s)),
+        }
+    }
+}
+
+impl HashAlgorithm {
+    pub fn name(self) -> &'static str {
+        match self {
+            HashAlgorithm::Md5 => "md5",
+            HashAlgorithm::Sha1 => "sha1",
+            HashAlgorithm::Sha256 => "sha256",
+            HashAlgorithm::Sha512 => "sha512",
+            HashAlgorithm::Crc32 => "crc32",
+            HashAlgorithm::Xxh64 => "xxh64",
+            HashAlgorithm::Xxh3 => "xxh3",
+        }
+    }
+
+    /// Length of the hex digest
+    fn hex_len(self) -> usize {
+        match self {
+            HashAlgorithm::Crc32 => 8,
+            HashAlgorithm::Xxh64 | HashAlgorithm::Xxh3 => 16,
+            HashAlgorithm::Md5 => 32,
+            HashAlgorithm::Sha1 => 40,
+            HashAlgorithm::Sha256 => 64,
+            HashAlgorithm::Sha512 => 128,
+        }
+    }
+
+    /// Guess the algorithm from the length of a hex digest
+    /// 16 digits is taken as xxh64; pass a hint for xxh3
+    fn from_hex_len(len: usize) -> Option<Self> {
+        match len {
+            8 => Some(HashAlgorithm::Crc32),
+            16 => Some(HashAlgorithm::Xxh64),
+            32 => Some(HashAlgorithm::Md5),
+            40 => Some(HashAlgorithm::Sha1),
+            64 => Some(HashAlgorithm::Sha256),
+            128 => Some(HashAlgorithm::Sha512),
+            _ => None,
+        }
+    }
+}
+
+/// Incremental hasher; feed chunks with `update` for files too large to hold
+pub enum Hasher {
+    Md5(Md5),
+    Sha1(Sha1),
+    Sha256(Sha256),
+    Sha512(Box<Sha512>),
+    Crc32(crc32fast::Hasher),
+    Xxh64(Xxh64),
+    Xxh3(Box<Xxh3>),
+}
+
+impl Hasher {
+    pub fn new(algorithm: HashAlgorithm) -> Self {
+        match algorithm {
+            HashAlgorithm::Md5 => Hasher::Md5(Md5::new()),
+            HashAlgorithm::Sha1 => Hasher::Sha1(Sha1::new()),
+            HashAlgorithm::Sha256 => Hasher::Sha256(Sha256::new()),
+            HashAlgorithm::Sha512 => Hasher::Sha512(Box::default()),
+            HashAlgorithm::Crc32 => Hasher::Crc32(crc32fast::Hasher::new()),
+            HashAlgorithm::Xxh64 => Hasher::Xxh64(Xxh64::new(0)),
+            HashAlgorithm::Xxh3 => Hasher::Xxh3(Box::default()),
+        }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        match self {
+            Hasher::Md5(h) => h.update(data),
+            Hasher::Sha1(h) => h.update(data),
+            Hasher::Sha256(h) => h.update(data),
+            Hasher::Sha512(h) => h.update(data),
+            Hasher::Crc32(h) => h.update(data),
+            Hasher::Xxh64(h) => h.update(data),
+            Hasher::Xxh3(h) => h.update(data),
+        }
+    }
+
+    /// Lowercase hex digest
+    pub fn finish(self) -> String {
+        match self {
+            Hasher::Md5(h) => to_hex(&h.finalize()),
+            Hasher::Sha1(h) => to_hex(&h.finalize()),
+            Hasher::Sha256(h) => to_hex(&h.finalize()),
+            Hasher::Sha512(h) => to_hex(&h.finalize()),
+            Hasher::Crc32(h) => format!("{:08x}", h.finalize()),
+            Hasher::Xxh64(h) => format!("{:016x}", h.digest()),
+            Hasher::Xxh3(h) => format!("{:016x}", h.digest()),
+        }
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut hex, b| {
+        let _ = write!(hex, "{:02x}", b);
+        hex
+    })
+}
+
+/// One digest of a buffer, range or file
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct HashResult {
+    pub algorithm: String,
+    pub hex: String,
+}
+
+/// Several algorithms computed in a single pass over the data
+pub struct MultiHasher {
+    hashers: Vec<(HashAlgorithm, Hasher)>,
+}
+
+impl MultiHasher {
+    pub fn new(algorithms: &[HashAlgorithm]) -> Self {
+        let mut hashers: Vec<(HashAlgorithm, Hasher)> = Vec::new();
+        for &algorithm in algorithms {
+            if !hashers.iter().any(|(a, _)| *a == algorithm) {
+                hashers.push((algorithm, Hasher::new(algorithm)));
+            }
+        }
+        MultiHasher { hashers }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        for (_, hasher) in &mut self.hashers {
+            hasher.update(data);
+        }
+    }
+
+    pub fn finish(self) -> Vec<HashResult> {
+        self.hashers
+            .into_iter()
+            .map(|(algorithm, hasher)| HashResult {
+                algorithm: algorithm.name().to_string(),
+                hex: hasher.finish(),
+            })
+            .collect()
+    }
+}
+
+/// Hash in-memory bytes with each algorithm
+pub fn hash_bytes(algorithms: &[HashAlgorithm], data: &[u8]) -> Vec<HashResult> {
+    let mut hasher = MultiHasher::new(algorithms);
+    hasher.update(data);
+    hasher.finish()
+}
+
+/// A digest read from a checksum sidecar file
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExpectedHash {
+    pub algorithm: HashAlgorithm,
+    pub hex: String,
+    pub file_name: Option<String>,
+}
+
+/// Outcome of comparing a computed digest with the expected one
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct VerifyResult {
+    pub algorithm: String,
+    pub file_name: Option<String>,
+    pub expected: String,
+    pub actual: String,
+    pub matches: bool,
+}
+
+/// Parse one sidecar line:
+///   coreutils  `<hex>  <name>` or `<hex> *<name>` (binary mode)
+///   BSD        `SHA256 (<name>) = <hex>`
+///   bare       `<hex>`
+fn parse_sidecar_line(line: &str, hint: Option<HashAlgorithm>) -> Option<ExpectedHash> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return None;
+    }
+
+    if let Some((tag, rest)) = line.split_once(" (") {
+        if let (Ok(algorithm), Some((name, hex))) = (tag.parse(), rest.rsplit_once(") = ")) {
+            return Some(ExpectedHash {
+                algorithm,
+                hex: hex.trim().to_ascii_lowercase(),
+                file_name: Some(name.to_string()),
+            });
+        }
+    }
+
+    let (hex, name) = match line.split_once(char::is_whitespace) {
+        Some((hex, name)) => {
+            let name = name.trim_start();
+            let name = name.strip_prefix('*').unwrap_or(name);
+            (hex, Some(name.to_string()))
+        }
+        None => (line, None),
+    };
+    if !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    let algorithm = match hint {
+        Some(algorithm) if algorithm.hex_len() == hex.len() => algorithm,
+        _ => HashAlgorithm::from_hex_len(hex.len())?,
+    };
+
+    Some(ExpectedHash {
+        algorithm,
+        hex: hex.to_ascii_lowercase(),
+        file_name: name,
+    })
+}
+
+/// Find the expected digest for `file_name` in a `.sha256`-style sidecar
+/// Without a name, or when no entry names it, a single-entry sidecar still applies
+/// `hint` (usually from the sidecar extension) settles ambiguous digest lengths
+pub fn parse_sidecar(
+    content: &str,
+    file_name: Option<&str>,
+    hint: Option<HashAlgorithm>,
+) -> Result<ExpectedHash, String> {
+    let entries: Vec<ExpectedHash> = content
+        .lines()
+        .filter_map(|line| parse_sidecar_line(line, hint))
+        .collect();
+
+    let base_name = |path: &str| path.rsplit(['/', '\\']).next().unwrap_or(path).to_string();
+    if let Some(file_name) = file_name {
+        let wanted = base_name(file_name);
+        let named = entries
+            .iter()
+            .find(|e| e.file_name.as_deref().map(base_name).as_deref() == Some(wanted.as_str()));
+        if let Some(entry) = named {
+            return Ok(entry.clone());
+        }
+    }
+
+    match entries.len() {
+        0 => Err("No checksum found in sidecar".to_string()),
+        1 => Ok(entries.into_iter().next().unwrap()),
+        n => Err(format!(
+            "Sidecar lists {} checksums and none is for {}",
+            n,
+            file_name.unwrap_or("this file")
+        )),
+    }
+}
+
+/// Compare a computed digest with the expected one, ignoring case
+pub fn verify(expected: &ExpectedHash, actual: String) -> VerifyResult {
+    VerifyResult {
+        algorithm: expected.algorithm.name().to_string(),
+        file_name: expected.file_name.clone(),
+        matches: actual.eq_ignore_ascii_case(&expected.hex),
+        expected: expected.hex.clone(),
+        actual,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_known_digests() {
+        let all = [
+            HashAlgorithm::Md5,
+            HashAlgorithm::Sha1,
+            HashAlgorithm::Sha256,
+            HashAlgorithm::Crc32,
+            HashAlgorithm::Xxh64,
+            HashAlgorithm::Md5, // Duplicates are computed once
+        ];
+        let hex: Vec<String> = hash_bytes(&all, b"abc")
+            .into_iter()
+            .map(|r| r.hex)
+            .collect();
+        assert_eq!(
+            hex,
+            [
+                "900150983cd24fb0d6963f7d28e17f72",
+                "a9993e364706816aba3e25717850c26c9cd0d89d",
+                "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad",
+                "352441c2",
+                "44bc2cf5ad770999",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_streaming_matches_one_shot() {
+        let data = b"The quick brown fox jumps over the lazy dog".repeat(100);
+        for algorithm in ["sha512", "xxh3", "crc32"] {
+            let algorithm: HashAlgorithm = algorithm.parse().unwrap();
+            let mut hasher = Hasher::new(algorithm);
+            for chunk in data.chunks(7) {
+                hasher.update(chunk);
+            }
+            assert_eq!(hasher.finish(), hash_bytes(&[algorithm], &data)[0].hex);
+        }
+    }
+
+    #[test]
+    fn test_parse_sidecar_formats() {
+        let sha = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+        let coreutils = format!(
+            "{}  other.tar.gz\n{} *dist/app.tar.gz\n",
+            "0".repeat(64),
+            sha
+        );
+        let expected = parse_sidecar(&coreutils, Some("/tmp/app.tar.gz"), None).unwrap();
+        assert_eq!(expected.hex, sha);
+        assert_eq!(expected.algorithm, HashAlgorithm::Sha256);
+        assert!(verify(&expected, sha.to_uppercase()).matches);
+        assert!(parse_sidecar(&coreutils, Some("missing.zip"), None).is_err());
+
+        let bsd = "MD5 (app.zip) = 900150983CD24FB0D6963F7D28E17F72";
+        let expected = parse_sidecar(bsd, None, None).unwrap();
+        assert_eq!(expected.algorithm, HashAlgorithm::Md5);
+        assert_eq!(expected.file_name.as_deref(), Some("app.zip"));
+
+        let bare = parse_sidecar("44bc2cf5ad770999\n", None, Some(HashAlgorithm::Xxh3)).unwrap();
+        assert_eq!(bare.algorithm, HashAlgorithm::Xxh3);
+        assert!(!verify(&bare, "0".repeat(16)).matches);
+    }
+}
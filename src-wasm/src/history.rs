@@ -0,0 +1,121 @@
+use std::collections::VecDeque;
+
+/// Total bytes (removed + inserted) kept across the undo and redo stacks
+/// The oldest edits are dropped first once the limit is exceeded; a single
+/// edit over the limit is not kept at all
+const MAX_HISTORY_BYTES: usize = 64 * 1024 * 1024;
+
+/// One replacement of `removed` by `inserted` at byte `start`
+pub struct Edit {
+    pub start: usize,
+    pub removed: Vec<u8>,
+    pub inserted: Vec<u8>,
+}
+
+impl Edit {
+    fn size(&self) -> usize {
+        self.removed.len() + self.inserted.len()
+    }
+
+    /// The edit that reverts this one
+    fn inverse(self) -> Edit {
+        Edit {
+            start: self.start,
+            removed: self.inserted,
+            inserted: self.removed,
+        }
+    }
+}
+
+/// Undo/redo stacks of a buffer
+#[derive(Default)]
+pub struct EditHistory {
+    undo: VecDeque<Edit>,
+    redo: Vec<Edit>,
+    bytes: usize,
+}
+
+impl EditHistory {
+    /// Rebuild from saved undo and redo stacks (oldest edit first)
+    pub fn from_stacks(undo: Vec<Edit>, redo: Vec<Edit>) -> Self {
+        let bytes = undo.iter().chain(&redo).map(Edit::size).sum();
+        EditHistory {
+            undo: undo.into(),
+            redo,
+            bytes,
+        }
+    }
+
+    /// Bytes held by both stacks
+    pub fn bytes(&self) -> usize {
+        self.bytes
+    }
+
+    /// Undo and redo stacks, oldest edit first
+    pub fn stacks(&self) -> (&VecDeque<Edit>, &[Edit]) {
+        (&self.undo, &self.redo)
+    }
+
+    /// Record a new edit; anything that could be redone is discarded
+    /// An edit larger than MAX_HISTORY_BYTES (e.g. sorting a huge file) is
+    /// not kept, and neither are the edits before it, which no longer apply
+    /// to the content without it
+    pub fn record(&mut self, edit: Edit) {
+        self.bytes -= self.redo.drain(..).map(|e| e.size()).sum::<usize>();
+        if edit.size() > MAX_HISTORY_BYTES {
+            self.undo.clear();
+            self.bytes = 0;
+            return;
+        }
+        self.bytes += edit.size();
+        self.undo.push_back(edit);
+
+        while self.bytes > MAX_HISTORY_BYTES {
+            let dropped = self.undo.pop_front().unwrap();
+            self.bytes -= dropped.size();
+        }
+    }
+
+    /// Take the edit to revert, already inverted, and move it to the redo stack
+    pub fn undo(&mut self) -> Option<&Edit> {
+        let edit = self.undo.pop_back()?.inverse();
+        self.redo.push(edit);
+        self.redo.last()
+    }
+
+    /// Take the edit to re-apply and move it back to the undo stack
+    pub fn redo(&mut self) -> Option<&Edit> {
+        let edit = self.redo.pop()?.inverse();
+        self.undo.push_back(edit);
+        self.undo.back()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn edit(start: usize, size: usize) -> Edit {
+        Edit {
+            start,
+            removed: Vec::new(),
+            inserted: vec![b'x'; size],
+        }
+    }
+
+    #[test]
+    fn test_oversized_edit_clears_history() {
+        let mut history = EditHistory::default();
+        history.record(edit(0, 10));
+        history.record(edit(0, MAX_HISTORY_BYTES + 1));
+        assert!(history.undo().is_none());
+        assert_eq!(history.bytes, 0);
+
+        // Oldest edits go first once the budget is exceeded
+        history.record(edit(1, MAX_HISTORY_BYTES / 2));
+        history.record(edit(2, MAX_HISTORY_BYTES / 2));
+        history.record(edit(3, 1));
+        let starts: Vec<usize> = history.stacks().0.iter().map(|e| e.start).collect();
+        assert_eq!(starts, [2, 3]);
+    }
+}
@@ -2,20 +2,118 @@ use wasm_bindgen::prelude::*;
 use std::collections::HashMap;
 use std::sync::Mutex;
 
-mod file_buffer;
-use file_buffer::{FileBuffer, FileInfo};
+mod analysis;
+mod anchors;
+mod content;
+mod document;
+mod encoding;
+mod errors;
+pub mod file_buffer; // Also used natively by the desktop backend
+mod folding;
+mod fuzzy;
+mod grep_view;
+pub mod hashing; // Also used natively by the desktop backend
+mod history;
+mod line_ops;
+mod log_index;
+mod merge;
+mod multi_search;
+mod outline;
+#[cfg(feature = "parallel")]
+mod parallel;
+pub mod patch; // Also used natively by the desktop backend
+mod positions;
+mod registry;
+pub mod secrets; // Also used natively by the desktop backend
+mod segments;
+mod snapshot;
+mod unicode;
+mod whitespace;
+use anchors::Anchor;
+use encoding::{Codec, TransformResult};
+use errors::{to_js, ApiError, ErrorCode};
+use file_buffer::{EditResult, FileBuffer};
+use grep_view::{GrepView, GrepViewInfo};
+use hashing::HashAlgorithm;
+use line_ops::{LineOp, SortMode, SortOptions};
+use log_index::{LogIndex, LogLevel};
+use merge::{Merge3, MergeResult, Resolution};
+use multi_search::SearchAllOptions;
+use patch::FilePatchResult;
+use positions::{CharIndex, Position};
+use registry::BufferRegistry;
+use secrets::SecretKind;
+use snapshot::RestoredBuffer;
+use unicode::{IssueKind, NormalForm};
+use whitespace::{IndentTarget, NormalizeOptions};
 
 // Global file storage: file_id -> FileBuffer
 // Using lazy_static pattern for global state in WASM
-static FILE_BUFFERS: Mutex<Option<HashMap<u32, FileBuffer>>> = Mutex::new(None);
+static FILE_BUFFERS: Mutex<Option<BufferRegistry>> = Mutex::new(None);
 static NEXT_FILE_ID: Mutex<u32> = Mutex::new(1);
 
+// Pending three-way merges: merged file_id -> merge state
+static MERGE_SESSIONS: Mutex<Option<HashMap<u32, Merge3>>> = Mutex::new(None);
+
+// Filtered views: view file_id -> mapping back to the source buffer
+static GREP_VIEWS: Mutex<Option<HashMap<u32, GrepView>>> = Mutex::new(None);
+
 /// Initialize the global storage
 fn ensure_initialized() {
     let mut buffers = FILE_BUFFERS.lock().unwrap();
     if buffers.is_none() {
-        *buffers = Some(HashMap::new());
+        *buffers = Some(BufferRegistry::default());
+    }
+
+    let mut merges = MERGE_SESSIONS.lock().unwrap();
+    if merges.is_none() {
+        *merges = Some(HashMap::new());
+    }
+
+    let mut views = GREP_VIEWS.lock().unwrap();
+    if views.is_none() {
+        *views = Some(HashMap::new());
+    }
+}
+
+/// Store a buffer in the global map under a fresh file ID
+fn store_buffer(buffer: FileBuffer) -> u32 {
+    let file_id = {
+        let mut next_id = NEXT_FILE_ID.lock().unwrap();
+        let id = *next_id;
+        *next_id += 1;
+        id
+    };
+
+    let mut buffers = FILE_BUFFERS.lock().unwrap();
+    if let Some(map) = buffers.as_mut() {
+        map.insert(file_id, buffer);
     }
+
+    file_id
+}
+
+/// Run `op` on a stored buffer, reporting typed errors (see Document)
+fn with_stored_buffer<T>(
+    file_id: u32,
+    op: impl FnOnce(&mut FileBuffer) -> Result<T, ApiError>,
+) -> Result<T, ApiError> {
+    ensure_initialized();
+
+    let mut buffers = FILE_BUFFERS.lock().unwrap();
+    let map = buffers
+        .as_mut()
+        .ok_or_else(|| ApiError::not_found(format!("File {} not found", file_id)))?;
+    op(map.get_mut(&file_id)?)
+}
+
+/// Run `op` on a stored buffer for the ID-based functions, which report
+/// errors as plain message strings
+fn with_buffer<T>(
+    file_id: u32,
+    op: impl FnOnce(&mut FileBuffer) -> Result<T, ApiError>,
+) -> Result<T, JsValue> {
+    with_stored_buffer(file_id, op).map_err(ApiError::legacy)
 }
 
 /// Initialize WASM module (called once on load)
@@ -37,89 +135,1046 @@ pub fn init() {
 pub fn create_file_buffer(content: &[u8]) -> Result<u32, JsValue> {
     ensure_initialized();
 
-    // Generate unique file ID
-    let file_id = {
-        let mut next_id = NEXT_FILE_ID.lock().unwrap();
-        let id = *next_id;
-        *next_id += 1;
-        id
-    };
-
     // Create buffer and index lines
     let buffer = FileBuffer::new(content.to_vec())
         .map_err(|e| JsValue::from_str(&format!("Failed to create buffer: {}", e)))?;
 
-    // Store in global map
+    // Store in global map under a unique file ID
+    Ok(store_buffer(buffer))
+}
+
+/// Get file metadata
+#[wasm_bindgen]
+pub fn get_file_info(file_id: u32) -> Result<JsValue, JsValue> {
+    with_buffer(file_id, |buffer| to_js(&document::file_info(buffer)))
+}
+
+/// Get a range of lines
+/// Lines are 1-indexed (line 1 is the first line)
+#[wasm_bindgen]
+pub fn get_line_range(file_id: u32, start_line: u32, end_line: u32) -> Result<String, JsValue> {
+    with_buffer(file_id, |buffer| {
+        buffer.get_line_range(start_line as usize, end_line as usize)
+    })
+}
+
+/// Search file for pattern (supports regex)
+/// Returns up to max_results matches
+#[wasm_bindgen]
+pub fn search_file(file_id: u32, pattern: &str, max_results: usize) -> Result<JsValue, JsValue> {
+    with_buffer(file_id, |buffer| {
+        to_js(&buffer.search(pattern, max_results)?)
+    })
+}
+
+/// Search every open buffer (or `options.file_ids`) for a pattern
+/// `options`: `{ case_insensitive, literal, file_ids, max_per_file, resume }`.
+/// Results are grouped per file with counts and truncation flags. At most
+/// max_results (at least 1) matches are returned; when cut short, `next`
+/// is the cursor to pass as `options.resume` for the next page.
+#[wasm_bindgen]
+pub fn search_all(pattern: &str, options: JsValue, max_results: usize) -> Result<JsValue, JsValue> {
+    ensure_initialized();
+
+    let options: Option<SearchAllOptions> = serde_wasm_bindgen::from_value(options)
+        .map_err(|e| JsValue::from_str(&format!("Invalid options: {}", e)))?;
+
     let mut buffers = FILE_BUFFERS.lock().unwrap();
     if let Some(map) = buffers.as_mut() {
-        map.insert(file_id, buffer);
+        let result =
+            multi_search::search_all(map, pattern, &options.unwrap_or_default(), max_results)
+                .map_err(ApiError::legacy)?;
+        serde_wasm_bindgen::to_value(&result)
+            .map_err(|e| JsValue::from_str(&format!("Serialization error: {}", e)))
+    } else {
+        Err(JsValue::from_str("Storage not initialized"))
     }
+}
 
-    Ok(file_id)
+/// Fuzzy-find lines for "go to line containing..." (fzf-style scoring)
+/// Returns the best max_results lines with the UTF-16 positions of the
+/// matched characters for highlighting
+#[wasm_bindgen]
+pub fn fuzzy_find_lines(file_id: u32, query: &str, max_results: usize) -> Result<JsValue, JsValue> {
+    with_buffer(file_id, |buffer| {
+        to_js(&fuzzy::find_lines(buffer, query, max_results))
+    })
 }
 
-/// Get file metadata
+/// Fuzzy-rank a list of strings such as file paths for quick-open
+/// `index` in each match refers to the position in `candidates`
 #[wasm_bindgen]
-pub fn get_file_info(file_id: u32) -> Result<JsValue, JsValue> {
+pub fn fuzzy_filter(
+    candidates: Vec<String>,
+    query: &str,
+    max_results: usize,
+) -> Result<JsValue, JsValue> {
+    serde_wasm_bindgen::to_value(&fuzzy::filter(&candidates, query, max_results))
+        .map_err(|e| JsValue::from_str(&format!("Serialization error: {}", e)))
+}
+
+/// Append bytes to a buffer (e.g. new lines of a followed log file)
+/// Only the appended bytes are indexed. Returns the first changed line and
+/// the new line count so the editor can refresh and auto-scroll.
+#[wasm_bindgen]
+pub fn append_to_buffer(file_id: u32, content: &[u8]) -> Result<JsValue, JsValue> {
+    with_buffer(file_id, |buffer| to_js(&document::append(buffer, content)))
+}
+
+/// Replace the whole content of a buffer, keeping its file ID
+/// Used when a followed file was truncated or rotated and has to be re-read
+#[wasm_bindgen]
+pub fn replace_buffer_content(file_id: u32, content: &[u8]) -> Result<(), JsValue> {
     ensure_initialized();
 
-    let buffers = FILE_BUFFERS.lock().unwrap();
-    if let Some(map) = buffers.as_ref() {
-        let buffer = map
-            .get(&file_id)
-            .ok_or_else(|| JsValue::from_str(&format!("File {} not found", file_id)))?;
-
-        let stats = buffer.get_stats();
-        let info = FileInfo {
-            size: stats.size,
-            line_count: stats.line_count,
-            encoding: "UTF-8".to_string(),
-            index_size: stats.index_size,
-        };
+    let mut buffers = FILE_BUFFERS.lock().unwrap();
+    if let Some(map) = buffers.as_mut() {
+        if !map.contains_key(&file_id) {
+            return Err(JsValue::from_str(&format!("File {} not found", file_id)));
+        }
 
-        serde_wasm_bindgen::to_value(&info)
-            .map_err(|e| JsValue::from_str(&format!("Serialization error: {}", e)))
+        let buffer = FileBuffer::new(content.to_vec())
+            .map_err(|e| JsValue::from_str(&format!("Failed to create buffer: {}", e)))?;
+        swap_buffer(map, file_id, buffer);
+        Ok(())
     } else {
         Err(JsValue::from_str("Storage not initialized"))
     }
 }
 
-/// Get a range of lines
-/// Lines are 1-indexed (line 1 is the first line)
+/// Put a new buffer under an existing ID
+/// Anchors cannot follow a wholesale replacement; the old buffer's anchors
+/// are reported deleted through take_deleted_anchors instead of vanishing.
+/// Edits that keep the buffer go through FileBuffer::replace_content.
+fn swap_buffer(map: &mut BufferRegistry, file_id: u32, mut buffer: FileBuffer) {
+    if let Ok(old) = map.get_mut(&file_id) {
+        buffer.anchors = std::mem::take(&mut old.anchors);
+        buffer.anchors.clear();
+    }
+    map.insert(file_id, buffer);
+}
+
+/// Replace the bytes in `start_byte..end_byte` of a buffer with `text`
+/// Cached indexes (folding, log) are dropped and rebuilt on next use, and
+/// the edit is recorded for undo_edit. Returns the new line count.
 #[wasm_bindgen]
-pub fn get_line_range(file_id: u32, start_line: u32, end_line: u32) -> Result<String, JsValue> {
+pub fn edit_buffer(
+    file_id: u32,
+    start_byte: usize,
+    end_byte: usize,
+    text: &str,
+) -> Result<usize, JsValue> {
+    with_buffer(file_id, |buffer| {
+        document::edit(buffer, start_byte, end_byte, text.as_bytes()).map(|r| r.line_count)
+    })
+}
+
+/// Undo the most recent edit (from edit_buffer or a line operation)
+/// Returns the first changed line and new size, or null if nothing to undo
+#[wasm_bindgen]
+pub fn undo_edit(file_id: u32) -> Result<JsValue, JsValue> {
+    step_history(file_id, FileBuffer::undo)
+}
+
+/// Redo the most recently undone edit, or return null
+#[wasm_bindgen]
+pub fn redo_edit(file_id: u32) -> Result<JsValue, JsValue> {
+    step_history(file_id, FileBuffer::redo)
+}
+
+fn step_history(
+    file_id: u32,
+    step: fn(&mut FileBuffer) -> Option<usize>,
+) -> Result<JsValue, JsValue> {
+    with_buffer(file_id, |buffer| {
+        let result = step(buffer).map(|first_changed_line| EditResult {
+            first_changed_line,
+            line_count: buffer.line_count(),
+            size: buffer.content.len(),
+        });
+        to_js(&result)
+    })
+}
+
+/// Run a line operation on lines `start_line..=end_line` (whole file if
+/// omitted) as a single undoable edit
+fn apply_line_op(
+    file_id: u32,
+    start_line: Option<usize>,
+    end_line: Option<usize>,
+    op: LineOp,
+) -> Result<JsValue, JsValue> {
+    with_buffer(file_id, |buffer| {
+        let result = line_ops::apply(buffer, start_line, end_line, op)
+            .map_err(ApiError::invalid_argument)?;
+        to_js(&result)
+    })
+}
+
+/// Sort lines
+/// `mode` is lexical, natural, numeric or case-insensitive. The key is the
+/// whole line, field `key_column` (1-based, split on `delimiter` or
+/// whitespace), or the first capture group of `key_pattern`.
+#[allow(clippy::too_many_arguments)]
+#[wasm_bindgen]
+pub fn sort_lines(
+    file_id: u32,
+    start_line: Option<usize>,
+    end_line: Option<usize>,
+    mode: &str,
+    descending: bool,
+    key_column: Option<usize>,
+    delimiter: Option<String>,
+    key_pattern: Option<String>,
+) -> Result<JsValue, JsValue> {
+    let mode: SortMode = mode.parse().map_err(|e: String| JsValue::from_str(&e))?;
+    let options = SortOptions {
+        mode,
+        descending,
+        key_column,
+        delimiter: delimiter.as_deref(),
+        key_pattern: key_pattern.as_deref(),
+    };
+    apply_line_op(file_id, start_line, end_line, LineOp::Sort(options))
+}
+
+/// Remove duplicate lines, keeping first occurrences
+/// With `with_counts` each kept line is prefixed by its count (`uniq -c`)
+#[wasm_bindgen]
+pub fn unique_lines(
+    file_id: u32,
+    start_line: Option<usize>,
+    end_line: Option<usize>,
+    case_insensitive: bool,
+    with_counts: bool,
+) -> Result<JsValue, JsValue> {
+    let op = LineOp::Unique {
+        case_insensitive,
+        with_counts,
+    };
+    apply_line_op(file_id, start_line, end_line, op)
+}
+
+/// Reverse the order of lines
+#[wasm_bindgen]
+pub fn reverse_lines(
+    file_id: u32,
+    start_line: Option<usize>,
+    end_line: Option<usize>,
+) -> Result<JsValue, JsValue> {
+    apply_line_op(file_id, start_line, end_line, LineOp::Reverse)
+}
+
+/// Shuffle lines; the same seed gives the same order
+#[wasm_bindgen]
+pub fn shuffle_lines(
+    file_id: u32,
+    start_line: Option<usize>,
+    end_line: Option<usize>,
+    seed: u32,
+) -> Result<JsValue, JsValue> {
+    apply_line_op(file_id, start_line, end_line, LineOp::Shuffle { seed })
+}
+
+/// Join lines into one, separated by `separator`
+#[wasm_bindgen]
+pub fn join_lines(
+    file_id: u32,
+    start_line: Option<usize>,
+    end_line: Option<usize>,
+    separator: &str,
+) -> Result<JsValue, JsValue> {
+    apply_line_op(file_id, start_line, end_line, LineOp::Join { separator })
+}
+
+/// Split lines at every occurrence of `delimiter`
+#[wasm_bindgen]
+pub fn split_lines(
+    file_id: u32,
+    start_line: Option<usize>,
+    end_line: Option<usize>,
+    delimiter: &str,
+) -> Result<JsValue, JsValue> {
+    apply_line_op(file_id, start_line, end_line, LineOp::Split { delimiter })
+}
+
+/// Remove empty and whitespace-only lines
+#[wasm_bindgen]
+pub fn remove_blank_lines(
+    file_id: u32,
+    start_line: Option<usize>,
+    end_line: Option<usize>,
+) -> Result<JsValue, JsValue> {
+    apply_line_op(file_id, start_line, end_line, LineOp::RemoveBlank)
+}
+
+/// Detect the indentation style and width of a buffer
+#[wasm_bindgen]
+pub fn detect_indentation(file_id: u32) -> Result<JsValue, JsValue> {
+    with_buffer(file_id, |buffer| {
+        to_js(&whitespace::detect_indentation(buffer))
+    })
+}
+
+/// Normalize whitespace: re-indent to `indent` ("tabs", "spaces" or
+/// "spaces:N"; unchanged if omitted), trim trailing whitespace and add a
+/// final newline. Applied as one undoable edit unless `dry_run`.
+/// Returns a report with the detected indentation and per-line changes.
+#[allow(clippy::too_many_arguments)]
+#[wasm_bindgen]
+pub fn normalize_whitespace(
+    file_id: u32,
+    indent: Option<String>,
+    tab_width: Option<usize>,
+    trim_trailing: bool,
+    final_newline: bool,
+    max_changes: Option<usize>,
+    dry_run: bool,
+) -> Result<JsValue, JsValue> {
+    let indent = indent
+        .as_deref()
+        .map(str::parse::<IndentTarget>)
+        .transpose()
+        .map_err(|e: String| JsValue::from_str(&e))?;
+    let options = NormalizeOptions {
+        indent,
+        tab_width: tab_width.unwrap_or(4),
+        trim_trailing,
+        final_newline,
+    };
+
+    with_buffer(file_id, |buffer| {
+        let max_changes = max_changes.unwrap_or(whitespace::DEFAULT_MAX_CHANGES);
+        let report = whitespace::normalize(buffer, &options, max_changes, dry_run)
+            .map_err(ApiError::invalid_argument)?;
+        to_js(&report)
+    })
+}
+
+/// Scan for invisible, control, bidi and confusable characters and check
+/// normalization forms. Returns positions of up to `max_results` issues.
+#[wasm_bindgen]
+pub fn scan_unicode(file_id: u32, max_results: usize) -> Result<JsValue, JsValue> {
+    with_buffer(file_id, |buffer| to_js(&unicode::scan(buffer, max_results)))
+}
+
+/// Remove characters of the given kinds (invisible, whitespace, control,
+/// bidi, confusable) as one undoable edit. With `replace`, look-alike spaces
+/// become spaces and confusables their Latin letter instead of being removed.
+/// Returns the number of characters changed.
+#[wasm_bindgen]
+pub fn clean_unicode(file_id: u32, kinds: Vec<String>, replace: bool) -> Result<usize, JsValue> {
+    let kinds = kinds
+        .iter()
+        .map(|k| k.parse::<IssueKind>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| JsValue::from_str(&e))?;
+
+    with_buffer(file_id, |buffer| {
+        unicode::clean(buffer, &kinds, replace).map_err(ApiError::invalid_argument)
+    })
+}
+
+/// Normalize the buffer to NFC, NFD, NFKC or NFKD as one undoable edit
+/// Returns whether the content changed
+#[wasm_bindgen]
+pub fn normalize_unicode(file_id: u32, form: &str) -> Result<bool, JsValue> {
+    let form: NormalForm = form.parse().map_err(|e: String| JsValue::from_str(&e))?;
+
+    with_buffer(file_id, |buffer| {
+        unicode::normalize(buffer, form).map_err(ApiError::invalid_argument)
+    })
+}
+
+/// Hash the buffer, or the bytes in `start_byte..end_byte`, with each of
+/// md5, sha1, sha256, sha512, crc32, xxh64 or xxh3 in one pass
+#[wasm_bindgen]
+pub fn hash_buffer(
+    file_id: u32,
+    algorithms: Vec<String>,
+    start_byte: Option<usize>,
+    end_byte: Option<usize>,
+) -> Result<JsValue, JsValue> {
+    with_buffer(file_id, |buffer| {
+        to_js(&document::hash(buffer, &algorithms, start_byte, end_byte)?)
+    })
+}
+
+/// Check the buffer against the contents of a `.sha256`-style sidecar
+/// `file_name` picks the entry in multi-file sidecars; `algorithm`
+/// disambiguates 16-digit digests (xxh64 is assumed otherwise)
+#[wasm_bindgen]
+pub fn verify_buffer_hash(
+    file_id: u32,
+    sidecar: &str,
+    file_name: Option<String>,
+    algorithm: Option<String>,
+) -> Result<JsValue, JsValue> {
+    let hint = algorithm
+        .map(|a| a.parse::<HashAlgorithm>())
+        .transpose()
+        .map_err(|e| JsValue::from_str(&e))?;
+    let expected = hashing::parse_sidecar(sidecar, file_name.as_deref(), hint)
+        .map_err(|e| JsValue::from_str(&e))?;
+
+    with_buffer(file_id, |buffer| {
+        let mut hasher = hashing::Hasher::new(expected.algorithm);
+        hasher.update(&buffer.content);
+        to_js(&hashing::verify(&expected, hasher.finish()))
+    })
+}
+
+/// Find API keys, private keys, JWTs, passwords, emails and card numbers in
+/// the buffer for highlighting. `kinds` (e.g. "EMAIL") limits the scan;
+/// empty scans for everything.
+#[wasm_bindgen]
+pub fn scan_secrets(file_id: u32, kinds: Vec<String>) -> Result<JsValue, JsValue> {
+    with_buffer(file_id, |buffer| {
+        let kinds: Vec<SecretKind> = document::parse_names(&kinds)?;
+        to_js(&secrets::scan(&buffer.content, &kinds))
+    })
+}
+
+/// Encode or decode the bytes in `start_byte..end_byte` in place, as one
+/// undoable edit. `encoding` is base64, base64url, hex, url, html, json,
+/// quoted-printable or gzip-base64. Returns the new byte range.
+#[wasm_bindgen]
+pub fn transform_range(
+    file_id: u32,
+    start_byte: usize,
+    end_byte: usize,
+    encoding: &str,
+    decode: bool,
+) -> Result<JsValue, JsValue> {
+    let codec: Codec = encoding
+        .parse()
+        .map_err(|e: String| JsValue::from_str(&e))?;
+
+    with_buffer(file_id, |buffer| {
+        buffer.check_range(start_byte, end_byte)?;
+
+        let selection = &buffer.content[start_byte..end_byte];
+        let output = if decode {
+            encoding::decode(codec, selection)
+        } else {
+            encoding::encode(codec, selection)
+        }
+        .map_err(ApiError::parse)?;
+
+        buffer
+            .replace_range(start_byte, end_byte, &output)
+            .map_err(ApiError::invalid_range)?;
+
+        to_js(&TransformResult {
+            start_byte,
+            end_byte: start_byte + output.len(),
+            line_count: buffer.line_count(),
+            size: buffer.content.len(),
+        })
+    })
+}
+
+/// Get folding ranges starting within a line range (1-based, inclusive)
+/// Ranges come from brackets or indentation depending on `language`, with
+/// brackets inside strings and comments ignored
+#[wasm_bindgen]
+pub fn get_folding_ranges(
+    file_id: u32,
+    language: &str,
+    start_line: usize,
+    end_line: usize,
+) -> Result<JsValue, JsValue> {
+    with_buffer(file_id, |buffer| {
+        to_js(
+            buffer
+                .fold_index(language)
+                .folds_in_range(start_line, end_line),
+        )
+    })
+}
+
+/// Find the bracket matching the one at (or just before) a byte offset
+/// Returns null when there is no bracket pair there
+#[wasm_bindgen]
+pub fn match_bracket(file_id: u32, language: &str, byte_offset: usize) -> Result<JsValue, JsValue> {
+    with_buffer(file_id, |buffer| {
+        buffer.fold_index(language);
+        let index = buffer.fold_index.as_ref().unwrap();
+        to_js(&index.match_bracket(&buffer.line_offsets, byte_offset))
+    })
+}
+
+/// Get the symbol outline of a file (JSON/YAML keys, Markdown headings,
+/// Go declarations) as a tree
+#[wasm_bindgen]
+pub fn get_outline(file_id: u32, language: &str) -> Result<JsValue, JsValue> {
+    with_buffer(file_id, |buffer| {
+        let outline = buffer.outline(language).map_err(ApiError::parse)?;
+        to_js(&outline.symbols)
+    })
+}
+
+/// Fuzzy-find outline symbols by name (fzf-style scoring), best first
+#[wasm_bindgen]
+pub fn fuzzy_find_symbols(
+    file_id: u32,
+    language: &str,
+    query: &str,
+    max_results: usize,
+) -> Result<JsValue, JsValue> {
+    with_buffer(file_id, |buffer| {
+        let outline = buffer.outline(language).map_err(ApiError::parse)?;
+        to_js(&fuzzy::find_symbols(&outline.symbols, query, max_results))
+    })
+}
+
+/// Get the chain of symbols enclosing a line (1-based), outermost first
+#[wasm_bindgen]
+pub fn get_breadcrumbs(file_id: u32, language: &str, line: usize) -> Result<JsValue, JsValue> {
+    with_buffer(file_id, |buffer| {
+        let outline = buffer.outline(language).map_err(ApiError::parse)?;
+        to_js(&outline.breadcrumbs(line))
+    })
+}
+
+/// Languages get_outline can parse (empty if built without the "outline" feature)
+#[wasm_bindgen]
+pub fn get_outline_languages() -> Result<JsValue, JsValue> {
+    serde_wasm_bindgen::to_value(outline::supported_languages())
+        .map_err(|e| JsValue::from_str(&format!("Serialization error: {}", e)))
+}
+
+/// Describe how a line splits into segments of `segment_width` characters
+/// Used to page through very long lines (minified code, one-line JSON)
+#[wasm_bindgen]
+pub fn get_line_segment_info(
+    file_id: u32,
+    line: usize,
+    segment_width: usize,
+) -> Result<JsValue, JsValue> {
+    with_buffer(file_id, |buffer| {
+        let info = buffer
+            .segment_index(line, segment_width)
+            .map_err(ApiError::invalid_range)?
+            .info();
+        to_js(&info)
+    })
+}
+
+/// Get a window of columns `start_column..end_column` (0-based) of a line
+#[wasm_bindgen]
+pub fn get_line_window(
+    file_id: u32,
+    line: usize,
+    start_column: usize,
+    end_column: usize,
+    segment_width: usize,
+) -> Result<String, JsValue> {
+    with_buffer(file_id, |buffer| {
+        buffer
+            .segment_index(line, segment_width)
+            .map_err(ApiError::invalid_range)?;
+        let index = buffer.segments.as_ref().unwrap();
+        let (start, end) = index
+            .window(&buffer.content, start_column, end_column)
+            .map_err(ApiError::invalid_range)?;
+
+        String::from_utf8(buffer.content[start..end].to_vec()).map_err(|e| {
+            ApiError::new(
+                ErrorCode::Utf8,
+                format!("UTF-8 error at byte range {}-{}: {}", start, end, e),
+            )
+        })
+    })
+}
+
+/// Search within a single line, returning matches in segment coordinates
+#[wasm_bindgen]
+pub fn search_in_line(
+    file_id: u32,
+    line: usize,
+    pattern: &str,
+    max_results: usize,
+    segment_width: usize,
+) -> Result<JsValue, JsValue> {
+    with_buffer(file_id, |buffer| {
+        buffer
+            .segment_index(line, segment_width)
+            .map_err(ApiError::invalid_range)?;
+        let index = buffer.segments.as_ref().unwrap();
+        let matches = index
+            .search(&buffer.content, pattern, max_results)
+            .map_err(|e| ApiError::new(ErrorCode::Regex, e))?;
+        to_js(&matches)
+    })
+}
+
+/// Map a segment and column within it back to a byte offset in the file
+#[wasm_bindgen]
+pub fn segment_to_byte_offset(
+    file_id: u32,
+    line: usize,
+    segment: usize,
+    column: usize,
+    segment_width: usize,
+) -> Result<usize, JsValue> {
+    with_buffer(file_id, |buffer| {
+        buffer
+            .segment_index(line, segment_width)
+            .map_err(ApiError::invalid_range)?;
+        let index = buffer.segments.as_ref().unwrap();
+        index
+            .segment_to_byte(&buffer.content, segment, column)
+            .map_err(ApiError::invalid_range)
+    })
+}
+
+/// Map a byte offset in the file to its line, segment and column
+#[wasm_bindgen]
+pub fn byte_offset_to_segment(
+    file_id: u32,
+    byte_offset: usize,
+    segment_width: usize,
+) -> Result<JsValue, JsValue> {
+    with_buffer(file_id, |buffer| {
+        let line = buffer
+            .line_at_offset(byte_offset)
+            .map_err(ApiError::invalid_range)?;
+        buffer
+            .segment_index(line, segment_width)
+            .map_err(ApiError::invalid_range)?;
+        let index = buffer.segments.as_ref().unwrap();
+        // Offsets on a line break resolve to the end of the line
+        let position = index
+            .position(&buffer.content, byte_offset.min(index.end))
+            .map_err(ApiError::invalid_range)?;
+        to_js(&position)
+    })
+}
+
+/// Resolve a position through the buffer's char index (built on first use)
+fn resolve_position(
+    file_id: u32,
+    resolve: impl FnOnce(&CharIndex, &FileBuffer) -> Result<Position, String>,
+) -> Result<JsValue, JsValue> {
+    with_buffer(file_id, |buffer| {
+        buffer.char_index();
+        let index = buffer.char_index.as_ref().unwrap();
+        to_js(&resolve(index, buffer).map_err(ApiError::invalid_range)?)
+    })
+}
+
+/// Create an anchor at a byte offset that follows later edits
+/// `gravity` ("left" or "right") decides which side of text inserted
+/// exactly at the anchor it ends up on. Returns the anchor ID.
+#[wasm_bindgen]
+pub fn create_anchor(file_id: u32, byte_offset: usize, gravity: &str) -> Result<u32, JsValue> {
+    with_buffer(file_id, |buffer| {
+        document::create_anchor(buffer, byte_offset, gravity)
+    })
+}
+
+/// Current position of an anchor
+#[wasm_bindgen]
+pub fn get_anchor_position(file_id: u32, anchor_id: u32) -> Result<JsValue, JsValue> {
+    with_buffer(file_id, |buffer| {
+        to_js(&document::anchor_position(buffer, anchor_id)?)
+    })
+}
+
+/// Delete an anchor; returns whether it existed
+#[wasm_bindgen]
+pub fn delete_anchor(file_id: u32, anchor_id: u32) -> Result<bool, JsValue> {
+    with_buffer(file_id, |buffer| Ok(buffer.anchors.remove(anchor_id)))
+}
+
+/// All anchors as `{ id, offset, gravity }`, in creation order
+/// The result can be saved with the session and passed to restore_anchors
+#[wasm_bindgen]
+pub fn list_anchors(file_id: u32) -> Result<JsValue, JsValue> {
+    with_buffer(file_id, |buffer| to_js(&buffer.anchors.list()))
+}
+
+/// Replace the anchors of a buffer with a saved list, keeping their IDs
+#[wasm_bindgen]
+pub fn restore_anchors(file_id: u32, anchors: JsValue) -> Result<(), JsValue> {
+    let anchors: Vec<Anchor> = serde_wasm_bindgen::from_value(anchors)
+        .map_err(|e| JsValue::from_str(&format!("Invalid anchors: {}", e)))?;
+
+    with_buffer(file_id, |buffer| {
+        if let Some(anchor) = anchors.iter().find(|a| a.offset > buffer.content.len()) {
+            return Err(ApiError::invalid_range(format!(
+                "Anchor {} is past the end of the file ({} bytes)",
+                anchor.id,
+                buffer.content.len()
+            )));
+        }
+
+        buffer.anchors.restore(anchors);
+        Ok(())
+    })
+}
+
+/// IDs of anchors deleted by edits since the last call
+#[wasm_bindgen]
+pub fn take_deleted_anchors(file_id: u32) -> Result<Vec<u32>, JsValue> {
+    with_buffer(file_id, |buffer| Ok(buffer.anchors.take_deleted()))
+}
+
+/// Position (line, column, byte, char and UTF-16 offsets) at a byte offset
+#[wasm_bindgen]
+pub fn go_to_byte_offset(file_id: u32, byte_offset: usize) -> Result<JsValue, JsValue> {
+    resolve_position(file_id, |index, buffer| index.at_byte(buffer, byte_offset))
+}
+
+/// Position at the start of the line `percent`% of the way through the file
+#[wasm_bindgen]
+pub fn go_to_percent(file_id: u32, percent: f64) -> Result<JsValue, JsValue> {
+    resolve_position(file_id, |index, buffer| index.at_percent(buffer, percent))
+}
+
+/// Position at a 1-based line and 0-based character column
+#[wasm_bindgen]
+pub fn line_column_to_position(
+    file_id: u32,
+    line: usize,
+    column: usize,
+) -> Result<JsValue, JsValue> {
+    resolve_position(file_id, |index, buffer| {
+        index.at_line_column(buffer, line, column)
+    })
+}
+
+/// Position at an absolute character offset
+#[wasm_bindgen]
+pub fn char_offset_to_position(file_id: u32, char_offset: usize) -> Result<JsValue, JsValue> {
+    resolve_position(file_id, |index, buffer| index.at_char(buffer, char_offset))
+}
+
+/// Position at an absolute UTF-16 offset, as used by CodeMirror
+#[wasm_bindgen]
+pub fn utf16_offset_to_position(file_id: u32, utf16_offset: usize) -> Result<JsValue, JsValue> {
+    resolve_position(file_id, |index, buffer| {
+        index.at_utf16(buffer, utf16_offset)
+    })
+}
+
+/// Detailed text statistics: character/word counts, longest line, a
+/// line-length histogram, indentation style and the detected format
+#[wasm_bindgen]
+pub fn analyze(file_id: u32) -> Result<JsValue, JsValue> {
+    with_buffer(file_id, |buffer| to_js(&analysis::analyze(buffer)))
+}
+
+/// Validate JSON content of a file
+#[wasm_bindgen]
+pub fn validate_json(file_id: u32) -> Result<bool, JsValue> {
+    with_buffer(file_id, |buffer| buffer.validate_json().map(|_| true))
+}
+
+/// Format JSON content with specified indentation
+#[wasm_bindgen]
+pub fn format_json(file_id: u32, indent: usize) -> Result<String, JsValue> {
+    with_buffer(file_id, |buffer| buffer.format_json(indent))
+}
+
+/// Pack a buffer into a versioned binary snapshot for IndexedDB or a cache
+/// directory: content, line index and line ending, undo history and
+/// anchors
+#[wasm_bindgen]
+pub fn serialize_buffer(file_id: u32) -> Result<Vec<u8>, JsValue> {
+    with_buffer(file_id, |buffer| {
+        snapshot::serialize(buffer).map_err(ApiError::invalid_argument)
+    })
+}
+
+/// Restore a snapshot from serialize_buffer as a new buffer, without
+/// re-indexing. Returns the new file ID with the saved metadata.
+#[wasm_bindgen]
+pub fn restore_buffer(bytes: &[u8]) -> Result<JsValue, JsValue> {
+    ensure_initialized();
+
+    let snapshot = snapshot::deserialize(bytes).map_err(|e| JsValue::from_str(&e))?;
+    let size = snapshot.buffer.content.len();
+    let line_count = snapshot.buffer.line_count();
+    let restored = RestoredBuffer {
+        file_id: store_buffer(snapshot.buffer),
+        size,
+        line_count,
+        line_ending: snapshot.line_ending,
+    };
+
+    serde_wasm_bindgen::to_value(&restored)
+        .map_err(|e| JsValue::from_str(&format!("Serialization error: {}", e)))
+}
+
+/// Free a file buffer from memory
+/// Call this when closing a tab to prevent memory leaks
+#[wasm_bindgen]
+pub fn free_file_buffer(file_id: u32) -> Result<(), JsValue> {
+    ensure_initialized();
+    remove_buffer(file_id);
+    Ok(())
+}
+
+/// Remove a buffer with its merge session and grep view, if any
+/// Shared by free_file_buffer and dropping a Document; poisoned locks are
+/// skipped as this runs from Drop
+pub(crate) fn remove_buffer(file_id: u32) {
+    if let Ok(mut buffers) = FILE_BUFFERS.lock() {
+        if let Some(map) = buffers.as_mut() {
+            map.remove(&file_id);
+        }
+    }
+    if let Ok(mut merges) = MERGE_SESSIONS.lock() {
+        if let Some(merges) = merges.as_mut() {
+            merges.remove(&file_id);
+        }
+    }
+    if let Ok(mut views) = GREP_VIEWS.lock() {
+        if let Some(views) = views.as_mut() {
+            views.remove(&file_id);
+        }
+    }
+}
+
+/// Get memory usage statistics
+/// Reports resident and compressed bytes per buffer next to the totals
+#[wasm_bindgen]
+pub fn get_memory_stats() -> Result<JsValue, JsValue> {
     ensure_initialized();
 
     let buffers = FILE_BUFFERS.lock().unwrap();
     if let Some(map) = buffers.as_ref() {
-        let buffer = map
-            .get(&file_id)
-            .ok_or_else(|| JsValue::from_str(&format!("File {} not found", file_id)))?;
+        serde_wasm_bindgen::to_value(&map.stats())
+            .map_err(|e| JsValue::from_str(&format!("Serialization error: {}", e)))
+    } else {
+        Err(JsValue::from_str("Storage not initialized"))
+    }
+}
 
-        buffer
-            .get_line_range(start_line as usize, end_line as usize)
-            .map_err(|e| JsValue::from_str(&e))
+/// Limit the memory held by resident buffers; `None` removes the limit
+/// Least recently used buffers are compressed once the limit is exceeded
+/// and decompressed transparently when next accessed. Nothing is spilled
+/// out of the WASM heap, so the limit is exceeded once all are compressed.
+#[wasm_bindgen]
+pub fn set_memory_budget(limit_bytes: Option<usize>) -> Result<(), JsValue> {
+    ensure_initialized();
+
+    let mut buffers = FILE_BUFFERS.lock().unwrap();
+    if let Some(map) = buffers.as_mut() {
+        map.set_budget(limit_bytes);
+        Ok(())
     } else {
         Err(JsValue::from_str("Storage not initialized"))
     }
 }
 
-/// Search file for pattern (supports regex)
-/// Returns up to max_results matches
+/// Compress every buffer except the `keep_recent` most recently used,
+/// e.g. when the app goes idle. Returns how many were compressed.
 #[wasm_bindgen]
-pub fn search_file(file_id: u32, pattern: &str, max_results: usize) -> Result<JsValue, JsValue> {
+pub fn compress_inactive_buffers(keep_recent: usize) -> Result<usize, JsValue> {
+    ensure_initialized();
+
+    let mut buffers = FILE_BUFFERS.lock().unwrap();
+    if let Some(map) = buffers.as_mut() {
+        Ok(map.compress_inactive(keep_recent))
+    } else {
+        Err(JsValue::from_str("Storage not initialized"))
+    }
+}
+
+/// Buffers still held in WASM whose IDs are not in `open_ids` (the IDs the
+/// UI knows about), least recently used first. These were never freed.
+#[wasm_bindgen]
+pub fn list_leaked_buffers(open_ids: Vec<u32>) -> Result<JsValue, JsValue> {
     ensure_initialized();
 
     let buffers = FILE_BUFFERS.lock().unwrap();
     if let Some(map) = buffers.as_ref() {
-        let buffer = map
-            .get(&file_id)
-            .ok_or_else(|| JsValue::from_str(&format!("File {} not found", file_id)))?;
+        serde_wasm_bindgen::to_value(&map.leaked(&open_ids))
+            .map_err(|e| JsValue::from_str(&format!("Serialization error: {}", e)))
+    } else {
+        Err(JsValue::from_str("Storage not initialized"))
+    }
+}
+
+/// Get full file content as string
+/// This is used when saving the file - content is stored in WASM, not React state
+#[wasm_bindgen]
+pub fn get_content(file_id: u32) -> Result<String, JsValue> {
+    with_buffer(file_id, |buffer| buffer.content_string())
+}
+
+/// Three-way merge of two buffers derived from a common base
+/// Creates a new buffer holding the merged text, with git-style conflict
+/// markers around every region both sides changed differently
+#[wasm_bindgen]
+pub fn merge3(base_id: u32, ours_id: u32, theirs_id: u32) -> Result<JsValue, JsValue> {
+    ensure_initialized();
+
+    let merge = {
+        let mut buffers = FILE_BUFFERS.lock().unwrap();
+        let map = buffers
+            .as_mut()
+            .ok_or_else(|| JsValue::from_str("Storage not initialized"))?;
+
+        let mut text = |file_id: u32| {
+            map.get(&file_id)
+                .map(|buffer| String::from_utf8_lossy(&buffer.content).into_owned())
+                .map_err(ApiError::legacy)
+        };
+
+        Merge3::new(&text(base_id)?, &text(ours_id)?, &text(theirs_id)?)
+    };
+
+    let output = merge.render();
+    let buffer = FileBuffer::new(output.content.into_bytes())
+        .map_err(|e| JsValue::from_str(&format!("Failed to create buffer: {}", e)))?;
+    let file_id = store_buffer(buffer);
+
+    let result = MergeResult {
+        file_id,
+        conflict_count: merge.conflict_count(),
+        conflicts: output.conflicts,
+    };
 
-        let results = buffer
-            .search(pattern, max_results)
+    if let Some(merges) = MERGE_SESSIONS.lock().unwrap().as_mut() {
+        merges.insert(file_id, merge);
+    }
+
+    serde_wasm_bindgen::to_value(&result)
+        .map_err(|e| JsValue::from_str(&format!("Serialization error: {}", e)))
+}
+
+/// Resolve one conflict of a merged buffer as "ours", "theirs" or "both"
+/// Only the conflict's marker block is rewritten, as one undoable edit, so
+/// edits made to the merged buffer since are kept. Returns the conflicts
+/// still unresolved.
+#[wasm_bindgen]
+pub fn resolve_conflict(
+    file_id: u32,
+    conflict_index: usize,
+    resolution: &str,
+) -> Result<JsValue, JsValue> {
+    ensure_initialized();
+
+    let resolution: Resolution = resolution
+        .parse()
+        .map_err(|e: String| JsValue::from_str(&e))?;
+
+    let conflicts = with_buffer(file_id, |buffer| {
+        let merges = MERGE_SESSIONS.lock().unwrap();
+        let merge = merge_session(&merges, file_id)?;
+        merge
+            .resolve_in_buffer(buffer, conflict_index, resolution)
+            .map_err(ApiError::invalid_argument)
+    })?;
+
+    serde_wasm_bindgen::to_value(&conflicts)
+        .map_err(|e| JsValue::from_str(&format!("Serialization error: {}", e)))
+}
+
+/// Get the unresolved conflicts of a merged buffer
+/// Conflicts are read from the markers in the buffer, with their line span
+/// in it, so edits and undos since the merge are accounted for
+#[wasm_bindgen]
+pub fn get_merge_conflicts(file_id: u32) -> Result<JsValue, JsValue> {
+    let conflicts = with_buffer(file_id, |buffer| {
+        let merges = MERGE_SESSIONS.lock().unwrap();
+        Ok(merge_session(&merges, file_id)?.conflicts_in(&buffer.content))
+    })?;
+
+    serde_wasm_bindgen::to_value(&conflicts)
+        .map_err(|e| JsValue::from_str(&format!("Serialization error: {}", e)))
+}
+
+/// Merge session of a merged buffer
+fn merge_session(merges: &Option<HashMap<u32, Merge3>>, file_id: u32) -> Result<&Merge3, ApiError> {
+    merges
+        .as_ref()
+        .and_then(|map| map.get(&file_id))
+        .ok_or_else(|| {
+            ApiError::invalid_argument(format!("File {} is not a merge result", file_id))
+        })
+}
+
+/// Parse a unified diff held in a buffer
+/// Returns the file patches with their hunks
+#[wasm_bindgen]
+pub fn parse_patch(patch_id: u32) -> Result<JsValue, JsValue> {
+    with_buffer(patch_id, |buffer| {
+        let patches = patch::parse_patch(&String::from_utf8_lossy(&buffer.content))
+            .map_err(ApiError::parse)?;
+        to_js(&patches)
+    })
+}
+
+/// Apply a unified diff held in a buffer to other buffers
+/// The n-th file of the patch is applied to the n-th entry of `file_ids`.
+/// Hunks may match up to `max_fuzz` context lines loosely; hunks that do not
+/// match are reported as failed and the rest are still applied. Each buffer
+/// is changed by one undoable edit. If any buffer is missing, given twice or
+/// not valid UTF-8, the call fails without changing any of them.
+#[wasm_bindgen]
+pub fn apply_patch(patch_id: u32, file_ids: Vec<u32>, max_fuzz: usize) -> Result<JsValue, JsValue> {
+    ensure_initialized();
+
+    let mut buffers = FILE_BUFFERS.lock().unwrap();
+    if let Some(map) = buffers.as_mut() {
+        let patch_buffer = map.get(&patch_id).map_err(ApiError::legacy)?;
+
+        let patches = patch::parse_patch(&String::from_utf8_lossy(&patch_buffer.content))
             .map_err(|e| JsValue::from_str(&e))?;
 
+        if patches.len() != file_ids.len() {
+            return Err(JsValue::from_str(&format!(
+                "Patch touches {} files but {} buffers were given",
+                patches.len(),
+                file_ids.len()
+            )));
+        }
+
+        // Patch every buffer before changing any, so a missing or invalid
+        // buffer leaves them all untouched
+        let mut patched_files = Vec::with_capacity(patches.len());
+        for (i, (file_patch, &file_id)) in patches.iter().zip(&file_ids).enumerate() {
+            if file_ids[..i].contains(&file_id) {
+                return Err(JsValue::from_str(&format!(
+                    "File {} is given for more than one patched file",
+                    file_id
+                )));
+            }
+            let buffer = map.get(&file_id).map_err(ApiError::legacy)?;
+            // Decoding lossily would rewrite every invalid byte as U+FFFD
+            let text = std::str::from_utf8(&buffer.content)
+                .map_err(|_| JsValue::from_str(&format!("File {} is not valid UTF-8", file_id)))?;
+
+            let (patched, hunks) = patch::apply_patch(text, file_patch, max_fuzz);
+            patched_files.push((file_id, patched, hunks));
+        }
+
+        let mut results = Vec::with_capacity(patches.len());
+        for (file_patch, (file_id, patched, hunks)) in patches.iter().zip(patched_files) {
+            let applied_hunks = hunks.iter().filter(|h| h.applied).count();
+
+            if applied_hunks > 0 {
+                let buffer = map.get_mut(&file_id).map_err(ApiError::legacy)?;
+                buffer
+                    .replace_content(patched.as_bytes())
+                    .map_err(|e| JsValue::from_str(&e))?;
+            }
+
+            results.push(FilePatchResult {
+                file_id,
+                old_path: file_patch.old_path.clone(),
+                new_path: file_patch.new_path.clone(),
+                applied_hunks,
+                failed_hunks: hunks.len() - applied_hunks,
+                hunks,
+            });
+        }
+
         serde_wasm_bindgen::to_value(&results)
             .map_err(|e| JsValue::from_str(&format!("Serialization error: {}", e)))
     } else {
@@ -127,105 +1182,242 @@ pub fn search_file(file_id: u32, pattern: &str, max_results: usize) -> Result<Js
     }
 }
 
-/// Validate JSON content of a file
+/// Generate a unified diff between two buffers
 #[wasm_bindgen]
-pub fn validate_json(file_id: u32) -> Result<bool, JsValue> {
+pub fn diff_buffers(
+    old_id: u32,
+    new_id: u32,
+    old_label: &str,
+    new_label: &str,
+    context_lines: usize,
+) -> Result<String, JsValue> {
     ensure_initialized();
 
-    let buffers = FILE_BUFFERS.lock().unwrap();
-    if let Some(map) = buffers.as_ref() {
-        let buffer = map
-            .get(&file_id)
-            .ok_or_else(|| JsValue::from_str(&format!("File {} not found", file_id)))?;
+    let mut buffers = FILE_BUFFERS.lock().unwrap();
+    if let Some(map) = buffers.as_mut() {
+        let mut text = |file_id: u32| {
+            map.get(&file_id)
+                .map(|buffer| String::from_utf8_lossy(&buffer.content).into_owned())
+                .map_err(ApiError::legacy)
+        };
 
-        match buffer.validate_json() {
-            Ok(_) => Ok(true),
-            Err(e) => Err(JsValue::from_str(&e)),
-        }
+        Ok(patch::unified_diff(
+            &text(old_id)?,
+            &text(new_id)?,
+            old_label,
+            new_label,
+            context_lines,
+        ))
     } else {
         Err(JsValue::from_str("Storage not initialized"))
     }
 }
 
-/// Format JSON content with specified indentation
+/// Index a log file: detect its format and extract level and timestamp per line
+/// `default_year` fills in the year for formats that omit it (classic syslog).
+/// Returns a summary with per-level counts and the covered time span.
+/// The index is dropped when the buffer is edited or compressed by the
+/// memory budget; call this again after either.
 #[wasm_bindgen]
-pub fn format_json(file_id: u32, indent: usize) -> Result<String, JsValue> {
+pub fn index_log(file_id: u32, default_year: i32) -> Result<JsValue, JsValue> {
+    with_buffer(file_id, |buffer| {
+        to_js(&buffer.index_log(default_year).summary())
+    })
+}
+
+/// Filter an indexed log by level and time range
+/// `levels` lists level names to keep (all levels if empty); `start_ms` and
+/// `end_ms` bound timestamps in ms since the epoch. Returns 1-based line numbers.
+#[wasm_bindgen]
+pub fn filter_log(
+    file_id: u32,
+    levels: Vec<String>,
+    start_ms: Option<f64>,
+    end_ms: Option<f64>,
+    max_results: usize,
+) -> Result<JsValue, JsValue> {
+    let levels = levels
+        .iter()
+        .map(|name| {
+            LogLevel::parse(name)
+                .ok_or_else(|| JsValue::from_str(&format!("Unknown log level '{}'", name)))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    with_buffer(file_id, |buffer| {
+        let lines = indexed_log(buffer, file_id)?.filter(
+            &levels,
+            start_ms.map(|t| t as i64),
+            end_ms.map(|t| t as i64),
+            max_results,
+        );
+        to_js(&lines)
+    })
+}
+
+/// Histogram of log entries over time, split into `bucket_count` buckets
+#[wasm_bindgen]
+pub fn log_histogram(file_id: u32, bucket_count: usize) -> Result<JsValue, JsValue> {
+    with_buffer(file_id, |buffer| {
+        to_js(&indexed_log(buffer, file_id)?.histogram(bucket_count))
+    })
+}
+
+/// Log index of a buffer, built by index_log
+fn indexed_log(buffer: &FileBuffer, file_id: u32) -> Result<&LogIndex, ApiError> {
+    buffer.log_index.as_ref().ok_or_else(|| {
+        ApiError::invalid_argument(format!(
+            "File {} has no log index; call index_log first",
+            file_id
+        ))
+    })
+}
+
+/// Create a filtered view of a buffer holding only the lines that match any
+/// `include` pattern (all lines if empty) and no `exclude` pattern
+/// The view is a regular buffer, paged with get_line_range, that remembers
+/// the source line of each of its lines
+#[wasm_bindgen]
+pub fn create_grep_view(
+    file_id: u32,
+    include: Vec<String>,
+    exclude: Vec<String>,
+    case_insensitive: bool,
+) -> Result<JsValue, JsValue> {
     ensure_initialized();
 
-    let buffers = FILE_BUFFERS.lock().unwrap();
-    if let Some(map) = buffers.as_ref() {
-        let buffer = map
-            .get(&file_id)
-            .ok_or_else(|| JsValue::from_str(&format!("File {} not found", file_id)))?;
+    let (content, line_map, source_line_count) = with_buffer(file_id, |source| {
+        let (content, line_map) =
+            grep_view::filter_lines(source, &include, &exclude, case_insensitive)
+                .map_err(|e| ApiError::new(ErrorCode::Regex, e))?;
+        Ok((content, line_map, source.line_count()))
+    })?;
 
-        buffer
-            .format_json(indent)
-            .map_err(|e| JsValue::from_str(&e))
-    } else {
-        Err(JsValue::from_str("Storage not initialized"))
+    let buffer = FileBuffer::new(content)
+        .map_err(|e| JsValue::from_str(&format!("Failed to create buffer: {}", e)))?;
+    let view_id = store_buffer(buffer);
+
+    let info = GrepViewInfo {
+        file_id: view_id,
+        source_id: file_id,
+        line_count: line_map.len(),
+        source_line_count,
+    };
+
+    if let Some(views) = GREP_VIEWS.lock().unwrap().as_mut() {
+        views.insert(
+            view_id,
+            GrepView {
+                source_id: file_id,
+                include,
+                exclude,
+                case_insensitive,
+                line_map,
+            },
+        );
     }
+
+    serde_wasm_bindgen::to_value(&info)
+        .map_err(|e| JsValue::from_str(&format!("Serialization error: {}", e)))
 }
 
-/// Free a file buffer from memory
-/// Call this when closing a tab to prevent memory leaks
+/// Re-run a grep view's patterns against its source buffer
+/// Use after the source changed (edits, appended log lines)
 #[wasm_bindgen]
-pub fn free_file_buffer(file_id: u32) -> Result<(), JsValue> {
+pub fn refresh_grep_view(view_id: u32) -> Result<JsValue, JsValue> {
     ensure_initialized();
 
     let mut buffers = FILE_BUFFERS.lock().unwrap();
-    if let Some(map) = buffers.as_mut() {
-        map.remove(&file_id);
-        Ok(())
+    let mut views = GREP_VIEWS.lock().unwrap();
+    if let (Some(map), Some(views)) = (buffers.as_mut(), views.as_mut()) {
+        let view = views
+            .get_mut(&view_id)
+            .ok_or_else(|| JsValue::from_str(&format!("File {} is not a grep view", view_id)))?;
+        let source = map.get(&view.source_id).map_err(|e| match e.code {
+            ErrorCode::NotFound => {
+                JsValue::from_str(&format!("Source file {} not found", view.source_id))
+            }
+            _ => e.legacy(),
+        })?;
+
+        let (content, line_map) =
+            grep_view::filter_lines(source, &view.include, &view.exclude, view.case_insensitive)
+                .map_err(|e| JsValue::from_str(&e))?;
+        let source_line_count = source.line_count();
+
+        let buffer = FileBuffer::new(content)
+            .map_err(|e| JsValue::from_str(&format!("Failed to create buffer: {}", e)))?;
+        swap_buffer(map, view_id, buffer);
+        view.line_map = line_map;
+
+        let info = GrepViewInfo {
+            file_id: view_id,
+            source_id: view.source_id,
+            line_count: view.line_map.len(),
+            source_line_count,
+        };
+
+        serde_wasm_bindgen::to_value(&info)
+            .map_err(|e| JsValue::from_str(&format!("Serialization error: {}", e)))
     } else {
         Err(JsValue::from_str("Storage not initialized"))
     }
 }
 
-/// Get memory usage statistics
+/// Map a line of a grep view to its line in the source buffer (1-based)
 #[wasm_bindgen]
-pub fn get_memory_stats() -> Result<JsValue, JsValue> {
+pub fn view_line_to_source(view_id: u32, view_line: usize) -> Result<u32, JsValue> {
     ensure_initialized();
 
-    let buffers = FILE_BUFFERS.lock().unwrap();
-    if let Some(map) = buffers.as_ref() {
-        let mut total_size = 0usize;
-        let mut total_index_size = 0usize;
+    let views = GREP_VIEWS.lock().unwrap();
+    if let Some(map) = views.as_ref() {
+        let view = map
+            .get(&view_id)
+            .ok_or_else(|| JsValue::from_str(&format!("File {} is not a grep view", view_id)))?;
 
-        for buffer in map.values() {
-            let stats = buffer.get_stats();
-            total_size += stats.size;
-            total_index_size += stats.index_size;
-        }
+        view.source_line(view_line)
+            .map_err(|e| JsValue::from_str(&e))
+    } else {
+        Err(JsValue::from_str("Storage not initialized"))
+    }
+}
 
-        let stats = serde_json::json!({
-            "file_count": map.len(),
-            "total_content_size": total_size,
-            "total_index_size": total_index_size,
-            "total_size": total_size + total_index_size,
-        });
+/// Map a source line to the grep view line showing it, or the next view line
+/// after it (None past the last match)
+#[wasm_bindgen]
+pub fn source_line_to_view(view_id: u32, source_line: u32) -> Result<Option<u32>, JsValue> {
+    ensure_initialized();
 
-        serde_wasm_bindgen::to_value(&stats)
-            .map_err(|e| JsValue::from_str(&format!("Serialization error: {}", e)))
+    let views = GREP_VIEWS.lock().unwrap();
+    if let Some(map) = views.as_ref() {
+        let view = map
+            .get(&view_id)
+            .ok_or_else(|| JsValue::from_str(&format!("File {} is not a grep view", view_id)))?;
+
+        Ok(view.view_line(source_line))
     } else {
         Err(JsValue::from_str("Storage not initialized"))
     }
 }
 
-/// Get full file content as string
-/// This is used when saving the file - content is stored in WASM, not React state
+/// Source line numbers for a page of a grep view, for the line-number gutter
 #[wasm_bindgen]
-pub fn get_content(file_id: u32) -> Result<String, JsValue> {
+pub fn get_view_source_lines(
+    view_id: u32,
+    start_line: usize,
+    end_line: usize,
+) -> Result<Vec<u32>, JsValue> {
     ensure_initialized();
 
-    let buffers = FILE_BUFFERS.lock().unwrap();
-    if let Some(map) = buffers.as_ref() {
-        let buffer = map
-            .get(&file_id)
-            .ok_or_else(|| JsValue::from_str(&format!("File {} not found", file_id)))?;
+    let views = GREP_VIEWS.lock().unwrap();
+    if let Some(map) = views.as_ref() {
+        let view = map
+            .get(&view_id)
+            .ok_or_else(|| JsValue::from_str(&format!("File {} is not a grep view", view_id)))?;
 
-        // Convert UTF-8 bytes to string
-        String::from_utf8(buffer.content.clone())
-            .map_err(|e| JsValue::from_str(&format!("UTF-8 decode error: {}", e)))
+        view.source_lines(start_line, end_line)
+            .map(|lines| lines.to_vec())
+            .map_err(|e| JsValue::from_str(&e))
     } else {
         Err(JsValue::from_str("Storage not initialized"))
     }
@@ -234,6 +1426,7 @@ pub fn get_content(file_id: u32) -> Result<String, JsValue> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use file_buffer::{FileInfo, SearchMatch};
     use wasm_bindgen_test::*;
 
     #[wasm_bindgen_test]
@@ -272,4 +1465,26 @@ mod tests {
         assert_eq!(results[0].line, 1);
         assert_eq!(results[1].line, 3);
     }
+
+    #[wasm_bindgen_test]
+    fn test_merge3_and_resolve() {
+        init();
+        let base = create_file_buffer(b"a\nb\nc\n").unwrap();
+        let ours = create_file_buffer(b"a\nours\nc\n").unwrap();
+        let theirs = create_file_buffer(b"a\ntheirs\nc\n").unwrap();
+
+        let result_js = merge3(base, ours, theirs).unwrap();
+        let result: MergeResult = serde_wasm_bindgen::from_value(result_js).unwrap();
+        assert_eq!(result.conflicts.len(), 1);
+
+        // An edit made before resolving survives, and the resolution undoes
+        edit_buffer(result.file_id, 0, 1, "first").unwrap();
+        resolve_conflict(result.file_id, 0, "theirs").unwrap();
+        let merged = get_content(result.file_id).unwrap();
+        assert_eq!(merged, "first\ntheirs\nc\n");
+
+        undo_edit(result.file_id).unwrap();
+        let reopened = get_content(result.file_id).unwrap();
+        assert!(reopened.starts_with("first\n<<<<<<< ours\n"));
+    }
 }
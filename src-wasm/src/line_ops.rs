@@ -0,0 +1,468 @@
+use crate::file_buffer::FileBuffer;
+use regex::bytes::Regex;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::str::FromStr;
+
+/// How sort keys are compared
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SortMode {
+    Lexical,
+    Natural, // Digit runs compare by value: "file2" < "file10"
+    Numeric, // Leading number of the key; keys without one sort last
+    CaseInsensitive,
+}
+
+impl FromStr for SortMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "lexical" => Ok(SortMode::Lexical),
+            "natural" => Ok(SortMode::Natural),
+            "numeric" => Ok(SortMode::Numeric),
+            "case-insensitive" | "case_insensitive" => Ok(SortMode::CaseInsensitive),
+            _ => Err(format!(
+                "Unknown sort mode '{}' (expected lexical, natural, numeric or case-insensitive)",
+                s
+            )),
+        }
+    }
+}
+
+/// Sort settings; the key is the whole line unless a column or regex is given
+pub struct SortOptions<'a> {
+    pub mode: SortMode,
+    pub descending: bool,
+    pub key_column: Option<usize>,    // 1-based field
+    pub delimiter: Option<&'a str>,   // Field separator, whitespace if None
+    pub key_pattern: Option<&'a str>, // First capture group (or whole match) is the key
+}
+
+/// A bulk operation on a range of lines
+pub enum LineOp<'a> {
+    Sort(SortOptions<'a>),
+    Unique {
+        case_insensitive: bool,
+        with_counts: bool, // Prefix each line with its count, like `uniq -c`
+    },
+    Reverse,
+    Shuffle {
+        seed: u32,
+    },
+    Join {
+        separator: &'a str,
+    },
+    Split {
+        delimiter: &'a str,
+    },
+    RemoveBlank,
+}
+
+/// Outcome of a line operation
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct LineOpResult {
+    pub start_line: usize,
+    pub lines_before: usize, // Lines in the range before the operation
+    pub lines_after: usize,  // Lines the range holds now
+    pub line_count: usize,
+    pub size: usize,
+}
+
+/// Apply `op` to lines `start_line..=end_line` (the whole file by default)
+/// The range is rewritten with a single replace_range, so one undo reverts it
+pub fn apply(
+    buffer: &mut FileBuffer,
+    start_line: Option<usize>,
+    end_line: Option<usize>,
+    op: LineOp,
+) -> Result<LineOpResult, String> {
+    let start_line = start_line.unwrap_or(1);
+    let end_line = end_line.unwrap_or(buffer.line_count());
+
+    if buffer.line_count() == 0 {
+        return Ok(LineOpResult {
+            start_line: 1,
+            lines_before: 0,
+            lines_after: 0,
+            line_count: 0,
+            size: buffer.content.len(),
+        });
+    }
+    if start_line > end_line {
+        return Err(format!(
+            "Invalid range: start {} > end {}",
+            start_line, end_line
+        ));
+    }
+
+    let (start, _) = buffer.get_line_byte_range(start_line)?;
+    let (_, end) = buffer.get_line_byte_range(end_line)?;
+    let region = &buffer.content[start..end];
+
+    let trailing_newline = region.ends_with(b"\n");
+    let eol: &[u8] = match region.iter().position(|&b| b == b'\n') {
+        Some(i) if i > 0 && region[i - 1] == b'\r' => b"\r\n",
+        _ => b"\n",
+    };
+
+    let body = region.strip_suffix(b"\n").unwrap_or(region);
+    let lines: Vec<&[u8]> = body
+        .split(|&b| b == b'\n')
+        .map(|l| l.strip_suffix(b"\r").unwrap_or(l))
+        .collect();
+    let lines_before = lines.len();
+
+    let output = match op {
+        LineOp::Sort(options) => sort(lines, &options)?
+            .into_iter()
+            .map(Cow::Borrowed)
+            .collect(),
+        LineOp::Unique {
+            case_insensitive,
+            with_counts,
+        } => unique(lines, case_insensitive, with_counts),
+        LineOp::Reverse => lines.into_iter().rev().map(Cow::Borrowed).collect(),
+        LineOp::Shuffle { seed } => shuffle(lines, seed)
+            .into_iter()
+            .map(Cow::Borrowed)
+            .collect(),
+        LineOp::Join { separator } => vec![Cow::Owned(lines.join(separator.as_bytes()))],
+        LineOp::Split { delimiter } => split(lines, delimiter)?,
+        LineOp::RemoveBlank => lines
+            .into_iter()
+            .filter(|l| !l.iter().all(|b| b.is_ascii_whitespace()))
+            .map(Cow::Borrowed)
+            .collect(),
+    };
+    let lines_after = output.len();
+
+    let mut text = Vec::with_capacity(region.len());
+    for (i, line) in output.iter().enumerate() {
+        if i > 0 {
+            text.extend_from_slice(eol);
+        }
+        text.extend_from_slice(line);
+    }
+    if trailing_newline && !output.is_empty() {
+        text.extend_from_slice(eol);
+    }
+
+    // Don't record an edit that changes nothing
+    if text != region {
+        buffer.replace_range(start, end, &text)?;
+    }
+
+    Ok(LineOpResult {
+        start_line,
+        lines_before,
+        lines_after,
+        line_count: buffer.line_count(),
+        size: buffer.content.len(),
+    })
+}
+
+/// Sort key of a line according to the column/pattern options
+fn extract_key<'l>(line: &'l [u8], options: &SortOptions<'l>, pattern: Option<&Regex>) -> &'l [u8] {
+    if let Some(re) = pattern {
+        return match re.captures(line) {
+            Some(caps) => caps
+                .get(1)
+                .or_else(|| caps.get(0))
+                .map_or(&[], |m| m.as_bytes()),
+            None => &[],
+        };
+    }
+
+    let Some(column) = options.key_column else {
+        return line;
+    };
+    let field = match options.delimiter.filter(|d| !d.is_empty()) {
+        Some(delimiter) => split_bytes(line, delimiter.as_bytes()).nth(column.saturating_sub(1)),
+        None => line
+            .split(|b| b.is_ascii_whitespace())
+            .filter(|f| !f.is_empty())
+            .nth(column.saturating_sub(1)),
+    };
+    field.unwrap_or(&[])
+}
+
+/// Split on a multi-byte delimiter
+fn split_bytes<'l>(line: &'l [u8], delimiter: &'l [u8]) -> impl Iterator<Item = &'l [u8]> {
+    let mut rest = Some(line);
+    std::iter::from_fn(move || {
+        let current = rest?;
+        match current
+            .windows(delimiter.len())
+            .position(|w| w == delimiter)
+        {
+            Some(i) => {
+                rest = Some(&current[i + delimiter.len()..]);
+                Some(&current[..i])
+            }
+            None => {
+                rest = None;
+                Some(current)
+            }
+        }
+    })
+}
+
+/// Leading number of a key (after whitespace), e.g. "-1.5e3 ms" -> -1500
+fn leading_number(key: &[u8], re: &Regex) -> Option<f64> {
+    let m = re.find(key)?;
+    std::str::from_utf8(m.as_bytes()).ok()?.trim().parse().ok()
+}
+
+/// Compare with digit runs ordered by numeric value
+fn natural_cmp(a: &[u8], b: &[u8]) -> Ordering {
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        if a[i].is_ascii_digit() && b[j].is_ascii_digit() {
+            let run = |s: &[u8], from: usize| {
+                from + s[from..].iter().take_while(|c| c.is_ascii_digit()).count()
+            };
+            let (end_a, end_b) = (run(a, i), run(b, j));
+            let trim = |s: &'_ [u8]| -> usize { s.iter().take_while(|&&c| c == b'0').count() };
+            let num_a = &a[i + trim(&a[i..end_a])..end_a];
+            let num_b = &b[j + trim(&b[j..end_b])..end_b];
+
+            let ord = num_a.len().cmp(&num_b.len()).then_with(|| num_a.cmp(num_b));
+            if ord != Ordering::Equal {
+                return ord;
+            }
+            i = end_a;
+            j = end_b;
+        } else {
+            if a[i] != b[j] {
+                return a[i].cmp(&b[j]);
+            }
+            i += 1;
+            j += 1;
+        }
+    }
+    (a.len() - i).cmp(&(b.len() - j)).then_with(|| a.cmp(b))
+}
+
+/// Stable sort of the lines by their keys
+pub fn sort<'l>(lines: Vec<&'l [u8]>, options: &SortOptions) -> Result<Vec<&'l [u8]>, String> {
+    let pattern = options
+        .key_pattern
+        .map(Regex::new)
+        .transpose()
+        .map_err(|e| format!("Invalid regex: {}", e))?;
+    let keys: Vec<&[u8]> = lines
+        .iter()
+        .map(|l| extract_key(l, options, pattern.as_ref()))
+        .collect();
+
+    let lowered: Vec<String> = match options.mode {
+        SortMode::CaseInsensitive => keys
+            .iter()
+            .map(|k| String::from_utf8_lossy(k).to_lowercase())
+            .collect(),
+        _ => Vec::new(),
+    };
+    let numbers: Vec<Option<f64>> = match options.mode {
+        SortMode::Numeric => {
+            let number = Regex::new(r"^\s*[-+]?(\d+\.?\d*|\.\d+)([eE][-+]?\d+)?").unwrap();
+            keys.iter().map(|k| leading_number(k, &number)).collect()
+        }
+        _ => Vec::new(),
+    };
+
+    let compare = |a: usize, b: usize| -> Ordering {
+        let ord = match options.mode {
+            SortMode::Lexical => Ordering::Equal,
+            SortMode::Natural => natural_cmp(keys[a], keys[b]),
+            SortMode::CaseInsensitive => lowered[a].cmp(&lowered[b]),
+            SortMode::Numeric => match (numbers[a], numbers[b]) {
+                (Some(x), Some(y)) => x.total_cmp(&y),
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => Ordering::Equal,
+            },
+        };
+        ord.then_with(|| keys[a].cmp(keys[b]))
+    };
+
+    let mut order: Vec<usize> = (0..lines.len()).collect();
+    order.sort_by(|&a, &b| {
+        let ord = compare(a, b);
+        if options.descending {
+            ord.reverse()
+        } else {
+            ord
+        }
+    });
+
+    Ok(order.into_iter().map(|i| lines[i]).collect())
+}
+
+/// Drop repeated lines, keeping the first occurrence of each
+pub fn unique(lines: Vec<&[u8]>, case_insensitive: bool, with_counts: bool) -> Vec<Cow<'_, [u8]>> {
+    let mut counts: HashMap<Cow<[u8]>, usize> = HashMap::new();
+    let mut kept = Vec::new();
+
+    for line in lines {
+        let key = if case_insensitive {
+            Cow::Owned(String::from_utf8_lossy(line).to_lowercase().into_bytes())
+        } else {
+            Cow::Borrowed(line)
+        };
+        let count = counts.entry(key.clone()).or_insert(0);
+        if *count == 0 {
+            kept.push((line, key));
+        }
+        *count += 1;
+    }
+
+    kept.into_iter()
+        .map(|(line, key)| {
+            if with_counts {
+                let mut prefixed = format!("{:>7} ", counts[&key]).into_bytes();
+                prefixed.extend_from_slice(line);
+                Cow::Owned(prefixed)
+            } else {
+                Cow::Borrowed(line)
+            }
+        })
+        .collect()
+}
+
+/// Fisher-Yates shuffle driven by a seeded SplitMix64 generator, so the
+/// same seed always produces the same order
+pub fn shuffle(mut lines: Vec<&[u8]>, seed: u32) -> Vec<&[u8]> {
+    let mut state = seed as u64;
+    let mut next = || {
+        state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    };
+
+    for i in (1..lines.len()).rev() {
+        let j = (next() % (i as u64 + 1)) as usize;
+        lines.swap(i, j);
+    }
+    lines
+}
+
+/// Break every line at each occurrence of `delimiter`
+pub fn split<'l>(lines: Vec<&'l [u8]>, delimiter: &'l str) -> Result<Vec<Cow<'l, [u8]>>, String> {
+    if delimiter.is_empty() {
+        return Err("Split delimiter must not be empty".to_string());
+    }
+
+    Ok(lines
+        .into_iter()
+        .flat_map(|line| split_bytes(line, delimiter.as_bytes()))
+        .map(Cow::Borrowed)
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(text: &str, op: LineOp) -> String {
+        let mut buffer = FileBuffer::new(text.as_bytes().to_vec()).unwrap();
+        apply(&mut buffer, None, None, op).unwrap();
+        String::from_utf8(buffer.content.into_vec()).unwrap()
+    }
+
+    fn sort_by(mode: SortMode) -> LineOp<'static> {
+        LineOp::Sort(SortOptions {
+            mode,
+            descending: false,
+            key_column: None,
+            delimiter: None,
+            key_pattern: None,
+        })
+    }
+
+    #[test]
+    fn test_sort_modes() {
+        let text = "file10\nFile2\nfile2\nfile1\n";
+        assert_eq!(
+            run(text, sort_by(SortMode::Lexical)),
+            "File2\nfile1\nfile10\nfile2\n"
+        );
+        assert_eq!(
+            run(text, sort_by(SortMode::Natural)),
+            "File2\nfile1\nfile2\nfile10\n"
+        );
+        assert_eq!(
+            run(text, sort_by(SortMode::CaseInsensitive)),
+            "file1\nfile10\nFile2\nfile2\n"
+        );
+        assert_eq!(
+            run("10 ms\nn/a\n-2.5\n3e1\n", sort_by(SortMode::Numeric)),
+            "-2.5\n10 ms\n3e1\nn/a\n"
+        );
+    }
+
+    #[test]
+    fn test_sort_by_column_and_pattern() {
+        let by_column = LineOp::Sort(SortOptions {
+            mode: SortMode::Numeric,
+            descending: true,
+            key_column: Some(2),
+            delimiter: Some(","),
+            key_pattern: None,
+        });
+        assert_eq!(run("a,1\r\nb,3\r\nc,2", by_column), "b,3\r\nc,2\r\na,1");
+
+        let by_pattern = LineOp::Sort(SortOptions {
+            mode: SortMode::Lexical,
+            descending: false,
+            key_column: None,
+            delimiter: None,
+            key_pattern: Some(r"id=(\w+)"),
+        });
+        assert_eq!(run("x id=b\ny id=a\n", by_pattern), "y id=a\nx id=b\n");
+    }
+
+    #[test]
+    fn test_unique_reverse_join_split() {
+        let dedupe = LineOp::Unique {
+            case_insensitive: true,
+            with_counts: true,
+        };
+        assert_eq!(
+            run("a\nB\nA\nb\nc\n", dedupe),
+            "      2 a\n      2 B\n      1 c\n"
+        );
+        assert_eq!(run("1\n2\n3", LineOp::Reverse), "3\n2\n1");
+        assert_eq!(
+            run("a\nb\nc\n", LineOp::Join { separator: ", " }),
+            "a, b, c\n"
+        );
+        assert_eq!(
+            run("a,b\nc\n", LineOp::Split { delimiter: "," }),
+            "a\nb\nc\n"
+        );
+        assert_eq!(run("a\n\n  \nb\n", LineOp::RemoveBlank), "a\nb\n");
+    }
+
+    #[test]
+    fn test_range_shuffle_and_undo() {
+        let mut buffer = FileBuffer::new(b"keep\n3\n1\n2\nkeep\n".to_vec()).unwrap();
+        let result = apply(&mut buffer, Some(2), Some(4), sort_by(SortMode::Numeric)).unwrap();
+        assert_eq!(buffer.content, b"keep\n1\n2\n3\nkeep\n");
+        assert_eq!((result.lines_before, result.lines_after), (3, 3));
+
+        assert_eq!(buffer.undo(), Some(2));
+        assert_eq!(buffer.content, b"keep\n3\n1\n2\nkeep\n");
+
+        let lines: Vec<&[u8]> = (0..50u8)
+            .map(|i| &b"abcdefghijklmnopqrstuvwxyz"[i as usize % 26..])
+            .collect();
+        let shuffled = shuffle(lines.clone(), 7);
+        assert_eq!(shuffled, shuffle(lines.clone(), 7));
+        assert_ne!(shuffled, lines);
+    }
+}
@@ -0,0 +1,608 @@
+use serde::{Deserialize, Serialize};
+
+/// Timestamp value for lines without one
+const NO_TIMESTAMP: i64 = i64::MIN;
+
+/// Flag set on lines that continue the previous entry (stack traces etc.)
+const CONTINUATION: u8 = 0x80;
+
+/// Number of leading lines used to detect the log format
+const DETECT_SAMPLE_LINES: usize = 200;
+
+/// Number of words after the timestamp searched for a level
+const LEVEL_SEARCH_WORDS: usize = 6;
+
+/// Log severity, ordered from least to most severe
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    Unknown = 0,
+    Trace = 1,
+    Debug = 2,
+    Info = 3,
+    Warn = 4,
+    Error = 5,
+    Fatal = 6,
+}
+
+impl LogLevel {
+    fn from_u8(value: u8) -> Self {
+        match value & !CONTINUATION {
+            1 => LogLevel::Trace,
+            2 => LogLevel::Debug,
+            3 => LogLevel::Info,
+            4 => LogLevel::Warn,
+            5 => LogLevel::Error,
+            6 => LogLevel::Fatal,
+            _ => LogLevel::Unknown,
+        }
+    }
+
+    /// Parse a level keyword as written by common logging libraries
+    pub fn parse(word: &str) -> Option<Self> {
+        match word.to_ascii_uppercase().as_str() {
+            "TRACE" | "FINEST" | "FINER" => Some(LogLevel::Trace),
+            "DEBUG" | "DBG" | "FINE" => Some(LogLevel::Debug),
+            "INFO" | "INF" | "NOTICE" | "CONFIG" => Some(LogLevel::Info),
+            "WARN" | "WARNING" | "WRN" => Some(LogLevel::Warn),
+            "ERROR" | "ERR" | "SEVERE" => Some(LogLevel::Error),
+            "FATAL" | "CRITICAL" | "CRIT" | "ALERT" | "EMERG" | "PANIC" => Some(LogLevel::Fatal),
+            _ => None,
+        }
+    }
+
+    /// Numeric levels used by bunyan/pino JSON logs
+    fn from_number(n: i64) -> Self {
+        match n {
+            ..=10 => LogLevel::Trace,
+            11..=20 => LogLevel::Debug,
+            21..=30 => LogLevel::Info,
+            31..=40 => LogLevel::Warn,
+            41..=50 => LogLevel::Error,
+            _ => LogLevel::Fatal,
+        }
+    }
+}
+
+/// Detected log line format
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    Json,
+    Syslog,
+    Log4j,
+    Iso8601,
+    Unknown,
+}
+
+/// Per-level line counts
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct LevelCounts {
+    pub unknown: usize,
+    pub trace: usize,
+    pub debug: usize,
+    pub info: usize,
+    pub warn: usize,
+    pub error: usize,
+    pub fatal: usize,
+}
+
+impl LevelCounts {
+    fn add(&mut self, level: LogLevel) {
+        match level {
+            LogLevel::Unknown => self.unknown += 1,
+            LogLevel::Trace => self.trace += 1,
+            LogLevel::Debug => self.debug += 1,
+            LogLevel::Info => self.info += 1,
+            LogLevel::Warn => self.warn += 1,
+            LogLevel::Error => self.error += 1,
+            LogLevel::Fatal => self.fatal += 1,
+        }
+    }
+}
+
+/// Summary returned after indexing
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct LogSummary {
+    pub format: LogFormat,
+    pub line_count: usize,
+    pub entry_count: usize,           // Lines that start a log entry
+    pub first_timestamp: Option<i64>, // Milliseconds since the Unix epoch (UTC)
+    pub last_timestamp: Option<i64>,
+    pub levels: LevelCounts, // Entries per level
+}
+
+/// One bar of the events-over-time histogram
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct HistogramBucket {
+    pub start: i64, // Inclusive, ms since epoch
+    pub end: i64,   // Exclusive, ms since epoch
+    pub total: usize,
+    pub levels: LevelCounts,
+}
+
+/// Per-line level and timestamp index of a log buffer
+/// Continuation lines (stack traces, wrapped messages) inherit the level and
+/// timestamp of the entry they belong to
+pub struct LogIndex {
+    pub format: LogFormat,
+    levels: Vec<u8>,      // LogLevel, with CONTINUATION flag
+    timestamps: Vec<i64>, // ms since epoch, or NO_TIMESTAMP
+}
+
+impl LogIndex {
+    /// Index every line of a buffer
+    /// `default_year` is used for formats that omit the year (classic syslog)
+    pub fn build(
+        content: &[u8],
+        line_offsets: &[usize],
+        line_count: usize,
+        default_year: i32,
+    ) -> Self {
+        let line = |n: usize| {
+            let start = line_offsets[n];
+            let end = line_offsets.get(n + 1).map_or(content.len(), |&o| o);
+            trim_eol(&content[start..end])
+        };
+
+        let format = detect_format((0..line_count.min(DETECT_SAMPLE_LINES)).map(line));
+
+        let mut levels = Vec::with_capacity(line_count);
+        let mut timestamps = Vec::with_capacity(line_count);
+        let mut current = (LogLevel::Unknown, NO_TIMESTAMP);
+
+        for n in 0..line_count {
+            match parse_line(line(n), format, default_year) {
+                Some((level, timestamp)) => {
+                    current = (level, timestamp);
+                    levels.push(level as u8);
+                }
+                None => levels.push(current.0 as u8 | CONTINUATION),
+            }
+            timestamps.push(current.1);
+        }
+
+        LogIndex {
+            format,
+            levels,
+            timestamps,
+        }
+    }
+
+    /// Approximate heap bytes held by the index
+    pub fn heap_size(&self) -> usize {
+        std::mem::size_of_val(self.levels.as_slice())
+            + std::mem::size_of_val(self.timestamps.as_slice())
+    }
+
+    /// Level of a line (0-based)
+    pub fn level(&self, line: usize) -> LogLevel {
+        LogLevel::from_u8(self.levels[line])
+    }
+
+    /// Timestamp of a line (0-based), if known
+    pub fn timestamp(&self, line: usize) -> Option<i64> {
+        Some(self.timestamps[line]).filter(|&t| t != NO_TIMESTAMP)
+    }
+
+    fn is_entry(&self, line: usize) -> bool {
+        self.levels[line] & CONTINUATION == 0
+    }
+
+    pub fn summary(&self) -> LogSummary {
+        let mut levels = LevelCounts::default();
+        let mut entry_count = 0;
+
+        for line in (0..self.levels.len()).filter(|&l| self.is_entry(l)) {
+            levels.add(self.level(line));
+            entry_count += 1;
+        }
+
+        let known = |t: &&i64| **t != NO_TIMESTAMP;
+        LogSummary {
+            format: self.format,
+            line_count: self.levels.len(),
+            entry_count,
+            first_timestamp: self.timestamps.iter().find(known).copied(),
+            last_timestamp: self.timestamps.iter().rev().find(known).copied(),
+            levels,
+        }
+    }
+
+    /// Lines (1-based) whose level is in `levels` (any level if empty) and
+    /// whose timestamp lies in `[start, end)`. Lines without a timestamp are
+    /// excluded once a time bound is given.
+    pub fn filter(
+        &self,
+        levels: &[LogLevel],
+        start: Option<i64>,
+        end: Option<i64>,
+        max_results: usize,
+    ) -> Vec<usize> {
+        let timed = start.is_some() || end.is_some();
+
+        (0..self.levels.len())
+            .filter(|&line| levels.is_empty() || levels.contains(&self.level(line)))
+            .filter(|&line| {
+                if !timed {
+                    return true;
+                }
+                match self.timestamp(line) {
+                    Some(t) => {
+                        !matches!(start, Some(s) if t < s) && !matches!(end, Some(e) if t >= e)
+                    }
+                    None => false,
+                }
+            })
+            .map(|line| line + 1)
+            .take(max_results)
+            .collect()
+    }
+
+    /// Count entries over time in `bucket_count` equal-width buckets spanning
+    /// the first to the last timestamp
+    pub fn histogram(&self, bucket_count: usize) -> Vec<HistogramBucket> {
+        let summary = self.summary();
+        let (first, last) = match (summary.first_timestamp, summary.last_timestamp) {
+            (Some(first), Some(last)) if bucket_count > 0 => (first, last.max(first)),
+            _ => return Vec::new(),
+        };
+
+        let width = ((last - first) / bucket_count as i64 + 1).max(1);
+        let mut buckets: Vec<HistogramBucket> = (0..bucket_count as i64)
+            .map(|b| HistogramBucket {
+                start: first + b * width,
+                end: first + (b + 1) * width,
+                total: 0,
+                levels: LevelCounts::default(),
+            })
+            .collect();
+
+        for line in (0..self.levels.len()).filter(|&l| self.is_entry(l)) {
+            if let Some(t) = self.timestamp(line) {
+                // Out-of-order timestamps before the first one are clamped
+                let b = (((t - first).max(0)) / width) as usize;
+                let bucket = &mut buckets[b.min(bucket_count - 1)];
+                bucket.total += 1;
+                bucket.levels.add(self.level(line));
+            }
+        }
+
+        buckets
+    }
+}
+
+fn trim_eol(line: &[u8]) -> &[u8] {
+    let line = line.strip_suffix(b"\n").unwrap_or(line);
+    line.strip_suffix(b"\r").unwrap_or(line)
+}
+
+/// Pick the format most sample lines agree on
+fn detect_format<'a>(sample: impl Iterator<Item = &'a [u8]>) -> LogFormat {
+    let mut counts = [0usize; 4];
+    let formats = [
+        LogFormat::Json,
+        LogFormat::Syslog,
+        LogFormat::Log4j,
+        LogFormat::Iso8601,
+    ];
+
+    for line in sample.filter(|l| !l.is_empty()) {
+        if line.starts_with(b"{") && serde_json::from_slice::<serde_json::Value>(line).is_ok() {
+            counts[0] += 1;
+        } else if parse_syslog_prefix(line, 1970).is_some() {
+            counts[1] += 1;
+        } else if let Some((_, consumed)) = parse_iso_prefix(line) {
+            let is_log4j = line.get(10) == Some(&b' ')
+                && first_words(&line[consumed..], 1).any(|w| LogLevel::parse(w).is_some());
+            counts[if is_log4j { 2 } else { 3 }] += 1;
+        }
+    }
+
+    counts
+        .iter()
+        .enumerate()
+        .filter(|(_, &count)| count > 0)
+        .max_by_key(|(i, &count)| (count, std::cmp::Reverse(*i)))
+        .map_or(LogFormat::Unknown, |(i, _)| formats[i])
+}
+
+/// Extract (level, timestamp) from a line that starts a log entry
+/// Returns None for continuation lines
+fn parse_line(line: &[u8], format: LogFormat, default_year: i32) -> Option<(LogLevel, i64)> {
+    match format {
+        LogFormat::Json => parse_json_line(line),
+        LogFormat::Syslog => {
+            let (timestamp, consumed) = parse_syslog_prefix(line, default_year)?;
+            Some((find_level(&line[consumed..]), timestamp))
+        }
+        LogFormat::Log4j | LogFormat::Iso8601 => {
+            let (timestamp, consumed) = parse_iso_prefix(line)?;
+            Some((find_level(&line[consumed..]), timestamp))
+        }
+        LogFormat::Unknown => {
+            // Without timestamps every non-indented line is its own entry
+            if line.first().is_some_and(|b| b.is_ascii_whitespace()) {
+                None
+            } else {
+                Some((find_level(line), NO_TIMESTAMP))
+            }
+        }
+    }
+}
+
+fn parse_json_line(line: &[u8]) -> Option<(LogLevel, i64)> {
+    if !line.starts_with(b"{") {
+        return None;
+    }
+    let value: serde_json::Value = serde_json::from_slice(line).ok()?;
+    let object = value.as_object()?;
+
+    let level = ["level", "severity", "lvl", "loglevel", "levelname"]
+        .iter()
+        .find_map(|key| object.get(*key))
+        .and_then(|v| match v {
+            serde_json::Value::String(s) => LogLevel::parse(s),
+            serde_json::Value::Number(n) => n.as_i64().map(LogLevel::from_number),
+            _ => None,
+        })
+        .unwrap_or(LogLevel::Unknown);
+
+    let timestamp = ["timestamp", "@timestamp", "time", "ts", "datetime", "date"]
+        .iter()
+        .find_map(|key| object.get(*key))
+        .and_then(|v| match v {
+            serde_json::Value::String(s) => parse_iso_prefix(s.as_bytes()).map(|(t, _)| t),
+            // Epoch seconds or milliseconds
+            serde_json::Value::Number(n) => n.as_f64().map(|f| {
+                if f.abs() >= 1e11 {
+                    f as i64
+                } else {
+                    (f * 1000.0) as i64
+                }
+            }),
+            _ => None,
+        })
+        .unwrap_or(NO_TIMESTAMP);
+
+    Some((level, timestamp))
+}
+
+/// Words at the start of `text`, split on anything but letters
+fn first_words(text: &[u8], count: usize) -> impl Iterator<Item = &str> {
+    std::str::from_utf8(&text[..text.len().min(120)])
+        .unwrap_or("")
+        .split(|c: char| !c.is_ascii_alphabetic())
+        .filter(|w| !w.is_empty())
+        .take(count)
+}
+
+fn find_level(text: &[u8]) -> LogLevel {
+    first_words(text, LEVEL_SEARCH_WORDS)
+        .find_map(LogLevel::parse)
+        .unwrap_or(LogLevel::Unknown)
+}
+
+fn digits(bytes: &[u8], start: usize, len: usize) -> Option<i64> {
+    let slice = bytes.get(start..start + len)?;
+    slice.iter().try_fold(0i64, |acc, &b| {
+        b.is_ascii_digit().then(|| acc * 10 + (b - b'0') as i64)
+    })
+}
+
+/// Days since 1970-01-01 for a proleptic Gregorian date
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let mp = (month + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+fn to_millis(year: i64, month: i64, day: i64, hour: i64, minute: i64, second: i64) -> Option<i64> {
+    if !(1..=12).contains(&month)
+        || !(1..=31).contains(&day)
+        || hour > 23
+        || minute > 59
+        || second > 60
+    {
+        return None;
+    }
+    let days = days_from_civil(year, month, day);
+    Some(((days * 24 + hour) * 60 + minute) * 60_000 + second * 1000)
+}
+
+/// Parse an ISO-8601 style timestamp at the start of a line, optionally
+/// wrapped in brackets or prefixed by an RFC 5424 "<PRI>VERSION " header
+/// Returns (ms since epoch, bytes consumed)
+pub fn parse_iso_prefix(line: &[u8]) -> Option<(i64, usize)> {
+    let mut p = 0;
+    if line.first() == Some(&b'<') {
+        // RFC 5424: "<34>1 2003-10-11T22:14:15.003Z ..."
+        p = line.iter().position(|&b| b == b'>')? + 1;
+        while line.get(p).is_some_and(u8::is_ascii_digit) {
+            p += 1;
+        }
+        while line.get(p) == Some(&b' ') {
+            p += 1;
+        }
+    }
+    if line.get(p) == Some(&b'[') {
+        p += 1;
+    }
+
+    let s = &line[p..];
+    if s.len() < 19
+        || s[4] != b'-'
+        || s[7] != b'-'
+        || !matches!(s[10], b'T' | b' ')
+        || s[13] != b':'
+        || s[16] != b':'
+    {
+        return None;
+    }
+
+    let mut millis = to_millis(
+        digits(s, 0, 4)?,
+        digits(s, 5, 2)?,
+        digits(s, 8, 2)?,
+        digits(s, 11, 2)?,
+        digits(s, 14, 2)?,
+        digits(s, 17, 2)?,
+    )?;
+    let mut i = 19;
+
+    // Fractional seconds (log4j uses a comma)
+    if matches!(s.get(i), Some(b'.') | Some(b',')) && s.get(i + 1).is_some_and(u8::is_ascii_digit) {
+        i += 1;
+        let frac_start = i;
+        while s.get(i).is_some_and(u8::is_ascii_digit) {
+            i += 1;
+        }
+        let frac = &s[frac_start..i];
+        let ms_digits = &frac[..frac.len().min(3)];
+        let ms = digits(ms_digits, 0, ms_digits.len())?;
+        millis += ms * 10i64.pow(3 - ms_digits.len() as u32);
+    }
+
+    // Time zone designator
+    match s.get(i) {
+        Some(b'Z') => i += 1,
+        Some(&sign @ (b'+' | b'-')) => {
+            if let Some(hours) = digits(s, i + 1, 2) {
+                let (minutes, len) = if s.get(i + 3) == Some(&b':') {
+                    (digits(s, i + 4, 2).unwrap_or(0), 6)
+                } else {
+                    (digits(s, i + 3, 2).unwrap_or(0), 5)
+                };
+                let offset = (hours * 60 + minutes) * 60_000;
+                millis += if sign == b'+' { -offset } else { offset };
+                i += len;
+            }
+        }
+        _ => {}
+    }
+
+    if s.get(i) == Some(&b']') {
+        i += 1;
+    }
+
+    Some((millis, p + i))
+}
+
+/// Parse a classic BSD syslog timestamp ("Jan  5 12:34:56") at the start of a line
+pub fn parse_syslog_prefix(line: &[u8], year: i32) -> Option<(i64, usize)> {
+    const MONTHS: [&[u8]; 12] = [
+        b"Jan", b"Feb", b"Mar", b"Apr", b"May", b"Jun", b"Jul", b"Aug", b"Sep", b"Oct", b"Nov",
+        b"Dec",
+    ];
+
+    if line.len() < 15 || line[3] != b' ' || line[9] != b':' || line[12] != b':' {
+        return None;
+    }
+    let month = MONTHS.iter().position(|m| line[..3] == **m)? as i64 + 1;
+    let day = match line[4] {
+        b' ' => digits(line, 5, 1)?,
+        _ => digits(line, 4, 2)?,
+    };
+
+    let millis = to_millis(
+        year as i64,
+        month,
+        day,
+        digits(line, 7, 2)?,
+        digits(line, 10, 2)?,
+        digits(line, 13, 2)?,
+    )?;
+
+    Some((millis, 15))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file_buffer::FileBuffer;
+
+    fn index(text: &str) -> LogIndex {
+        let buffer = FileBuffer::new(text.as_bytes().to_vec()).unwrap();
+        LogIndex::build(
+            &buffer.content,
+            &buffer.line_offsets,
+            buffer.line_count(),
+            2024,
+        )
+    }
+
+    #[test]
+    fn test_iso_timestamps() {
+        assert_eq!(
+            parse_iso_prefix(b"1970-01-01T00:00:01Z x"),
+            Some((1000, 20))
+        );
+        assert_eq!(
+            parse_iso_prefix(b"[2024-03-01 10:00:00.250] x").map(|(t, _)| t),
+            Some(1_709_287_200_250)
+        );
+        assert_eq!(
+            parse_iso_prefix(b"2024-03-01T12:00:00+02:00").map(|(t, _)| t),
+            parse_iso_prefix(b"2024-03-01T10:00:00Z").map(|(t, _)| t)
+        );
+        assert!(parse_iso_prefix(b"not a timestamp at all").is_none());
+    }
+
+    #[test]
+    fn test_log4j_with_stack_trace() {
+        let log = index(
+            "2024-01-01 10:00:00,000 INFO  [main] Started\n\
+             2024-01-01 10:00:05,000 ERROR [main] Boom\n\
+             java.lang.RuntimeException: Boom\n\
+             \tat Main.main(Main.java:1)\n\
+             2024-01-01 10:01:00,000 WARN  [main] Slow\n",
+        );
+
+        assert_eq!(log.format, LogFormat::Log4j);
+        let summary = log.summary();
+        assert_eq!(summary.entry_count, 3);
+        assert_eq!(summary.levels.error, 1);
+
+        // Stack trace lines follow their error entry
+        assert_eq!(
+            log.filter(&[LogLevel::Error], None, None, 100),
+            vec![2, 3, 4]
+        );
+    }
+
+    #[test]
+    fn test_json_and_time_range() {
+        let log = index(
+            "{\"time\":\"2024-01-01T00:00:00Z\",\"level\":\"info\",\"msg\":\"a\"}\n\
+             {\"time\":\"2024-01-01T00:00:10Z\",\"level\":50,\"msg\":\"b\"}\n\
+             {\"time\":\"2024-01-01T00:00:20Z\",\"level\":\"debug\",\"msg\":\"c\"}\n",
+        );
+
+        assert_eq!(log.format, LogFormat::Json);
+        assert_eq!(log.level(1), LogLevel::Error);
+
+        let start = parse_iso_prefix(b"2024-01-01T00:00:05Z").unwrap().0;
+        let end = parse_iso_prefix(b"2024-01-01T00:00:20Z").unwrap().0;
+        assert_eq!(log.filter(&[], Some(start), Some(end), 100), vec![2]);
+    }
+
+    #[test]
+    fn test_syslog_histogram() {
+        let log = index(
+            "Jan  1 00:00:00 host app: error: disk full\n\
+             Jan  1 00:00:30 host app: started\n\
+             Jan  1 00:01:00 host app: warning low memory\n",
+        );
+
+        assert_eq!(log.format, LogFormat::Syslog);
+        let histogram = log.histogram(2);
+        assert_eq!(histogram.len(), 2);
+        assert_eq!(histogram[0].total + histogram[1].total, 3);
+        assert_eq!(histogram[0].levels.error, 1);
+        assert_eq!(histogram[1].levels.warn, 1);
+    }
+}
@@ -0,0 +1,468 @@
+use crate::file_buffer::FileBuffer;
+use serde::{Deserialize, Serialize};
+use similar::{capture_diff_slices, Algorithm, DiffOp};
+use std::str::FromStr;
+
+const MARKER_OURS: &str = "<<<<<<< ours\n";
+const MARKER_SEPARATOR: &str = "=======\n";
+const MARKER_THEIRS: &str = ">>>>>>> theirs\n";
+
+/// How a conflict region should be resolved
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Resolution {
+    Ours,
+    Theirs,
+    Both,
+}
+
+impl FromStr for Resolution {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ours" => Ok(Resolution::Ours),
+            "theirs" => Ok(Resolution::Theirs),
+            "both" => Ok(Resolution::Both),
+            other => Err(format!(
+                "Unknown resolution '{}' (expected ours, theirs or both)",
+                other
+            )),
+        }
+    }
+}
+
+/// A piece of the merged output
+enum MergeChunk {
+    /// Text that merged cleanly
+    Clean(String),
+    /// Both sides changed the same base lines differently
+    Conflict {
+        base: String,
+        ours: String,
+        theirs: String,
+    },
+}
+
+/// Marker block of a conflict found in the merged buffer
+struct Block {
+    index: usize,
+    start: usize, // Byte offset of the "<<<<<<<" marker
+    end: usize,   // Byte offset after the ">>>>>>>" marker
+    start_line: usize,
+    end_line: usize,
+}
+
+/// Three-way merge state
+/// Keeps the conflicting chunks around so they can be resolved one by one.
+/// Which conflicts are still open is read from the merged buffer itself, so
+/// undoing a resolution there reopens the conflict.
+pub struct Merge3 {
+    chunks: Vec<MergeChunk>,
+}
+
+impl Merge3 {
+    /// Merge `ours` and `theirs`, both derived from `base`, line by line
+    pub fn new(base: &str, ours: &str, theirs: &str) -> Self {
+        let base_lines: Vec<&str> = base.split_inclusive('\n').collect();
+        let ours_lines: Vec<&str> = ours.split_inclusive('\n').collect();
+        let theirs_lines: Vec<&str> = theirs.split_inclusive('\n').collect();
+
+        // For every base line, the matching line in each side (if unchanged)
+        let ours_map = match_lines(&base_lines, &ours_lines);
+        let theirs_map = match_lines(&base_lines, &theirs_lines);
+
+        let mut chunks = Vec::new();
+        let mut clean = String::new();
+        let (mut i, mut j, mut k) = (0, 0, 0);
+
+        loop {
+            // Next base line that is unchanged on both sides
+            let mut m = i;
+            while m < base_lines.len() && (ours_map[m].is_none() || theirs_map[m].is_none()) {
+                m += 1;
+            }
+            let (jm, km) = if m < base_lines.len() {
+                (ours_map[m].unwrap_or(j), theirs_map[m].unwrap_or(k))
+            } else {
+                (ours_lines.len(), theirs_lines.len())
+            };
+
+            if m == i && jm == j && km == k {
+                if m == base_lines.len() {
+                    break;
+                }
+                // Stable line shared by all three versions
+                clean.push_str(base_lines[m]);
+                i += 1;
+                j += 1;
+                k += 1;
+                continue;
+            }
+
+            let base_chunk = &base_lines[i..m];
+            let ours_chunk = &ours_lines[j..jm];
+            let theirs_chunk = &theirs_lines[k..km];
+
+            if ours_chunk == base_chunk || ours_chunk == theirs_chunk {
+                clean.push_str(&theirs_chunk.concat());
+            } else if theirs_chunk == base_chunk {
+                clean.push_str(&ours_chunk.concat());
+            } else {
+                if !clean.is_empty() {
+                    chunks.push(MergeChunk::Clean(std::mem::take(&mut clean)));
+                }
+                chunks.push(MergeChunk::Conflict {
+                    base: base_chunk.concat(),
+                    ours: ours_chunk.concat(),
+                    theirs: theirs_chunk.concat(),
+                });
+            }
+
+            i = m;
+            j = jm;
+            k = km;
+        }
+
+        if !clean.is_empty() {
+            chunks.push(MergeChunk::Clean(clean));
+        }
+
+        Merge3 { chunks }
+    }
+
+    /// Total number of conflicts, resolved or not
+    pub fn conflict_count(&self) -> usize {
+        self.chunks
+            .iter()
+            .filter(|c| matches!(c, MergeChunk::Conflict { .. }))
+            .count()
+    }
+
+    /// Base, ours and theirs of every conflict, in document order
+    fn conflicts(&self) -> impl Iterator<Item = (&str, &str, &str)> {
+        self.chunks.iter().filter_map(|chunk| match chunk {
+            MergeChunk::Conflict { base, ours, theirs } => Some((&**base, &**ours, &**theirs)),
+            MergeChunk::Clean(_) => None,
+        })
+    }
+
+    /// Marker blocks of this merge's conflicts still present in `content`
+    /// Blocks are matched to conflicts by their sides, in order, wherever
+    /// edits around them have moved them
+    fn blocks(&self, content: &[u8]) -> Vec<Block> {
+        let sides: Vec<(String, String)> = self
+            .conflicts()
+            .map(|(_, ours, theirs)| (section(ours), section(theirs)))
+            .collect();
+
+        let mut blocks = Vec::new();
+        let mut next = 0; // First conflict not matched yet
+        let mut open: Option<(usize, usize)> = None; // Marker offset and line
+        let mut separator: Option<usize> = None; // Offset after "======="
+        let (mut offset, mut line) = (0, 1);
+
+        for text in content.split_inclusive(|&b| b == b'\n') {
+            if text == MARKER_OURS.as_bytes() {
+                open = Some((offset, line));
+                separator = None;
+            } else if text == MARKER_SEPARATOR.as_bytes() && open.is_some() && separator.is_none() {
+                separator = Some(offset + text.len());
+            } else if text == MARKER_THEIRS.as_bytes() {
+                if let (Some((start, start_line)), Some(theirs_start)) = (open, separator) {
+                    let ours =
+                        &content[start + MARKER_OURS.len()..theirs_start - MARKER_SEPARATOR.len()];
+                    let theirs = &content[theirs_start..offset];
+                    let found = sides[next..]
+                        .iter()
+                        .position(|(o, t)| o.as_bytes() == ours && t.as_bytes() == theirs);
+                    if let Some(i) = found {
+                        blocks.push(Block {
+                            index: next + i,
+                            start,
+                            end: offset + text.len(),
+                            start_line,
+                            end_line: line,
+                        });
+                        next += i + 1;
+                    }
+                }
+                open = None;
+                separator = None;
+            }
+            offset += text.len();
+            line += 1;
+        }
+        blocks
+    }
+
+    /// Unresolved conflicts of the merged buffer, with their 1-based line
+    /// span in it
+    pub fn conflicts_in(&self, content: &[u8]) -> Vec<ConflictRegion> {
+        let conflicts: Vec<_> = self.conflicts().collect();
+        self.blocks(content)
+            .into_iter()
+            .map(|block| {
+                let (base, ours, theirs) = conflicts[block.index];
+                ConflictRegion {
+                    index: block.index,
+                    start_line: block.start_line,
+                    end_line: block.end_line,
+                    base: base.to_string(),
+                    ours: ours.to_string(),
+                    theirs: theirs.to_string(),
+                }
+            })
+            .collect()
+    }
+
+    /// Replace the marker block of a conflict (0-based index, in document
+    /// order) in `buffer`, the merged buffer, as one undoable edit
+    /// Edits made to the buffer outside the block are kept. Returns the
+    /// conflicts still unresolved.
+    pub fn resolve_in_buffer(
+        &self,
+        buffer: &mut FileBuffer,
+        index: usize,
+        resolution: Resolution,
+    ) -> Result<Vec<ConflictRegion>, String> {
+        let total = self.conflict_count();
+        let (_, ours, theirs) = self.conflicts().nth(index).ok_or_else(|| {
+            format!(
+                "Conflict {} out of range (merge has {} conflicts)",
+                index, total
+            )
+        })?;
+        let block = self
+            .blocks(&buffer.content)
+            .into_iter()
+            .find(|block| block.index == index)
+            .ok_or_else(|| {
+                format!(
+                    "Conflict {} is not in the buffer (already resolved, or its markers were edited)",
+                    index
+                )
+            })?;
+
+        let text = match resolution {
+            Resolution::Ours => ours.to_string(),
+            Resolution::Theirs => theirs.to_string(),
+            Resolution::Both => section(ours) + theirs,
+        };
+        buffer.replace_range(block.start, block.end, text.as_bytes())?;
+        Ok(self.conflicts_in(&buffer.content))
+    }
+
+    /// Render the merged text
+    /// Conflicts are written with git-style markers and reported with their
+    /// 1-based line span in the rendered text
+    pub fn render(&self) -> MergeOutput {
+        let mut content = String::new();
+        let mut conflicts = Vec::new();
+        let mut line = 1;
+        let mut index = 0;
+
+        for chunk in &self.chunks {
+            match chunk {
+                MergeChunk::Clean(text) => push_text(&mut content, &mut line, text),
+                MergeChunk::Conflict { base, ours, theirs } => {
+                    if !content.is_empty() && !content.ends_with('\n') {
+                        push_text(&mut content, &mut line, "\n");
+                    }
+                    let start_line = line;
+                    push_text(&mut content, &mut line, MARKER_OURS);
+                    push_text(&mut content, &mut line, &section(ours));
+                    push_text(&mut content, &mut line, MARKER_SEPARATOR);
+                    push_text(&mut content, &mut line, &section(theirs));
+                    push_text(&mut content, &mut line, MARKER_THEIRS);
+
+                    conflicts.push(ConflictRegion {
+                        index,
+                        start_line,
+                        end_line: line - 1,
+                        base: base.clone(),
+                        ours: ours.clone(),
+                        theirs: theirs.clone(),
+                    });
+                    index += 1;
+                }
+            }
+        }
+
+        MergeOutput { content, conflicts }
+    }
+}
+
+/// Map each base line to its unchanged counterpart in `other`
+fn match_lines(base: &[&str], other: &[&str]) -> Vec<Option<usize>> {
+    let mut map = vec![None; base.len()];
+
+    for op in capture_diff_slices(Algorithm::Myers, base, other) {
+        if let DiffOp::Equal {
+            old_index,
+            new_index,
+            len,
+        } = op
+        {
+            for n in 0..len {
+                map[old_index + n] = Some(new_index + n);
+            }
+        }
+    }
+
+    map
+}
+
+/// Append text, keeping the running line number in sync
+fn push_text(content: &mut String, line: &mut usize, text: &str) {
+    *line += text.matches('\n').count();
+    content.push_str(text);
+}
+
+/// A conflict side terminated with a newline, so the next marker starts on
+/// its own line
+fn section(text: &str) -> String {
+    if text.is_empty() || text.ends_with('\n') {
+        text.to_string()
+    } else {
+        format!("{}\n", text)
+    }
+}
+
+/// Rendered merge result
+pub struct MergeOutput {
+    pub content: String,
+    pub conflicts: Vec<ConflictRegion>,
+}
+
+/// An unresolved conflict in the merged buffer
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ConflictRegion {
+    pub index: usize,
+    pub start_line: usize, // Line of the "<<<<<<<" marker
+    pub end_line: usize,   // Line of the ">>>>>>>" marker
+    pub base: String,
+    pub ours: String,
+    pub theirs: String,
+}
+
+/// Merge result returned to JavaScript
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct MergeResult {
+    pub file_id: u32,
+    pub conflict_count: usize,
+    pub conflicts: Vec<ConflictRegion>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::anchors::Gravity;
+
+    #[test]
+    fn test_clean_merge() {
+        let base = "a\nb\nc\nd\n";
+        let ours = "a\nB\nc\nd\n";
+        let theirs = "a\nb\nc\nD\n";
+
+        let merge = Merge3::new(base, ours, theirs);
+        let output = merge.render();
+
+        assert_eq!(output.content, "a\nB\nc\nD\n");
+        assert!(output.conflicts.is_empty());
+    }
+
+    #[test]
+    fn test_conflict_markers() {
+        let base = "a\nb\nc\n";
+        let ours = "a\nours\nc\n";
+        let theirs = "a\ntheirs\nc\n";
+
+        let output = Merge3::new(base, ours, theirs).render();
+
+        assert_eq!(
+            output.content,
+            "a\n<<<<<<< ours\nours\n=======\ntheirs\n>>>>>>> theirs\nc\n"
+        );
+        assert_eq!(output.conflicts.len(), 1);
+        assert_eq!(output.conflicts[0].start_line, 2);
+        assert_eq!(output.conflicts[0].end_line, 6);
+        assert_eq!(output.conflicts[0].base, "b\n");
+    }
+
+    #[test]
+    fn test_resolve_conflicts() {
+        let base = "a\nb\nc\nd\n";
+        let ours = "x\nb\nc\ny\n";
+        let theirs = "z\nb\nc\nw\n";
+
+        let merge = Merge3::new(base, ours, theirs);
+        assert_eq!(merge.conflict_count(), 2);
+        let mut buffer = FileBuffer::new(merge.render().content.into_bytes()).unwrap();
+
+        let conflicts = merge
+            .resolve_in_buffer(&mut buffer, 0, Resolution::Theirs)
+            .unwrap();
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].index, 1);
+        assert_eq!(conflicts[0].start_line, 4);
+
+        merge
+            .resolve_in_buffer(&mut buffer, 1, Resolution::Both)
+            .unwrap();
+        assert_eq!(buffer.content, b"z\nb\nc\ny\nw\n");
+
+        assert!(merge
+            .resolve_in_buffer(&mut buffer, 2, Resolution::Ours)
+            .unwrap_err()
+            .contains("out of range"));
+        assert!(merge
+            .resolve_in_buffer(&mut buffer, 1, Resolution::Ours)
+            .unwrap_err()
+            .contains("not in the buffer"));
+    }
+
+    #[test]
+    fn test_resolve_in_edited_buffer() {
+        let merge = Merge3::new("a\nb\nc\n", "a\nours\nc\n", "a\ntheirs\nc\n");
+        let rendered = merge.render().content;
+        let mut buffer = FileBuffer::new(rendered.into_bytes()).unwrap();
+
+        // Edits before and after the block, with different length changes
+        buffer.replace_range(0, 1, b"first\nextra").unwrap();
+        let end = buffer.content.len();
+        buffer.replace_range(end - 2, end, b"last\n").unwrap();
+        let conflicts = merge.conflicts_in(&buffer.content);
+        assert_eq!((conflicts[0].start_line, conflicts[0].end_line), (3, 7));
+
+        let anchor = buffer
+            .anchors
+            .create(buffer.content.len() - 2, Gravity::Left);
+        let conflicts = merge
+            .resolve_in_buffer(&mut buffer, 0, Resolution::Theirs)
+            .unwrap();
+        assert!(conflicts.is_empty());
+        assert_eq!(buffer.content, b"first\nextra\ntheirs\nlast\n");
+        assert_eq!(buffer.anchors.get(anchor).unwrap().offset, 22);
+
+        // Undoing the resolution reopens the conflict, which resolves again
+        buffer.undo();
+        let conflicts = merge.conflicts_in(&buffer.content);
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].start_line, 3);
+        merge
+            .resolve_in_buffer(&mut buffer, 0, Resolution::Ours)
+            .unwrap();
+        assert_eq!(buffer.content, b"first\nextra\nours\nlast\n");
+    }
+
+    #[test]
+    fn test_identical_changes_merge_cleanly() {
+        let base = "a\nb\n";
+        let changed = "a\nb\nc";
+
+        let output = Merge3::new(base, changed, changed).render();
+        assert_eq!(output.content, "a\nb\nc");
+        assert!(output.conflicts.is_empty());
+    }
+}
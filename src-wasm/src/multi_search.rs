@@ -0,0 +1,248 @@
+use crate::errors::{ApiError, ErrorCode};
+use crate::file_buffer::SearchMatch;
+use crate::registry::BufferRegistry;
+use regex::RegexBuilder;
+use serde::{Deserialize, Serialize};
+
+/// Where a truncated search stopped; pass it back as `resume` for the next page
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct SearchCursor {
+    pub file_id: u32,
+    pub line: usize,   // 1-based
+    pub column: usize, // Byte index into the line
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct SearchAllOptions {
+    pub case_insensitive: bool,
+    pub literal: bool,                // Match the pattern as plain text
+    pub file_ids: Option<Vec<u32>>,   // Only these buffers; all by default
+    pub max_per_file: Option<usize>,  // Cap per buffer; the rest is skipped
+    pub resume: Option<SearchCursor>, // `next` of the previous page
+}
+
+/// Matches in one buffer
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct FileMatches {
+    pub file_id: u32,
+    pub match_count: usize, // Matches returned for this buffer
+    pub truncated: bool,    // It has more matches than returned
+    pub matches: Vec<SearchMatch>,
+}
+
+/// One page of a search over several buffers
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SearchAllResult {
+    pub files: Vec<FileMatches>, // Buffers with matches, by file ID
+    pub total_matches: usize,
+    pub files_searched: usize,
+    pub next: Option<SearchCursor>, // Set when max_results cut the page short
+}
+
+/// Search buffers in file ID order, returning at most `max_results` matches
+/// Like search_file, a page stops at max_results; instead of restarting,
+/// the caller continues from `next`, and cancels by not asking for more.
+/// Buffers are read without counting as an access, so a search does not
+/// disturb which ones the memory budget keeps resident; a compressed one is
+/// inflated again by every page that reads it.
+pub fn search_all(
+    registry: &mut BufferRegistry,
+    pattern: &str,
+    options: &SearchAllOptions,
+    max_results: usize,
+) -> Result<SearchAllResult, ApiError> {
+    if max_results == 0 {
+        return Err(ApiError::invalid_argument("max_results must be at least 1"));
+    }
+    let pattern = if options.literal {
+        regex::escape(pattern)
+    } else {
+        pattern.to_string()
+    };
+    let re = RegexBuilder::new(&pattern)
+        .case_insensitive(options.case_insensitive)
+        .build()
+        .map_err(|e| ApiError::new(ErrorCode::Regex, format!("Invalid regex: {}", e)))?;
+
+    let mut ids = match &options.file_ids {
+        Some(ids) => ids.clone(),
+        None => registry.ids(),
+    };
+    ids.sort_unstable();
+    ids.dedup();
+    if let Some(cursor) = options.resume {
+        ids.retain(|&id| id >= cursor.file_id);
+    }
+
+    let mut result = SearchAllResult {
+        files: Vec::new(),
+        total_matches: 0,
+        files_searched: 0,
+        next: None,
+    };
+
+    for file_id in ids {
+        if result.total_matches == max_results {
+            // Full page, ended by per-file caps: continue with this buffer
+            result.next = Some(SearchCursor {
+                file_id,
+                line: 1,
+                column: 0,
+            });
+            break;
+        }
+
+        let from = match options.resume {
+            Some(cursor) if cursor.file_id == file_id => (cursor.line.max(1), cursor.column),
+            _ => (1, 0),
+        };
+        let remaining = max_results - result.total_matches;
+        // The per-file cap wins a tie: the rest of the buffer is skipped
+        let per_file_cap = options.max_per_file.filter(|&max| max <= remaining);
+        let limit = per_file_cap.unwrap_or(remaining);
+
+        let found = registry.inspect(&file_id, |buffer| buffer.search_regex(&re, from, limit));
+        let (matches, rest) = match found {
+            Ok(found) => found,
+            // Closed, or an unknown ID in file_ids
+            Err(e) if e.code == ErrorCode::NotFound => continue,
+            Err(e) => return Err(e),
+        };
+        result.files_searched += 1;
+        result.total_matches += matches.len();
+
+        if !matches.is_empty() {
+            result.files.push(FileMatches {
+                file_id,
+                match_count: matches.len(),
+                truncated: rest.is_some(),
+                matches,
+            });
+        }
+
+        if let Some((line, column)) = rest {
+            if per_file_cap.is_none() {
+                result.next = Some(SearchCursor {
+                    file_id,
+                    line,
+                    column,
+                });
+                break;
+            }
+        }
+    }
+
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file_buffer::FileBuffer;
+
+    fn registry(contents: &[&str]) -> BufferRegistry {
+        let mut registry = BufferRegistry::default();
+        for (i, content) in contents.iter().enumerate() {
+            let buffer = FileBuffer::new(content.as_bytes().to_vec()).unwrap();
+            registry.insert(i as u32 + 1, buffer);
+        }
+        registry
+    }
+
+    #[test]
+    fn test_groups_by_file_with_per_file_cap() {
+        let mut registry = registry(&["id=1\nid=2 id=3\n", "nothing\n", "id=4\n"]);
+        let options = SearchAllOptions {
+            max_per_file: Some(2),
+            ..Default::default()
+        };
+
+        let result = search_all(&mut registry, r"id=\d", &options, 100).unwrap();
+        assert_eq!(result.files_searched, 3);
+        assert_eq!(result.total_matches, 3);
+        assert_eq!(result.next, None);
+
+        let files: Vec<(u32, usize, bool)> = result
+            .files
+            .iter()
+            .map(|f| (f.file_id, f.match_count, f.truncated))
+            .collect();
+        assert_eq!(files, [(1, 2, true), (3, 1, false)]);
+        assert_eq!(result.files[0].matches[1].column, 0);
+        assert_eq!(result.files[0].matches[1].line, 2);
+    }
+
+    #[test]
+    fn test_pages_resume_where_previous_stopped() {
+        let mut registry = registry(&["a a\na\n", "b\n", "a\na a\n"]);
+        let mut options = SearchAllOptions::default();
+        let mut pages = Vec::new();
+
+        loop {
+            let page = search_all(&mut registry, "a", &options, 2).unwrap();
+            pages.push(
+                page.files
+                    .iter()
+                    .flat_map(|f| f.matches.iter().map(move |m| (f.file_id, m.line, m.column)))
+                    .collect::<Vec<_>>(),
+            );
+            match page.next {
+                Some(cursor) => options.resume = Some(cursor),
+                None => break,
+            }
+        }
+
+        assert_eq!(
+            pages,
+            [
+                vec![(1, 1, 0), (1, 1, 2)],
+                vec![(1, 2, 0), (3, 1, 0)],
+                vec![(3, 2, 0), (3, 2, 2)],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_per_file_cap_filling_the_page() {
+        let mut registry = registry(&["x x x\n", "x\n", "none\n"]);
+        let mut options = SearchAllOptions {
+            max_per_file: Some(2),
+            ..Default::default()
+        };
+
+        // The cap truncates buffer 1; the next page starts at buffer 2
+        let page = search_all(&mut registry, "x", &options, 2).unwrap();
+        assert_eq!(page.total_matches, 2);
+        assert!(page.files[0].truncated);
+        let next = page.next.unwrap();
+        assert_eq!((next.file_id, next.line, next.column), (2, 1, 0));
+
+        options.resume = Some(next);
+        let page = search_all(&mut registry, "x", &options, 2).unwrap();
+        assert_eq!(page.total_matches, 1);
+        assert_eq!(page.next, None);
+
+        let error = search_all(&mut registry, "x", &options, 0).unwrap_err();
+        assert_eq!(error.code, ErrorCode::InvalidArgument);
+    }
+
+    #[test]
+    fn test_literal_case_insensitive_and_compressed_buffers() {
+        let filler = "filler line\n".repeat(200);
+        let mut registry = registry(&[&format!("Price: $5.00 (approx)\n{}", filler), &filler]);
+        registry.compress_inactive(0);
+
+        let options = SearchAllOptions {
+            case_insensitive: true,
+            literal: true,
+            ..Default::default()
+        };
+        let result = search_all(&mut registry, "PRICE: $5.00 (", &options, 10).unwrap();
+        assert_eq!(result.total_matches, 1);
+        assert!(registry.stats().buffers.iter().all(|m| m.compressed));
+
+        let error = search_all(&mut registry, "(", &SearchAllOptions::default(), 10).unwrap_err();
+        assert_eq!(error.code, ErrorCode::Regex);
+    }
+}
@@ -0,0 +1,268 @@
+use serde::{Deserialize, Serialize};
+
+/// Kind of an outline symbol
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SymbolKind {
+    Function,
+    Method,
+    Struct,
+    Interface,
+    Type,
+    Heading,
+    Key,
+}
+
+/// A symbol in the document outline (lines are 1-based, inclusive)
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Symbol {
+    pub name: String,
+    pub kind: SymbolKind,
+    pub start_line: usize,
+    pub end_line: usize,
+    pub children: Vec<Symbol>,
+}
+
+/// One level of the breadcrumb trail at a line
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Breadcrumb {
+    pub name: String,
+    pub kind: SymbolKind,
+    pub start_line: usize,
+    pub end_line: usize,
+}
+
+/// Outline of a buffer for one language; cached on the buffer until the
+/// next edit
+pub struct Outline {
+    pub language: String,
+    pub symbols: Vec<Symbol>,
+}
+
+impl Outline {
+    /// Parse `content` with the tree-sitter grammar for `language`
+    pub fn build(content: &[u8], language: &str) -> Result<Self, String> {
+        Ok(Outline {
+            language: language.to_string(),
+            symbols: grammar::outline(content, language)?,
+        })
+    }
+
+    /// Approximate heap bytes held by the symbols
+    pub fn heap_size(&self) -> usize {
+        fn size(symbols: &[Symbol]) -> usize {
+            symbols
+                .iter()
+                .map(|s| std::mem::size_of::<Symbol>() + s.name.len() + size(&s.children))
+                .sum()
+        }
+        size(&self.symbols)
+    }
+
+    /// Chain of symbols enclosing `line`, outermost first
+    pub fn breadcrumbs(&self, line: usize) -> Vec<Breadcrumb> {
+        let mut trail = Vec::new();
+        let mut level = &self.symbols;
+
+        while let Some(symbol) = level
+            .iter()
+            .find(|s| s.start_line <= line && line <= s.end_line)
+        {
+            trail.push(Breadcrumb {
+                name: symbol.name.clone(),
+                kind: symbol.kind,
+                start_line: symbol.start_line,
+                end_line: symbol.end_line,
+            });
+            level = &symbol.children;
+        }
+
+        trail
+    }
+}
+
+/// Languages with a bundled grammar
+pub fn supported_languages() -> &'static [&'static str] {
+    grammar::LANGUAGES
+}
+
+#[cfg(feature = "outline")]
+mod grammar {
+    use super::{Symbol, SymbolKind};
+    use tree_sitter::{Language, Node, Parser};
+
+    pub const LANGUAGES: &[&str] = &["json", "yaml", "markdown", "go"];
+
+    fn language_for(name: &str) -> Option<Language> {
+        match name.to_ascii_lowercase().as_str() {
+            "json" | "jsonc" => Some(tree_sitter_json::LANGUAGE.into()),
+            "yaml" | "yml" => Some(tree_sitter_yaml::LANGUAGE.into()),
+            "markdown" | "md" => Some(tree_sitter_md::LANGUAGE.into()),
+            "go" => Some(tree_sitter_go::LANGUAGE.into()),
+            _ => None,
+        }
+    }
+
+    pub fn outline(content: &[u8], language: &str) -> Result<Vec<Symbol>, String> {
+        let grammar = language_for(language)
+            .ok_or_else(|| format!("No outline grammar for language '{}'", language))?;
+
+        let mut parser = Parser::new();
+        parser
+            .set_language(&grammar)
+            .map_err(|e| format!("Failed to load grammar: {}", e))?;
+        let tree = parser
+            .parse(content, None)
+            .ok_or_else(|| "Failed to parse document".to_string())?;
+
+        let mut symbols = Vec::new();
+        collect(tree.root_node(), content, &mut symbols);
+        Ok(symbols)
+    }
+
+    /// Walk the tree, nesting symbols under the nearest enclosing symbol
+    fn collect(node: Node, source: &[u8], out: &mut Vec<Symbol>) {
+        let mut cursor = node.walk();
+        for child in node.named_children(&mut cursor) {
+            match classify(child, source) {
+                Some((kind, name)) => {
+                    let mut symbol = Symbol {
+                        name,
+                        kind,
+                        start_line: child.start_position().row + 1,
+                        end_line: end_line(child),
+                        children: Vec::new(),
+                    };
+                    collect(child, source, &mut symbol.children);
+                    out.push(symbol);
+                }
+                None => collect(child, source, out),
+            }
+        }
+    }
+
+    /// Last line a node covers; a node ending at column 0 stops on the
+    /// previous line
+    fn end_line(node: Node) -> usize {
+        let end = node.end_position();
+        if end.column == 0 && end.row > node.start_position().row {
+            end.row
+        } else {
+            end.row + 1
+        }
+    }
+
+    fn text(node: Node, source: &[u8]) -> String {
+        String::from_utf8_lossy(&source[node.byte_range()])
+            .trim()
+            .to_string()
+    }
+
+    fn field_text(node: Node, field: &str, source: &[u8]) -> Option<String> {
+        node.child_by_field_name(field).map(|n| text(n, source))
+    }
+
+    /// Decide whether a node is an outline symbol, and its name
+    fn classify(node: Node, source: &[u8]) -> Option<(SymbolKind, String)> {
+        match node.kind() {
+            // JSON / YAML keys
+            "pair" | "block_mapping_pair" | "flow_pair" => {
+                let key = field_text(node, "key", source)?;
+                Some((SymbolKind::Key, key.trim_matches(['"', '\'']).to_string()))
+            }
+            // Markdown: a section spans its heading and everything below it
+            "section" => {
+                let mut cursor = node.walk();
+                let heading = node
+                    .named_children(&mut cursor)
+                    .find(|c| matches!(c.kind(), "atx_heading" | "setext_heading"))?;
+                let name = field_text(heading, "heading_content", source)
+                    .unwrap_or_else(|| text(heading, source));
+                Some((
+                    SymbolKind::Heading,
+                    name.trim_matches('#').trim().to_string(),
+                ))
+            }
+            // Go
+            "function_declaration" => {
+                Some((SymbolKind::Function, field_text(node, "name", source)?))
+            }
+            "method_declaration" => Some((SymbolKind::Method, field_text(node, "name", source)?)),
+            "type_spec" | "type_alias" => {
+                let kind = match node.child_by_field_name("type").map(|t| t.kind()) {
+                    Some("struct_type") => SymbolKind::Struct,
+                    Some("interface_type") => SymbolKind::Interface,
+                    _ => SymbolKind::Type,
+                };
+                Some((kind, field_text(node, "name", source)?))
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(not(feature = "outline"))]
+mod grammar {
+    use super::Symbol;
+
+    pub const LANGUAGES: &[&str] = &[];
+
+    pub fn outline(_content: &[u8], _language: &str) -> Result<Vec<Symbol>, String> {
+        Err("Outline support is not compiled in (enable the \"outline\" feature)".to_string())
+    }
+}
+
+#[cfg(all(test, feature = "outline"))]
+mod tests {
+    use super::*;
+
+    fn names(symbols: &[Symbol]) -> Vec<&str> {
+        symbols.iter().map(|s| s.name.as_str()).collect()
+    }
+
+    #[test]
+    fn test_json_keys() {
+        let outline =
+            Outline::build(br#"{"a": 1, "b": {"c": [1, 2], "d": null}}"#, "json").unwrap();
+
+        assert_eq!(names(&outline.symbols), vec!["a", "b"]);
+        assert_eq!(names(&outline.symbols[1].children), vec!["c", "d"]);
+    }
+
+    #[test]
+    fn test_markdown_headings() {
+        let doc = b"# Title\n\nIntro\n\n## Install\n\ntext\n\n## Usage\n\nmore\n";
+        let outline = Outline::build(doc, "markdown").unwrap();
+
+        assert_eq!(names(&outline.symbols), vec!["Title"]);
+        let sections = &outline.symbols[0].children;
+        assert_eq!(names(sections), vec!["Install", "Usage"]);
+        assert_eq!((sections[0].start_line, sections[0].end_line), (5, 8));
+
+        let trail = outline.breadcrumbs(11);
+        assert_eq!(trail.len(), 2);
+        assert_eq!(trail[1].name, "Usage");
+    }
+
+    #[test]
+    fn test_go_symbols() {
+        let source = b"package main\n\ntype Server struct {\n\tport int\n}\n\nfunc (s *Server) Run() {\n}\n\nfunc main() {\n}\n";
+        let outline = Outline::build(source, "go").unwrap();
+
+        let kinds: Vec<SymbolKind> = outline.symbols.iter().map(|s| s.kind).collect();
+        assert_eq!(names(&outline.symbols), vec!["Server", "Run", "main"]);
+        assert_eq!(
+            kinds,
+            vec![SymbolKind::Struct, SymbolKind::Method, SymbolKind::Function]
+        );
+    }
+
+    #[test]
+    fn test_yaml_keys_and_unknown_language() {
+        let outline = Outline::build(b"server:\n  port: 80\n  host: x\nname: y\n", "yaml").unwrap();
+        assert_eq!(names(&outline.symbols), vec!["server", "name"]);
+        assert_eq!(names(&outline.symbols[0].children), vec!["port", "host"]);
+
+        assert!(Outline::build(b"", "cobol").is_err());
+    }
+}
@@ -0,0 +1,170 @@
+use crate::file_buffer::{search_text, FileBuffer, SearchMatch};
+use rayon::prelude::*;
+use regex::Regex;
+
+/// Smaller buffers are scanned on the calling thread; below this the
+/// thread handoff costs more than it saves
+pub const MIN_PARALLEL_BYTES: usize = 4 * 1024 * 1024;
+
+/// Bytes per chunk handed to a worker
+const CHUNK_BYTES: usize = 1024 * 1024;
+
+/// Line start offsets, as FileBuffer::index_lines, found in parallel chunks
+pub fn index_lines(content: &[u8]) -> Vec<usize> {
+    index_lines_in_chunks(content, CHUNK_BYTES)
+}
+
+fn index_lines_in_chunks(content: &[u8], chunk_bytes: usize) -> Vec<usize> {
+    // Count first, then fill one exactly sized vector, so the index is never
+    // held twice (per-chunk vectors and the joined copy)
+    let counts: Vec<usize> = content
+        .par_chunks(chunk_bytes)
+        .map(|chunk| chunk.iter().filter(|&&byte| byte == b'\n').count())
+        .collect();
+
+    let mut offsets = vec![0; 1 + counts.iter().sum::<usize>()]; // Line 1 starts at byte 0
+    let mut slots = Vec::with_capacity(counts.len());
+    let mut rest = &mut offsets[1..];
+    for &count in &counts {
+        let (slot, tail) = rest.split_at_mut(count);
+        slots.push(slot);
+        rest = tail;
+    }
+
+    content
+        .par_chunks(chunk_bytes)
+        .zip(slots)
+        .enumerate()
+        .for_each(|(i, (chunk, slot))| {
+            let base = i * chunk_bytes;
+            let starts = chunk
+                .iter()
+                .enumerate()
+                .filter(|(_, &byte)| byte == b'\n')
+                .map(|(j, _)| base + j + 1);
+            for (offset, start) in slot.iter_mut().zip(starts) {
+                *offset = start;
+            }
+        });
+    offsets
+}
+
+/// FileBuffer::search_regex with line-aligned chunks searched in parallel
+/// Results are identical: chunks start after a newline, which never falls
+/// inside a UTF-8 sequence, so decoding chunks separately changes nothing
+pub fn search_regex(
+    buffer: &FileBuffer,
+    re: &Regex,
+    from: (usize, usize),
+    max_results: usize,
+) -> (Vec<SearchMatch>, Option<(usize, usize)>) {
+    search_in_chunks(buffer, re, from, max_results, CHUNK_BYTES)
+}
+
+fn search_in_chunks(
+    buffer: &FileBuffer,
+    re: &Regex,
+    from: (usize, usize),
+    max_results: usize,
+    chunk_bytes: usize,
+) -> (Vec<SearchMatch>, Option<(usize, usize)>) {
+    // (first line, start byte, end byte) of each chunk, whole lines only
+    let line_count = buffer.line_count();
+    let starts = &buffer.line_offsets[..line_count];
+    let mut chunks = Vec::new();
+    let mut line = from.0.max(1);
+    while line <= line_count {
+        let start = starts[line - 1];
+        let next = starts
+            .partition_point(|&offset| offset < start + chunk_bytes)
+            .max(line)
+            + 1;
+        let end = starts
+            .get(next - 1)
+            .map_or(buffer.content.len(), |&offset| offset);
+        chunks.push((line, start, end));
+        line = next;
+    }
+
+    // Search one chunk per thread at a time, in order, so a page that fills
+    // up early does not scan the rest of the buffer
+    let batch = rayon::current_num_threads().max(1);
+    let mut results = Vec::new();
+    for chunks in chunks.chunks(batch) {
+        let remaining = max_results - results.len();
+        let pages: Vec<_> = chunks
+            .par_iter()
+            .map(|&(first_line, start, end)| {
+                let text = String::from_utf8_lossy(&buffer.content[start..end]);
+                search_text(&text, first_line, re, from, remaining)
+            })
+            .collect();
+
+        // Stitch the pages together in order, as one scan would have stopped
+        for (mut matches, rest) in pages {
+            let remaining = max_results - results.len();
+            if matches.len() > remaining {
+                let next = &matches[remaining];
+                let next = (next.line, next.column);
+                matches.truncate(remaining);
+                results.extend(matches);
+                return (results, Some(next));
+            }
+            results.extend(matches);
+            if rest.is_some() {
+                return (results, rest);
+            }
+        }
+    }
+    (results, None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> Vec<u8> {
+        let mut content = Vec::new();
+        for i in 0..500 {
+            content.extend_from_slice(format!("line {} café\r\n", i).as_bytes());
+            if i % 7 == 0 {
+                content.extend_from_slice(b"\n\xff broken \xe9 utf8 line 7\n");
+            }
+        }
+        content.extend_from_slice(b"no newline at end 7");
+        content
+    }
+
+    #[test]
+    fn test_parallel_index_matches_sequential() {
+        let content = sample();
+        let expected = FileBuffer::new(content.clone()).unwrap().line_offsets;
+        for chunk_bytes in [1, 7, 64, 4096, content.len() + 1] {
+            assert_eq!(index_lines_in_chunks(&content, chunk_bytes), expected);
+        }
+    }
+
+    #[test]
+    fn test_parallel_search_matches_sequential() {
+        let buffer = FileBuffer::new(sample()).unwrap();
+        let re = Regex::new(r"7|é").unwrap();
+        let sequential = |from, max| {
+            let text = String::from_utf8_lossy(&buffer.content);
+            search_text(&text, 1, &re, from, max)
+        };
+
+        for (from, max) in [
+            ((1, 0), 10_000),
+            ((1, 0), 37),
+            ((250, 3), 100),
+            ((9999, 0), 5),
+        ] {
+            let expected = sequential(from, max);
+            for chunk_bytes in [1, 50, 1000, 1 << 20] {
+                let actual = search_in_chunks(&buffer, &re, from, max, chunk_bytes);
+                assert_eq!(actual.0, expected.0);
+                assert_eq!(actual.1, expected.1);
+            }
+        }
+    }
+}
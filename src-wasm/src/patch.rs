@@ -0,0 +1,420 @@
+use serde::{Deserialize, Serialize};
+use similar::TextDiff;
+
+/// Kind of a line inside a hunk
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LineKind {
+    Context,
+    Remove,
+    Add,
+}
+
+/// A single hunk line, stored without its line terminator
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct HunkLine {
+    pub kind: LineKind,
+    pub text: String,
+    pub no_newline: bool, // Followed by "\ No newline at end of file"
+}
+
+/// One "@@ -a,b +c,d @@" section of a patch
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Hunk {
+    pub old_start: usize,
+    pub old_len: usize,
+    pub new_start: usize,
+    pub new_len: usize,
+    pub lines: Vec<HunkLine>,
+}
+
+/// All hunks for one file of a (possibly multi-file) patch
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct FilePatch {
+    pub old_path: String,
+    pub new_path: String,
+    pub hunks: Vec<Hunk>,
+}
+
+/// Outcome of applying a single hunk
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct HunkResult {
+    pub index: usize,
+    pub applied: bool,
+    pub line: usize,   // 1-based line where the hunk was applied (0 if rejected)
+    pub offset: isize, // Lines away from the position stated in the patch
+    pub fuzz: usize,   // Context lines ignored to make the hunk match
+}
+
+/// Outcome of applying a file patch to one buffer
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct FilePatchResult {
+    pub file_id: u32,
+    pub old_path: String,
+    pub new_path: String,
+    pub applied_hunks: usize,
+    pub failed_hunks: usize,
+    pub hunks: Vec<HunkResult>,
+}
+
+/// Parse a unified diff into per-file patches
+/// Git extended headers ("diff --git", "index", ...) are skipped
+pub fn parse_patch(text: &str) -> Result<Vec<FilePatch>, String> {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut patches: Vec<FilePatch> = Vec::new();
+    let mut i = 0;
+
+    while i < lines.len() {
+        let line = lines[i];
+
+        if line.starts_with("--- ") && i + 1 < lines.len() && lines[i + 1].starts_with("+++ ") {
+            patches.push(FilePatch {
+                old_path: parse_path(&line[4..]),
+                new_path: parse_path(&lines[i + 1][4..]),
+                hunks: Vec::new(),
+            });
+            i += 2;
+        } else if line.starts_with("@@ ") {
+            let patch = patches
+                .last_mut()
+                .ok_or_else(|| format!("Line {}: hunk without file header", i + 1))?;
+            let (hunk, consumed) = parse_hunk(&lines[i..], i + 1)?;
+            patch.hunks.push(hunk);
+            i += consumed;
+        } else {
+            i += 1;
+        }
+    }
+
+    if patches.is_empty() {
+        return Err("No file headers found in patch".to_string());
+    }
+
+    Ok(patches)
+}
+
+/// Strip the timestamp some tools append after a tab
+fn parse_path(header: &str) -> String {
+    header
+        .split('\t')
+        .next()
+        .unwrap_or("")
+        .trim_end()
+        .to_string()
+}
+
+/// Parse "-a,b" / "+c,d" ranges; the length defaults to 1 when omitted
+fn parse_range(range: &str) -> Option<(usize, usize)> {
+    match range.split_once(',') {
+        Some((start, len)) => Some((start.parse().ok()?, len.parse().ok()?)),
+        None => Some((range.parse().ok()?, 1)),
+    }
+}
+
+/// Parse one hunk starting at its "@@" header
+/// Returns the hunk and the number of patch lines it spans
+fn parse_hunk(lines: &[&str], line_num: usize) -> Result<(Hunk, usize), String> {
+    let invalid = || format!("Line {}: invalid hunk header '{}'", line_num, lines[0]);
+
+    let mut parts = lines[0].split_whitespace().skip(1);
+    let old = parts
+        .next()
+        .and_then(|r| r.strip_prefix('-'))
+        .and_then(parse_range)
+        .ok_or_else(invalid)?;
+    let new = parts
+        .next()
+        .and_then(|r| r.strip_prefix('+'))
+        .and_then(parse_range)
+        .ok_or_else(invalid)?;
+
+    let mut hunk = Hunk {
+        old_start: old.0,
+        old_len: old.1,
+        new_start: new.0,
+        new_len: new.1,
+        lines: Vec::new(),
+    };
+
+    let (mut old_seen, mut new_seen) = (0, 0);
+    let mut i = 1;
+
+    while i < lines.len() && (old_seen < hunk.old_len || new_seen < hunk.new_len) {
+        let line = lines[i];
+        let (kind, text) = match line.chars().next() {
+            Some(' ') => (LineKind::Context, &line[1..]),
+            Some('-') => (LineKind::Remove, &line[1..]),
+            Some('+') => (LineKind::Add, &line[1..]),
+            Some('\\') => {
+                mark_no_newline(&mut hunk);
+                i += 1;
+                continue;
+            }
+            // Some editors strip the single space from blank context lines
+            None => (LineKind::Context, ""),
+            _ => break,
+        };
+
+        match kind {
+            LineKind::Context => {
+                old_seen += 1;
+                new_seen += 1;
+            }
+            LineKind::Remove => old_seen += 1,
+            LineKind::Add => new_seen += 1,
+        }
+
+        hunk.lines.push(HunkLine {
+            kind,
+            text: text.to_string(),
+            no_newline: false,
+        });
+        i += 1;
+    }
+
+    if old_seen != hunk.old_len || new_seen != hunk.new_len {
+        return Err(format!(
+            "Line {}: hunk expects -{} +{} lines but has -{} +{}",
+            line_num, hunk.old_len, hunk.new_len, old_seen, new_seen
+        ));
+    }
+
+    // A trailing "\ No newline" belongs to the last line of the hunk
+    if i < lines.len() && lines[i].starts_with('\\') {
+        mark_no_newline(&mut hunk);
+        i += 1;
+    }
+
+    Ok((hunk, i))
+}
+
+fn mark_no_newline(hunk: &mut Hunk) {
+    if let Some(last) = hunk.lines.last_mut() {
+        last.no_newline = true;
+    }
+}
+
+/// Apply a file patch to text
+/// Each hunk is searched for near its stated position, first exactly and then
+/// ignoring up to `max_fuzz` leading/trailing context lines. Hunks that cannot
+/// be placed are reported as failed and leave the text untouched.
+pub fn apply_patch(content: &str, patch: &FilePatch, max_fuzz: usize) -> (String, Vec<HunkResult>) {
+    let eol = if content.contains("\r\n") {
+        "\r\n"
+    } else {
+        "\n"
+    };
+    let mut lines: Vec<String> = content.split_inclusive('\n').map(str::to_string).collect();
+    let mut results = Vec::with_capacity(patch.hunks.len());
+
+    // Net lines added by applied hunks, and drift of the last applied hunk
+    let mut line_delta: isize = 0;
+    let mut last_offset: isize = 0;
+    // Hunks apply in order, so never match above the previous hunk
+    let mut min_pos = 0;
+
+    for (index, hunk) in patch.hunks.iter().enumerate() {
+        // A pure insertion's start is the line *after which* it goes
+        let stated = if hunk.old_len == 0 {
+            hunk.old_start
+        } else {
+            hunk.old_start.saturating_sub(1)
+        } as isize
+            + line_delta;
+
+        let found = (0..=max_fuzz).find_map(|fuzz| {
+            let (front, back) = trim_context(hunk, fuzz);
+            if fuzz > 0 && front == 0 && back == 0 {
+                return None;
+            }
+
+            let old: Vec<&HunkLine> = hunk.lines[front..hunk.lines.len() - back]
+                .iter()
+                .filter(|l| l.kind != LineKind::Add)
+                .collect();
+            let expected = stated + last_offset + front as isize;
+
+            find_hunk(&lines, &old, expected, min_pos).map(|pos| (pos, front, back, fuzz))
+        });
+
+        match found {
+            Some((pos, front, back, fuzz)) => {
+                let kept = &hunk.lines[front..hunk.lines.len() - back];
+                let old_count = kept.iter().filter(|l| l.kind != LineKind::Add).count();
+                let replacement: Vec<String> = kept
+                    .iter()
+                    .filter(|l| l.kind != LineKind::Remove)
+                    .map(|l| {
+                        if l.no_newline {
+                            l.text.clone()
+                        } else {
+                            format!("{}{}", l.text, eol)
+                        }
+                    })
+                    .collect();
+                let new_count = replacement.len();
+
+                lines.splice(pos..pos + old_count, replacement);
+
+                let offset = pos as isize - front as isize - stated;
+                results.push(HunkResult {
+                    index,
+                    applied: true,
+                    line: pos + 1,
+                    offset,
+                    fuzz,
+                });
+
+                line_delta += new_count as isize - old_count as isize;
+                last_offset = offset;
+                min_pos = pos + new_count;
+            }
+            None => results.push(HunkResult {
+                index,
+                applied: false,
+                line: 0,
+                offset: 0,
+                fuzz: 0,
+            }),
+        }
+    }
+
+    (lines.concat(), results)
+}
+
+/// Number of leading and trailing context lines to ignore at a fuzz level
+fn trim_context(hunk: &Hunk, fuzz: usize) -> (usize, usize) {
+    let leading = hunk
+        .lines
+        .iter()
+        .take_while(|l| l.kind == LineKind::Context)
+        .count();
+    let trailing = hunk
+        .lines
+        .iter()
+        .rev()
+        .take_while(|l| l.kind == LineKind::Context)
+        .count();
+
+    // Never trim a hunk down to nothing
+    if leading == hunk.lines.len() {
+        return (0, 0);
+    }
+
+    (fuzz.min(leading), fuzz.min(trailing))
+}
+
+/// Find where `old` matches in `lines`, searching outward from `expected`
+fn find_hunk(
+    lines: &[String],
+    old: &[&HunkLine],
+    expected: isize,
+    min_pos: usize,
+) -> Option<usize> {
+    let max_pos = lines.len().checked_sub(old.len())?;
+    if min_pos > max_pos {
+        return None;
+    }
+    let expected = expected.clamp(min_pos as isize, max_pos as isize) as usize;
+
+    let matches_at = |pos: usize| {
+        old.iter()
+            .zip(&lines[pos..])
+            .all(|(hunk_line, line)| line.trim_end_matches(['\n', '\r']) == hunk_line.text)
+    };
+
+    let span = (expected - min_pos).max(max_pos - expected);
+    (0..=span).find_map(|distance| {
+        if distance <= max_pos - expected && matches_at(expected + distance) {
+            return Some(expected + distance);
+        }
+        if distance > 0 && distance <= expected - min_pos && matches_at(expected - distance) {
+            return Some(expected - distance);
+        }
+        None
+    })
+}
+
+/// Generate a unified diff between two texts
+pub fn unified_diff(
+    old: &str,
+    new: &str,
+    old_label: &str,
+    new_label: &str,
+    context: usize,
+) -> String {
+    TextDiff::from_lines(old, new)
+        .unified_diff()
+        .context_radius(context)
+        .header(old_label, new_label)
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_roundtrip_diff_and_apply() {
+        let old = "one\ntwo\nthree\nfour\nfive\n";
+        let new = "one\nTWO\nthree\nfour\nfive\nsix\n";
+
+        let diff = unified_diff(old, new, "a/file.txt", "b/file.txt", 3);
+        let patches = parse_patch(&diff).unwrap();
+        assert_eq!(patches.len(), 1);
+        assert_eq!(patches[0].old_path, "a/file.txt");
+
+        let (patched, results) = apply_patch(old, &patches[0], 0);
+        assert_eq!(patched, new);
+        assert!(results.iter().all(|r| r.applied && r.offset == 0));
+    }
+
+    #[test]
+    fn test_apply_with_offset() {
+        let patch = "--- a/f\n+++ b/f\n@@ -1,3 +1,3 @@\n a\n-b\n+B\n c\n";
+        let patches = parse_patch(patch).unwrap();
+
+        let (patched, results) = apply_patch("x\ny\na\nb\nc\n", &patches[0], 0);
+        assert_eq!(patched, "x\ny\na\nB\nc\n");
+        assert_eq!(results[0].offset, 2);
+        assert_eq!(results[0].line, 3);
+    }
+
+    #[test]
+    fn test_apply_with_fuzz() {
+        let patch = "--- a/f\n+++ b/f\n@@ -1,3 +1,3 @@\n a\n-b\n+B\n c\n";
+        let patches = parse_patch(patch).unwrap();
+
+        // Leading context no longer matches
+        let (_, results) = apply_patch("z\nb\nc\n", &patches[0], 0);
+        assert!(!results[0].applied);
+
+        let (patched, results) = apply_patch("z\nb\nc\n", &patches[0], 1);
+        assert_eq!(patched, "z\nB\nc\n");
+        assert_eq!(results[0].fuzz, 1);
+    }
+
+    #[test]
+    fn test_no_newline_at_end_of_file() {
+        let diff = unified_diff("a\nb", "a\nc", "old", "new", 3);
+        assert!(diff.contains("\\ No newline at end of file"));
+
+        let patches = parse_patch(&diff).unwrap();
+        let (patched, _) = apply_patch("a\nb", &patches[0], 0);
+        assert_eq!(patched, "a\nc");
+    }
+
+    #[test]
+    fn test_multi_file_patch() {
+        let patch =
+            "diff --git a/x b/x\nindex 1..2 100644\n--- a/x\n+++ b/x\n@@ -1 +1 @@\n-1\n+2\n\
+                     --- a/y\t2024-01-01\n+++ b/y\n@@ -0,0 +1 @@\n+new\n";
+        let patches = parse_patch(patch).unwrap();
+
+        assert_eq!(patches.len(), 2);
+        assert_eq!(patches[1].old_path, "a/y");
+
+        let (patched, _) = apply_patch("", &patches[1], 0);
+        assert_eq!(patched, "new\n");
+    }
+}
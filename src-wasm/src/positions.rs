@@ -0,0 +1,290 @@
+use crate::file_buffer::FileBuffer;
+use crate::segments::is_char_start;
+use serde::{Deserialize, Serialize};
+
+/// One location in a buffer expressed in every coordinate system in use:
+/// the WASM side works in bytes and 1-based lines, CodeMirror in UTF-16
+/// offsets. Columns are 0-based.
+/// Characters are counted by their lead bytes, which matches the decoded
+/// text for valid UTF-8. In invalid UTF-8 an invalid lead byte counts as one
+/// character (one UTF-16 unit) and a stray continuation byte counts with the
+/// character before it, so positions can differ from text decoded with
+/// U+FFFD replacement characters.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,       // Characters from the line start
+    pub utf16_column: usize, // UTF-16 units from the line start
+    pub byte_offset: usize,
+    pub char_offset: usize,
+    pub utf16_offset: usize,
+}
+
+/// Character and UTF-16 unit counts before each line start
+/// Built on demand and dropped on edit, like the other derived indexes
+pub struct CharIndex {
+    pub chars: Vec<u32>, // Characters before each entry of line_offsets
+    pub utf16: Vec<u32>, // UTF-16 units before each entry of line_offsets
+    pub total_chars: usize,
+    pub total_utf16: usize,
+}
+
+/// UTF-16 units taken by the character starting with `lead`
+/// Only 4-byte sequences (lead 0xF0..=0xF4) need a surrogate pair; bytes
+/// above 0xF4 never start a valid character
+fn utf16_width(lead: u8) -> usize {
+    if (0xF0..=0xF4).contains(&lead) {
+        2
+    } else {
+        1
+    }
+}
+
+/// Count characters and UTF-16 units in a byte slice
+fn count_units(bytes: &[u8]) -> (usize, usize) {
+    bytes
+        .iter()
+        .filter(|&&b| is_char_start(b))
+        .fold((0, 0), |(chars, units), &b| {
+            (chars + 1, units + utf16_width(b))
+        })
+}
+
+/// Byte index in `bytes` after skipping `count` characters (or UTF-16 units)
+/// A UTF-16 count ending inside a surrogate pair stops before that character
+/// Returns None when `bytes` is too short
+fn advance(bytes: &[u8], count: usize, utf16: bool) -> Option<usize> {
+    let mut remaining = count;
+    let mut i = 0;
+
+    while remaining > 0 {
+        let &lead = bytes.get(i)?;
+        let width = if utf16 { utf16_width(lead) } else { 1 };
+        if width > remaining {
+            break;
+        }
+        remaining -= width;
+
+        i += 1;
+        while i < bytes.len() && !is_char_start(bytes[i]) {
+            i += 1;
+        }
+    }
+
+    Some(i)
+}
+
+impl CharIndex {
+    pub fn build(content: &[u8], line_offsets: &[usize]) -> Self {
+        let mut chars = Vec::with_capacity(line_offsets.len());
+        let mut utf16 = Vec::with_capacity(line_offsets.len());
+        let (mut total_chars, mut total_utf16) = (0, 0);
+
+        for (i, &start) in line_offsets.iter().enumerate() {
+            chars.push(total_chars as u32);
+            utf16.push(total_utf16 as u32);
+
+            let end = line_offsets.get(i + 1).map_or(content.len(), |&o| o);
+            let (c, u) = count_units(&content[start..end]);
+            total_chars += c;
+            total_utf16 += u;
+        }
+
+        CharIndex {
+            chars,
+            utf16,
+            total_chars,
+            total_utf16,
+        }
+    }
+
+    /// Approximate heap bytes held by the index
+    pub fn heap_size(&self) -> usize {
+        std::mem::size_of_val(self.chars.as_slice()) + std::mem::size_of_val(self.utf16.as_slice())
+    }
+
+    /// Start and end (newline included) of line index `i` (0-based)
+    fn line_bounds(buffer: &FileBuffer, i: usize) -> (usize, usize) {
+        let start = buffer.line_offsets[i];
+        let end = buffer
+            .line_offsets
+            .get(i + 1)
+            .map_or(buffer.content.len(), |&o| o);
+        (start, end)
+    }
+
+    /// Position at a byte offset; offsets inside a character resolve to it
+    pub fn at_byte(&self, buffer: &FileBuffer, byte_offset: usize) -> Result<Position, String> {
+        if byte_offset > buffer.content.len() {
+            return Err(format!(
+                "Byte offset {} out of range (file has {} bytes)",
+                byte_offset,
+                buffer.content.len()
+            ));
+        }
+
+        let mut byte_offset = byte_offset;
+        while byte_offset < buffer.content.len()
+            && byte_offset > 0
+            && !is_char_start(buffer.content[byte_offset])
+        {
+            byte_offset -= 1;
+        }
+
+        let i = buffer
+            .line_offsets
+            .partition_point(|&o| o <= byte_offset)
+            .max(1)
+            - 1;
+        let (start, _) = Self::line_bounds(buffer, i);
+        let (column, utf16_column) = count_units(&buffer.content[start..byte_offset]);
+
+        Ok(Position {
+            line: i + 1,
+            column,
+            utf16_column,
+            byte_offset,
+            char_offset: self.chars[i] as usize + column,
+            utf16_offset: self.utf16[i] as usize + utf16_column,
+        })
+    }
+
+    /// Position at a 1-based line and 0-based character column
+    /// The column may point at the line end but not past it
+    pub fn at_line_column(
+        &self,
+        buffer: &FileBuffer,
+        line: usize,
+        column: usize,
+    ) -> Result<Position, String> {
+        if line == 0 || line > buffer.line_offsets.len() {
+            return Err(format!(
+                "Line {} out of range (file has {} lines)",
+                line,
+                buffer.line_count()
+            ));
+        }
+
+        let (start, end) = Self::line_bounds(buffer, line - 1);
+        let text = &buffer.content[start..end];
+        let text = text.strip_suffix(b"\n").unwrap_or(text);
+        let text = text.strip_suffix(b"\r").unwrap_or(text);
+
+        let offset = advance(text, column, false)
+            .ok_or_else(|| format!("Column {} is past the end of line {}", column, line))?;
+        self.at_byte(buffer, start + offset)
+    }
+
+    /// Position at an absolute character offset
+    pub fn at_char(&self, buffer: &FileBuffer, char_offset: usize) -> Result<Position, String> {
+        if char_offset > self.total_chars {
+            return Err(format!(
+                "Character offset {} out of range (file has {} characters)",
+                char_offset, self.total_chars
+            ));
+        }
+
+        let i = self.chars.partition_point(|&c| c as usize <= char_offset) - 1;
+        self.at_line_offset(buffer, i, char_offset - self.chars[i] as usize, false)
+    }
+
+    /// Position at an absolute UTF-16 offset (CodeMirror's coordinates)
+    /// An offset between the halves of a surrogate pair resolves to the pair
+    pub fn at_utf16(&self, buffer: &FileBuffer, utf16_offset: usize) -> Result<Position, String> {
+        if utf16_offset > self.total_utf16 {
+            return Err(format!(
+                "UTF-16 offset {} out of range (file has {} units)",
+                utf16_offset, self.total_utf16
+            ));
+        }
+
+        let i = self.utf16.partition_point(|&u| u as usize <= utf16_offset) - 1;
+        self.at_line_offset(buffer, i, utf16_offset - self.utf16[i] as usize, true)
+    }
+
+    fn at_line_offset(
+        &self,
+        buffer: &FileBuffer,
+        i: usize,
+        count: usize,
+        utf16: bool,
+    ) -> Result<Position, String> {
+        let (start, end) = Self::line_bounds(buffer, i);
+        let offset = advance(&buffer.content[start..end], count, utf16)
+            .ok_or_else(|| "Offset is past the end of the file".to_string())?;
+        self.at_byte(buffer, start + offset)
+    }
+
+    /// Start of the line containing the byte `percent`% into the file
+    pub fn at_percent(&self, buffer: &FileBuffer, percent: f64) -> Result<Position, String> {
+        if !percent.is_finite() {
+            return Err(format!("Invalid percentage: {}", percent));
+        }
+
+        let byte = (buffer.content.len() as f64 * percent.clamp(0.0, 100.0) / 100.0) as usize;
+        let line = self.at_byte(buffer, byte)?.line;
+        self.at_line_column(buffer, line, 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn buffer(text: &str) -> (FileBuffer, CharIndex) {
+        let buffer = FileBuffer::new(text.as_bytes().to_vec()).unwrap();
+        let index = CharIndex::build(&buffer.content, &buffer.line_offsets);
+        (buffer, index)
+    }
+
+    #[test]
+    fn test_conversions_agree() {
+        let (buffer, index) = buffer("abc\nhé😀x\nlast");
+
+        // "x" on line 2: byte 4+1+2+4, char 4+3, UTF-16 4+4
+        let pos = index.at_byte(&buffer, 11).unwrap();
+        assert_eq!(
+            pos,
+            Position {
+                line: 2,
+                column: 3,
+                utf16_column: 4,
+                byte_offset: 11,
+                char_offset: 7,
+                utf16_offset: 8,
+            }
+        );
+        assert_eq!(index.at_line_column(&buffer, 2, 3).unwrap(), pos);
+        assert_eq!(index.at_char(&buffer, 7).unwrap(), pos);
+        assert_eq!(index.at_utf16(&buffer, 8).unwrap(), pos);
+
+        // Inside the emoji / between its surrogate halves
+        assert_eq!(index.at_byte(&buffer, 9).unwrap().byte_offset, 7);
+        assert_eq!(index.at_utf16(&buffer, 7).unwrap().byte_offset, 7);
+
+        assert_eq!(index.total_chars, 13);
+        assert!(index.at_line_column(&buffer, 1, 4).is_err());
+        assert!(index.at_char(&buffer, 14).is_err());
+    }
+
+    #[test]
+    fn test_invalid_utf8_counts_by_lead_byte() {
+        // Invalid lead 0xFF, stray continuation 0x80, then a valid emoji
+        let content = b"\xff\x80\xf0\x9f\x98\x80".to_vec();
+        let buffer = FileBuffer::new(content).unwrap();
+        let index = CharIndex::build(&buffer.content, &buffer.line_offsets);
+        assert_eq!((index.total_chars, index.total_utf16), (2, 3));
+    }
+
+    #[test]
+    fn test_percent_and_end_of_file() {
+        let (buffer, index) = buffer("0123456789\nabcdefghi\n");
+
+        assert_eq!(index.at_percent(&buffer, 0.0).unwrap().line, 1);
+        assert_eq!(index.at_percent(&buffer, 75.0).unwrap().byte_offset, 11);
+
+        // The offset after a trailing newline is the start of an empty line
+        let end = index.at_byte(&buffer, 21).unwrap();
+        assert_eq!((end.line, end.column, end.char_offset), (3, 0, 21));
+    }
+}
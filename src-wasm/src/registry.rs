@@ -0,0 +1,442 @@
+use crate::errors::{ApiError, ErrorCode};
+use crate::file_buffer::{FileBuffer, FileStats};
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::{Read, Write};
+
+/// Memory use of one buffer
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct BufferMemory {
+    pub file_id: u32,
+    pub size: usize,             // Content size when decompressed
+    pub resident_bytes: usize,   // Content, indexes and undo history currently held
+    pub compressed_bytes: usize, // Deflated content while inactive, else 0
+    pub compressed: bool,
+    pub last_access: u64, // Access counter value; lower is less recently used
+}
+
+/// Memory use of the whole registry
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct MemoryStats {
+    pub file_count: usize,
+    pub total_content_size: usize, // As if every buffer were decompressed
+    pub total_index_size: usize,
+    pub total_cache_size: usize, // Other indexes and undo histories
+    pub total_size: usize,
+    pub resident_size: usize,
+    pub compressed_size: usize,
+    pub budget: Option<usize>,
+    pub buffers: Vec<BufferMemory>,
+}
+
+struct Slot {
+    buffer: FileBuffer,
+    compressed: Option<(Vec<u8>, usize)>, // Deflated content and its original size
+    incompressible: Option<u64>,          // Revision that did not shrink
+    last_access: u64,
+}
+
+impl Slot {
+    fn memory(&self, file_id: u32) -> BufferMemory {
+        let stats = self.buffer.get_stats();
+        let (compressed_bytes, size) = match &self.compressed {
+            Some((data, size)) => (data.len(), *size),
+            None => (0, stats.size),
+        };
+        BufferMemory {
+            file_id,
+            size,
+            resident_bytes: stats.size + stats.index_size + stats.cache_size,
+            compressed_bytes,
+            compressed: self.compressed.is_some(),
+            last_access: self.last_access,
+        }
+    }
+
+    /// Whether compressing could still save memory
+    /// A failed attempt is only retried once the content has changed
+    fn compressible(&self) -> bool {
+        self.compressed.is_none()
+            && self.incompressible != Some(self.buffer.revision)
+            && !self.buffer.content.is_empty()
+    }
+
+    /// Deflate the content; false if that would not save anything
+    fn compress(&mut self) -> bool {
+        if !self.compressible() {
+            return false;
+        }
+
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::fast());
+        let data = encoder
+            .write_all(&self.buffer.content)
+            .and_then(|_| encoder.finish());
+        match data {
+            Ok(data) if data.len() < self.buffer.content.len() => {
+                let content = self.buffer.take_content();
+                self.compressed = Some((data, content.len()));
+                true
+            }
+            _ => {
+                self.incompressible = Some(self.buffer.revision);
+                false
+            }
+        }
+    }
+
+    /// Restore the content; on failure the buffer stays compressed
+    fn decompress(&mut self, file_id: u32) -> Result<(), ApiError> {
+        if let Some((data, size)) = &self.compressed {
+            let content = inflate(file_id, data, *size)?;
+            self.buffer.restore_content(content);
+            self.compressed = None;
+        }
+        Ok(())
+    }
+}
+
+fn inflate(file_id: u32, data: &[u8], size: usize) -> Result<Vec<u8>, ApiError> {
+    let mut content = Vec::with_capacity(size);
+    let result = DeflateDecoder::new(data).read_to_end(&mut content);
+    match result {
+        Ok(_) if content.len() == size => Ok(content),
+        Ok(_) => Err(ApiError::new(
+            ErrorCode::Internal,
+            format!(
+                "File {} decompressed to {} bytes instead of {}",
+                file_id,
+                content.len(),
+                size
+            ),
+        )),
+        Err(e) => Err(ApiError::new(
+            ErrorCode::Internal,
+            format!("File {} could not be decompressed: {}", file_id, e),
+        )),
+    }
+}
+
+fn not_found(file_id: u32) -> ApiError {
+    ApiError::not_found(format!("File {} not found", file_id))
+}
+
+/// Storage for every open buffer, keyed by file ID
+/// When a budget is set, the least recently used buffers are compressed
+/// once resident memory exceeds it and decompressed transparently on the
+/// next access. Spilling is not implemented: deflated content stays in the
+/// WASM heap (nothing is handed to JS for IndexedDB or similar), so if
+/// everything is compressed the budget is simply exceeded.
+#[derive(Default)]
+pub struct BufferRegistry {
+    slots: HashMap<u32, Slot>,
+    budget: Option<usize>,
+    clock: u64,
+}
+
+impl BufferRegistry {
+    /// Resident byte limit; None disables eviction
+    pub fn set_budget(&mut self, budget: Option<usize>) {
+        self.budget = budget;
+        self.enforce_budget(None);
+    }
+
+    pub fn insert(&mut self, file_id: u32, buffer: FileBuffer) {
+        self.clock += 1;
+        self.slots.insert(
+            file_id,
+            Slot {
+                buffer,
+                compressed: None,
+                incompressible: None,
+                last_access: self.clock,
+            },
+        );
+        self.enforce_budget(Some(file_id));
+    }
+
+    /// Drop a buffer; returns whether it existed
+    pub fn remove(&mut self, file_id: &u32) -> bool {
+        self.slots.remove(file_id).is_some()
+    }
+
+    pub fn contains_key(&self, file_id: &u32) -> bool {
+        self.slots.contains_key(file_id)
+    }
+
+    /// Decompress a buffer if needed and mark it as most recently used
+    fn load(&mut self, file_id: u32) -> Result<&mut Slot, ApiError> {
+        let slot = self
+            .slots
+            .get_mut(&file_id)
+            .ok_or_else(|| not_found(file_id))?;
+        let was_compressed = slot.compressed.is_some();
+        slot.decompress(file_id)?;
+        self.clock += 1;
+        slot.last_access = self.clock;
+
+        if was_compressed {
+            self.enforce_budget(Some(file_id));
+        }
+        self.slots
+            .get_mut(&file_id)
+            .ok_or_else(|| not_found(file_id))
+    }
+
+    /// A buffer; NotFound for unknown IDs, Internal if it fails to decompress
+    pub fn get(&mut self, file_id: &u32) -> Result<&FileBuffer, ApiError> {
+        self.load(*file_id).map(|slot| &slot.buffer)
+    }
+
+    /// Read a buffer without counting as an access, e.g. for a search over
+    /// every buffer: a compressed one is inflated for the call only, and its
+    /// deflated copy is kept rather than compressed again afterwards
+    pub fn inspect<R>(
+        &mut self,
+        file_id: &u32,
+        f: impl FnOnce(&FileBuffer) -> R,
+    ) -> Result<R, ApiError> {
+        let slot = self
+            .slots
+            .get_mut(file_id)
+            .ok_or_else(|| not_found(*file_id))?;
+        let Some((data, size)) = &slot.compressed else {
+            return Ok(f(&slot.buffer));
+        };
+
+        slot.buffer.restore_content(inflate(*file_id, data, *size)?);
+        let result = f(&slot.buffer);
+        slot.buffer.take_content();
+        Ok(result)
+    }
+
+    /// IDs of all buffers in ascending order
+    pub fn ids(&self) -> Vec<u32> {
+        let mut ids: Vec<u32> = self.slots.keys().copied().collect();
+        ids.sort_unstable();
+        ids
+    }
+
+    pub fn get_mut(&mut self, file_id: &u32) -> Result<&mut FileBuffer, ApiError> {
+        self.load(*file_id).map(|slot| &mut slot.buffer)
+    }
+
+    fn resident_size(&self) -> usize {
+        self.slots
+            .iter()
+            .map(|(&id, slot)| slot.memory(id).resident_bytes)
+            .sum()
+    }
+
+    /// Compress least recently used buffers until under budget
+    fn enforce_budget(&mut self, keep: Option<u32>) {
+        let Some(budget) = self.budget else {
+            return;
+        };
+
+        let mut resident = self.resident_size();
+        while resident > budget {
+            let candidate = self
+                .slots
+                .iter()
+                .filter(|(&id, slot)| Some(id) != keep && slot.compressible())
+                .min_by_key(|(_, slot)| slot.last_access)
+                .map(|(&id, _)| id);
+            let Some(id) = candidate else {
+                break;
+            };
+
+            let slot = self.slots.get_mut(&id).unwrap();
+            let before = slot.memory(id).resident_bytes;
+            if slot.compress() {
+                resident -= before - slot.memory(id).resident_bytes;
+            }
+        }
+    }
+
+    /// Compress every buffer except the `keep_recent` most recently used
+    /// Returns how many buffers were compressed
+    pub fn compress_inactive(&mut self, keep_recent: usize) -> usize {
+        let mut ids: Vec<(u64, u32)> = self
+            .slots
+            .iter()
+            .map(|(&id, slot)| (slot.last_access, id))
+            .collect();
+        ids.sort_unstable_by(|a, b| b.cmp(a));
+
+        ids.into_iter()
+            .skip(keep_recent)
+            .filter(|(_, id)| self.slots.get_mut(id).unwrap().compress())
+            .count()
+    }
+
+    /// Buffers whose IDs the caller no longer holds, oldest access first
+    pub fn leaked(&self, open_ids: &[u32]) -> Vec<BufferMemory> {
+        let mut leaked: Vec<BufferMemory> = self
+            .slots
+            .iter()
+            .filter(|(id, _)| !open_ids.contains(id))
+            .map(|(&id, slot)| slot.memory(id))
+            .collect();
+        leaked.sort_by_key(|m| m.last_access);
+        leaked
+    }
+
+    pub fn stats(&self) -> MemoryStats {
+        let mut buffers: Vec<BufferMemory> = self
+            .slots
+            .iter()
+            .map(|(&id, slot)| slot.memory(id))
+            .collect();
+        buffers.sort_by_key(|m| m.file_id);
+
+        let total_content_size = buffers.iter().map(|m| m.size).sum();
+        let stats: Vec<FileStats> = self.slots.values().map(|s| s.buffer.get_stats()).collect();
+        let total_index_size = stats.iter().map(|s| s.index_size).sum();
+        let total_cache_size = stats.iter().map(|s| s.cache_size).sum();
+
+        MemoryStats {
+            file_count: buffers.len(),
+            total_content_size,
+            total_index_size,
+            total_cache_size,
+            total_size: total_content_size + total_index_size + total_cache_size,
+            resident_size: buffers.iter().map(|m| m.resident_bytes).sum(),
+            compressed_size: buffers.iter().map(|m| m.compressed_bytes).sum(),
+            budget: self.budget,
+            buffers,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(lines: usize) -> Vec<u8> {
+        (0..lines)
+            .map(|i| format!("line {} of a fairly repetitive log file\n", i))
+            .collect::<String>()
+            .into_bytes()
+    }
+
+    #[test]
+    fn test_budget_compresses_least_recently_used() {
+        let mut registry = BufferRegistry::default();
+        registry.insert(1, FileBuffer::new(text(1000)).unwrap());
+        registry.insert(2, FileBuffer::new(text(1000)).unwrap());
+        registry.insert(3, FileBuffer::new(text(1000)).unwrap());
+        registry.get(&1).unwrap();
+
+        // Room for about two buffers: 2 is the least recently used
+        let one = registry.stats().buffers[0].resident_bytes;
+        registry.set_budget(Some(one * 2 + 64));
+        let stats = registry.stats();
+        let compressed: Vec<u32> = stats
+            .buffers
+            .iter()
+            .filter(|m| m.compressed)
+            .map(|m| m.file_id)
+            .collect();
+        assert_eq!(compressed, [2]);
+        assert!(stats.compressed_size > 0 && stats.compressed_size < one);
+        assert_eq!(stats.total_content_size, text(1000).len() * 3);
+
+        // Access decompresses transparently and evicts the next oldest
+        let buffer = registry.get(&2).unwrap();
+        assert_eq!(buffer.content, text(1000));
+        assert_eq!(buffer.line_count(), 1000);
+        assert!(registry.stats().buffers[2].compressed);
+    }
+
+    #[test]
+    fn test_compressed_buffer_keeps_history_and_anchors() {
+        let mut registry = BufferRegistry::default();
+        let mut buffer = FileBuffer::new(text(100)).unwrap();
+        buffer.replace_range(0, 4, b"LINE").unwrap();
+        let anchor = buffer.anchors.create(10, crate::anchors::Gravity::Left);
+        registry.insert(7, buffer);
+
+        assert_eq!(registry.compress_inactive(0), 1);
+        let stats = registry.stats();
+        assert!(stats.buffers[0].compressed);
+        assert!(stats.resident_size < stats.compressed_size);
+
+        let buffer = registry.get_mut(&7).unwrap();
+        assert!(buffer.anchors.get(anchor).is_some());
+        buffer.undo();
+        assert_eq!(buffer.content, text(100));
+    }
+
+    #[test]
+    fn test_resident_bytes_count_history_and_caches() {
+        let mut registry = BufferRegistry::default();
+        registry.insert(1, FileBuffer::new(text(100)).unwrap());
+        let before = registry.stats().buffers[0].resident_bytes;
+
+        let buffer = registry.get_mut(&1).unwrap();
+        buffer.replace_range(0, 4, b"LINE").unwrap();
+        buffer.index_log(2024);
+        let stats = registry.stats();
+        assert!(stats.total_cache_size > 8);
+        assert_eq!(
+            stats.buffers[0].resident_bytes,
+            before + stats.total_cache_size
+        );
+
+        // The log index is dropped with the content; only the history stays
+        registry.compress_inactive(0);
+        assert_eq!(registry.stats().total_cache_size, 8);
+    }
+
+    #[test]
+    fn test_incompressible_until_edited() {
+        let mut registry = BufferRegistry::default();
+        let mut state = 0x2545_f491_u32;
+        let noise: Vec<u8> = (0..4096)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                state as u8
+            })
+            .collect();
+        registry.insert(1, FileBuffer::new(noise).unwrap());
+        assert_eq!(registry.compress_inactive(0), 0);
+
+        // Reads do not make it worth another try, edits do
+        registry.get_mut(&1).unwrap().line_count();
+        assert!(!registry.slots[&1].compressible());
+        let buffer = registry.get_mut(&1).unwrap();
+        buffer.replace_range(0, 4096, &[b'a'; 4096]).unwrap();
+        assert_eq!(registry.compress_inactive(0), 1);
+    }
+
+    #[test]
+    fn test_corrupt_compressed_buffer_is_an_error() {
+        let mut registry = BufferRegistry::default();
+        registry.insert(1, FileBuffer::new(text(100)).unwrap());
+        registry.compress_inactive(0);
+        if let Some((data, _)) = registry.slots.get_mut(&1).unwrap().compressed.as_mut() {
+            data.truncate(data.len() / 2);
+        }
+
+        let code = |result: Result<&FileBuffer, ApiError>| result.err().map(|e| e.code);
+        assert_eq!(code(registry.get(&1)), Some(ErrorCode::Internal));
+        assert!(registry.inspect(&1, |b| b.line_count()).is_err());
+        assert_eq!(code(registry.get(&2)), Some(ErrorCode::NotFound));
+        assert!(registry.remove(&1));
+    }
+
+    #[test]
+    fn test_leaked_buffers() {
+        let mut registry = BufferRegistry::default();
+        for id in 1..=3 {
+            registry.insert(id, FileBuffer::new(b"x\n".to_vec()).unwrap());
+        }
+        let leaked: Vec<u32> = registry.leaked(&[2]).iter().map(|m| m.file_id).collect();
+        assert_eq!(leaked, [1, 3]);
+    }
+}
@@ -0,0 +1,280 @@
+use regex::bytes::Regex;
+use serde::{Deserialize, Serialize};
+
+/// Virtual segments of a single (usually very long) line
+/// The line is split every `width` characters so the editor can fetch and
+/// scroll through column windows instead of materialising the whole line.
+/// Columns count characters by their lead bytes (see positions::Position):
+/// exact for valid UTF-8, while a stray continuation byte in invalid UTF-8
+/// counts with the character before it.
+pub struct SegmentIndex {
+    pub line: usize,
+    pub width: usize,
+    pub start: usize,           // Byte offset of the line start
+    pub end: usize,             // Byte offset of the line end, newline excluded
+    pub char_length: usize,     // Line length in characters
+    pub boundaries: Vec<usize>, // Byte offset where each segment starts
+}
+
+/// Segment layout of a line returned to JavaScript
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct LineSegmentInfo {
+    pub line: usize,
+    pub byte_length: usize,
+    pub char_length: usize,
+    pub segment_width: usize,
+    pub segment_count: usize,
+}
+
+/// A position expressed in segment coordinates (columns are 0-based)
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SegmentPosition {
+    pub line: usize,
+    pub segment: usize,
+    pub column: usize,      // Column within the segment
+    pub line_column: usize, // Column within the whole line
+    pub byte_offset: usize,
+}
+
+/// Regex match inside a line, in segment coordinates
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SegmentMatch {
+    pub start: SegmentPosition,
+    pub end_column: usize, // Line column just past the match
+    pub byte_length: usize,
+}
+
+/// Start of a UTF-8 character, or an invalid lead byte (counted as one)
+pub fn is_char_start(byte: u8) -> bool {
+    byte & 0xC0 != 0x80
+}
+
+impl SegmentIndex {
+    /// Index line `line` spanning `start..end` (end may include the newline)
+    pub fn build(content: &[u8], line: usize, start: usize, end: usize, width: usize) -> Self {
+        let width = width.max(1);
+
+        let mut end = end;
+        if end > start && content[end - 1] == b'\n' {
+            end -= 1;
+            if end > start && content[end - 1] == b'\r' {
+                end -= 1;
+            }
+        }
+
+        let mut boundaries = vec![start];
+        let mut chars = 0;
+        for (i, &byte) in content[start..end].iter().enumerate() {
+            if is_char_start(byte) {
+                if chars > 0 && chars % width == 0 {
+                    boundaries.push(start + i);
+                }
+                chars += 1;
+            }
+        }
+
+        SegmentIndex {
+            line,
+            width,
+            start,
+            end,
+            char_length: chars,
+            boundaries,
+        }
+    }
+
+    /// Approximate heap bytes held by the index
+    pub fn heap_size(&self) -> usize {
+        std::mem::size_of_val(self.boundaries.as_slice())
+    }
+
+    pub fn info(&self) -> LineSegmentInfo {
+        LineSegmentInfo {
+            line: self.line,
+            byte_length: self.end - self.start,
+            char_length: self.char_length,
+            segment_width: self.width,
+            segment_count: self.boundaries.len(),
+        }
+    }
+
+    /// Byte offset of a line column (`char_length` maps to the line end)
+    pub fn column_to_byte(&self, content: &[u8], column: usize) -> Result<usize, String> {
+        if column > self.char_length {
+            return Err(format!(
+                "Column {} out of range (line {} has {} characters)",
+                column, self.line, self.char_length
+            ));
+        }
+
+        let segment = column / self.width;
+        let Some(&from) = self.boundaries.get(segment) else {
+            return Ok(self.end);
+        };
+
+        let mut remaining = column % self.width;
+        let mut offset = from;
+        while remaining > 0 {
+            offset += 1;
+            while offset < self.end && !is_char_start(content[offset]) {
+                offset += 1;
+            }
+            remaining -= 1;
+        }
+
+        Ok(offset)
+    }
+
+    /// Byte offset of a column within a segment
+    pub fn segment_to_byte(
+        &self,
+        content: &[u8],
+        segment: usize,
+        column: usize,
+    ) -> Result<usize, String> {
+        if segment >= self.boundaries.len() || column > self.width {
+            return Err(format!(
+                "Segment {} column {} out of range (line {} has {} segments of {})",
+                segment,
+                column,
+                self.line,
+                self.boundaries.len(),
+                self.width
+            ));
+        }
+
+        self.column_to_byte(
+            content,
+            (segment * self.width + column).min(self.char_length),
+        )
+    }
+
+    /// Segment position of a byte offset in this line
+    /// Offsets inside a multi-byte character resolve to that character
+    pub fn position(&self, content: &[u8], byte_offset: usize) -> Result<SegmentPosition, String> {
+        if byte_offset < self.start || byte_offset > self.end {
+            return Err(format!(
+                "Byte offset {} is not in line {} ({}-{})",
+                byte_offset, self.line, self.start, self.end
+            ));
+        }
+
+        let segment = self.boundaries.partition_point(|&b| b <= byte_offset) - 1;
+        let from = self.boundaries[segment];
+        let mut column = content[from..byte_offset]
+            .iter()
+            .filter(|&&b| is_char_start(b))
+            .count();
+
+        // Mid-character offset: step back to the character's first byte
+        let mut offset = byte_offset;
+        if offset < self.end && !is_char_start(content[offset]) {
+            while offset > from && !is_char_start(content[offset]) {
+                offset -= 1;
+            }
+            column -= 1;
+        }
+
+        Ok(SegmentPosition {
+            line: self.line,
+            segment,
+            column,
+            line_column: segment * self.width + column,
+            byte_offset: offset,
+        })
+    }
+
+    /// Byte range for a window of line columns `start_column..end_column`
+    /// The window is clamped to the line
+    pub fn window(
+        &self,
+        content: &[u8],
+        start_column: usize,
+        end_column: usize,
+    ) -> Result<(usize, usize), String> {
+        if start_column > end_column {
+            return Err(format!(
+                "Invalid range: start {} > end {}",
+                start_column, end_column
+            ));
+        }
+
+        let start = self.column_to_byte(content, start_column.min(self.char_length))?;
+        let end = self.column_to_byte(content, end_column.min(self.char_length))?;
+        Ok((start, end))
+    }
+
+    /// Search the line for a regex, returning up to `max_results` matches
+    pub fn search(
+        &self,
+        content: &[u8],
+        pattern: &str,
+        max_results: usize,
+    ) -> Result<Vec<SegmentMatch>, String> {
+        let re = Regex::new(pattern).map_err(|e| format!("Invalid regex: {}", e))?;
+        let line = &content[self.start..self.end];
+
+        let mut results = Vec::new();
+        for mat in re.find_iter(line).take(max_results) {
+            let start = self.position(content, self.start + mat.start())?;
+            let end = self.position(content, self.start + mat.end())?;
+            results.push(SegmentMatch {
+                start,
+                end_column: end.line_column,
+                byte_length: mat.len(),
+            });
+        }
+
+        Ok(results)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_segments_and_windows() {
+        let content = "héllo wörld\nnext\n".as_bytes();
+        let index = SegmentIndex::build(content, 1, 0, 14, 4);
+
+        assert_eq!(index.char_length, 11);
+        assert_eq!(index.boundaries, vec![0, 5, 10]);
+        assert_eq!(index.info().segment_count, 3);
+
+        let (start, end) = index.window(content, 4, 8).unwrap();
+        assert_eq!(&content[start..end], "o wö".as_bytes());
+
+        // Window past the end is clamped
+        let (start, end) = index.window(content, 8, 100).unwrap();
+        assert_eq!(&content[start..end], "rld".as_bytes());
+        assert!(index.column_to_byte(content, 12).is_err());
+    }
+
+    #[test]
+    fn test_position_mapping() {
+        let content = "héllo wörld".as_bytes();
+        let index = SegmentIndex::build(content, 1, 0, content.len(), 4);
+
+        assert_eq!(index.segment_to_byte(content, 2, 1).unwrap(), 11);
+
+        let pos = index.position(content, 11).unwrap();
+        assert_eq!((pos.segment, pos.column, pos.line_column), (2, 1, 9));
+
+        // Second byte of "é" resolves to the character start
+        let pos = index.position(content, 2).unwrap();
+        assert_eq!((pos.column, pos.byte_offset), (1, 1));
+    }
+
+    #[test]
+    fn test_search_in_line() {
+        let content = format!("{}needle{}needle", "a".repeat(10), "é".repeat(5)).into_bytes();
+        let index = SegmentIndex::build(&content, 1, 0, content.len(), 8);
+
+        let matches = index.search(&content, "needle", 10).unwrap();
+        assert_eq!(matches.len(), 2);
+        assert_eq!((matches[0].start.segment, matches[0].start.column), (1, 2));
+        assert_eq!(matches[1].start.line_column, 21);
+        assert_eq!(matches[1].end_column, 27);
+    }
+}
@@ -0,0 +1,351 @@
+use crate::anchors::{Anchor, Gravity};
+use crate::file_buffer::FileBuffer;
+use crate::history::{Edit, EditHistory};
+use serde::{Deserialize, Serialize};
+
+// Snapshot layout (integers little-endian, byte strings prefixed by a u64
+// length):
+//   magic "TIDYBUF\0", u16 version
+//   line ending (u8)
+//   content, line offsets (u64 count + u32 each, so at most 4 GiB)
+//   undo edits, redo edits (u64 count + u64 start, removed, inserted each)
+//   anchors (u64 count + u32 id, u64 offset, u8 gravity each)
+//   u32 CRC32 of everything before it
+// Version 1 also stored an encoding string before the line ending, always
+// "UTF-8" as buffers do not track their encoding; it is skipped on restore.
+const MAGIC: &[u8; 8] = b"TIDYBUF\0";
+const VERSION: u16 = 2;
+
+/// Line ending style of a buffer, recorded so a restored buffer is saved
+/// the way it was loaded
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum LineEnding {
+    None, // No line breaks at all
+    Lf,
+    Crlf,
+    Mixed,
+}
+
+impl LineEnding {
+    pub fn detect(content: &[u8]) -> Self {
+        let lf = content.iter().filter(|&&b| b == b'\n').count();
+        let crlf = content.windows(2).filter(|w| w == b"\r\n").count();
+        match (lf, crlf) {
+            (0, _) => LineEnding::None,
+            (_, 0) => LineEnding::Lf,
+            _ if lf == crlf => LineEnding::Crlf,
+            _ => LineEnding::Mixed,
+        }
+    }
+
+    fn code(self) -> u8 {
+        self as u8
+    }
+
+    fn from_code(code: u8) -> Result<Self, String> {
+        match code {
+            0 => Ok(LineEnding::None),
+            1 => Ok(LineEnding::Lf),
+            2 => Ok(LineEnding::Crlf),
+            3 => Ok(LineEnding::Mixed),
+            _ => Err(format!("Invalid line ending code {}", code)),
+        }
+    }
+}
+
+/// What restore_buffer hands back to JavaScript
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct RestoredBuffer {
+    pub file_id: u32,
+    pub size: usize,
+    pub line_count: usize,
+    pub line_ending: LineEnding,
+}
+
+/// A buffer and the metadata stored next to it
+pub struct Snapshot {
+    pub buffer: FileBuffer,
+    pub line_ending: LineEnding,
+}
+
+struct Writer(Vec<u8>);
+
+impl Writer {
+    fn u8(&mut self, value: u8) {
+        self.0.push(value);
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn u64(&mut self, value: usize) {
+        self.0.extend_from_slice(&(value as u64).to_le_bytes());
+    }
+
+    fn bytes(&mut self, bytes: &[u8]) {
+        self.u64(bytes.len());
+        self.0.extend_from_slice(bytes);
+    }
+
+    fn edits<'e>(&mut self, edits: impl ExactSizeIterator<Item = &'e Edit>) {
+        self.u64(edits.len());
+        for edit in edits {
+            self.u64(edit.start);
+            self.bytes(&edit.removed);
+            self.bytes(&edit.inserted);
+        }
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], String> {
+        if len > self.data.len() - self.pos {
+            return Err("Snapshot is truncated".to_string());
+        }
+        let bytes = &self.data[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, String> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<usize, String> {
+        let value = u64::from_le_bytes(self.take(8)?.try_into().unwrap());
+        usize::try_from(value).map_err(|_| "Snapshot is too large".to_string())
+    }
+
+    fn bytes(&mut self) -> Result<&'a [u8], String> {
+        let len = self.u64()?;
+        self.take(len)
+    }
+
+    /// Element count, checked against the bytes left so a corrupt count
+    /// cannot trigger a huge allocation
+    fn count(&mut self, min_item_size: usize) -> Result<usize, String> {
+        let count = self.u64()?;
+        if count > (self.data.len() - self.pos) / min_item_size {
+            return Err("Snapshot is truncated".to_string());
+        }
+        Ok(count)
+    }
+
+    fn edits(&mut self) -> Result<Vec<Edit>, String> {
+        let count = self.count(24)?;
+        (0..count)
+            .map(|_| {
+                Ok(Edit {
+                    start: self.u64()?,
+                    removed: self.bytes()?.to_vec(),
+                    inserted: self.bytes()?.to_vec(),
+                })
+            })
+            .collect()
+    }
+}
+
+/// Pack content, line index, metadata, undo history and anchors
+/// Derived indexes (folding, outline, log) are rebuilt on demand instead
+pub fn serialize(buffer: &FileBuffer) -> Result<Vec<u8>, String> {
+    if u32::try_from(buffer.content.len()).is_err() {
+        return Err("Buffers over 4 GiB cannot be snapshotted".to_string());
+    }
+
+    let mut w = Writer(Vec::with_capacity(
+        buffer.content.len() + buffer.line_offsets.len() * 4 + 64,
+    ));
+    w.0.extend_from_slice(MAGIC);
+    w.0.extend_from_slice(&VERSION.to_le_bytes());
+
+    w.u8(LineEnding::detect(&buffer.content).code());
+
+    w.bytes(&buffer.content);
+    w.u64(buffer.line_offsets.len());
+    for &offset in &buffer.line_offsets {
+        w.u32(offset as u32);
+    }
+
+    let (undo, redo) = buffer.history.stacks();
+    w.edits(undo.iter());
+    w.edits(redo.iter());
+
+    let anchors = buffer.anchors.list();
+    w.u64(anchors.len());
+    for anchor in &anchors {
+        w.u32(anchor.id);
+        w.u64(anchor.offset);
+        w.u8(match anchor.gravity {
+            Gravity::Left => 0,
+            Gravity::Right => 1,
+        });
+    }
+
+    let checksum = crc32fast::hash(&w.0);
+    w.u32(checksum);
+    Ok(w.0)
+}
+
+/// Unpack a snapshot without re-indexing the content
+/// The stored line index is only checked for consistency, which touches
+/// one byte per line instead of scanning the whole content
+pub fn deserialize(data: &[u8]) -> Result<Snapshot, String> {
+    if data.len() < MAGIC.len() + 2 + 4 || !data.starts_with(MAGIC) {
+        return Err("Not a buffer snapshot".to_string());
+    }
+    let (body, checksum) = data.split_at(data.len() - 4);
+    if crc32fast::hash(body) != u32::from_le_bytes(checksum.try_into().unwrap()) {
+        return Err("Snapshot checksum mismatch (corrupted)".to_string());
+    }
+
+    let mut r = Reader {
+        data: body,
+        pos: MAGIC.len(),
+    };
+    let version = r.u16()?;
+    if version != VERSION && version != 1 {
+        return Err(format!(
+            "Unsupported snapshot version {} (expected {})",
+            version, VERSION
+        ));
+    }
+
+    if version == 1 {
+        r.bytes()?; // Encoding
+    }
+    let line_ending = LineEnding::from_code(r.u8()?)?;
+
+    let content = r.bytes()?.to_vec();
+    let line_count = r.count(4)?;
+    let line_offsets = (0..line_count)
+        .map(|_| r.u32().map(|offset| offset as usize))
+        .collect::<Result<Vec<usize>, String>>()?;
+    let consistent = line_offsets.first() == Some(&0)
+        && line_offsets.windows(2).all(|w| w[0] < w[1])
+        && line_offsets[1..]
+            .iter()
+            .all(|&offset| offset <= content.len() && content[offset - 1] == b'\n');
+    if !consistent {
+        return Err("Snapshot line index does not match its content".to_string());
+    }
+
+    let undo = r.edits()?;
+    let redo = r.edits()?;
+
+    let anchor_count = r.count(13)?;
+    let mut anchors = Vec::with_capacity(anchor_count);
+    for _ in 0..anchor_count {
+        let id = r.u32()?;
+        let offset = r.u64()?;
+        let gravity = match r.u8()? {
+            0 => Gravity::Left,
+            _ => Gravity::Right,
+        };
+        if offset > content.len() {
+            return Err(format!("Anchor {} is outside the content", id));
+        }
+        anchors.push(Anchor {
+            id,
+            offset,
+            gravity,
+        });
+    }
+
+    let mut buffer = FileBuffer::with_index(content, line_offsets);
+    buffer.history = EditHistory::from_stacks(undo, redo);
+    buffer.anchors.restore(anchors);
+
+    Ok(Snapshot {
+        buffer,
+        line_ending,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip_keeps_index_history_and_anchors() {
+        let mut buffer = FileBuffer::new(b"one\r\ntwo\r\nthree".to_vec()).unwrap();
+        buffer.replace_range(5, 8, b"TWO").unwrap();
+        buffer.replace_range(0, 3, b"1").unwrap();
+        buffer.undo();
+        let anchor = buffer.anchors.create(5, Gravity::Right);
+
+        let snapshot = deserialize(&serialize(&buffer).unwrap()).unwrap();
+        assert_eq!(snapshot.line_ending, LineEnding::Crlf);
+
+        let mut restored = snapshot.buffer;
+        assert_eq!(restored.content, buffer.content);
+        assert_eq!(restored.line_offsets, buffer.line_offsets);
+        assert_eq!(restored.get_line_range(3, 3).unwrap(), "three");
+        assert_eq!(restored.anchors.get(anchor).unwrap().offset, 5);
+
+        restored.redo();
+        assert_eq!(restored.content, b"1\r\nTWO\r\nthree");
+        restored.undo();
+        restored.undo();
+        assert_eq!(restored.content, b"one\r\ntwo\r\nthree");
+    }
+
+    #[test]
+    fn test_rejects_corrupt_and_unknown_snapshots() {
+        let buffer = FileBuffer::new(b"a\nb\n".to_vec()).unwrap();
+        let data = serialize(&buffer).unwrap();
+
+        let mut flipped = data.clone();
+        flipped[20] ^= 1;
+        assert!(deserialize(&flipped).err().unwrap().contains("checksum"));
+        assert!(deserialize(&data[..data.len() - 6]).is_err());
+        assert!(deserialize(b"not a snapshot").is_err());
+
+        // A newer version with a valid checksum
+        let mut newer = data[..data.len() - 4].to_vec();
+        newer[8] = 3;
+        let checksum = crc32fast::hash(&newer);
+        newer.extend_from_slice(&checksum.to_le_bytes());
+        assert!(deserialize(&newer).err().unwrap().contains("version 3"));
+    }
+
+    #[test]
+    fn test_restores_version_1_snapshots() {
+        let buffer = FileBuffer::new(b"a\r\nb\r\n".to_vec()).unwrap();
+        let data = serialize(&buffer).unwrap();
+
+        // Version 1 had an encoding string after the version
+        let mut old = data[..10].to_vec();
+        old[8] = 1;
+        old.extend_from_slice(&5u64.to_le_bytes());
+        old.extend_from_slice(b"UTF-8");
+        old.extend_from_slice(&data[10..data.len() - 4]);
+        let checksum = crc32fast::hash(&old);
+        old.extend_from_slice(&checksum.to_le_bytes());
+
+        let snapshot = deserialize(&old).unwrap();
+        assert_eq!(snapshot.buffer.content, buffer.content);
+        assert_eq!(snapshot.line_ending, LineEnding::Crlf);
+    }
+
+    #[test]
+    fn test_detects_line_endings() {
+        assert_eq!(LineEnding::detect(b"no breaks"), LineEnding::None);
+        assert_eq!(LineEnding::detect(b"a\nb\n"), LineEnding::Lf);
+        assert_eq!(LineEnding::detect(b"a\r\nb\n"), LineEnding::Mixed);
+    }
+}
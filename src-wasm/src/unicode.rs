@@ -0,0 +1,404 @@
+use crate::file_buffer::FileBuffer;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use unicode_normalization::{is_nfc, is_nfd, is_nfkc, UnicodeNormalization};
+
+/// Kind of suspicious character
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum IssueKind {
+    Invisible,  // Zero-width and formatting characters
+    Whitespace, // Space look-alikes such as NBSP
+    Control,    // C0/C1 controls other than tab and line breaks
+    Bidi,       // Direction overrides, embeddings and isolates
+    Confusable, // Look-alike of a Latin letter inside a Latin word
+}
+
+impl FromStr for IssueKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "invisible" => Ok(IssueKind::Invisible),
+            "whitespace" => Ok(IssueKind::Whitespace),
+            "control" => Ok(IssueKind::Control),
+            "bidi" => Ok(IssueKind::Bidi),
+            "confusable" => Ok(IssueKind::Confusable),
+            _ => Err(format!("Unknown character category '{}'", s)),
+        }
+    }
+}
+
+/// A suspicious character and where it is (column in characters, 0-based)
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct UnicodeIssue {
+    pub line: usize,
+    pub column: usize,
+    pub byte_offset: usize,
+    pub code_point: String, // "U+200B"
+    pub name: String,
+    pub kind: IssueKind,
+    pub replacement: Option<char>, // What `replace` mode substitutes
+}
+
+/// Counts per category
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct IssueCounts {
+    pub invisible: usize,
+    pub whitespace: usize,
+    pub control: usize,
+    pub bidi: usize,
+    pub confusable: usize,
+}
+
+/// Result of a Unicode scan
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct UnicodeReport {
+    pub issues: Vec<UnicodeIssue>, // At most max_results entries
+    pub counts: IssueCounts,
+    pub truncated: bool,
+    pub invalid_utf8_lines: usize, // Skipped by the scan
+    pub is_nfc: bool,
+    pub is_nfd: bool,
+    pub is_nfkc: bool,
+    pub non_nfc_lines: usize, // Lines that change under NFC
+}
+
+/// Normalization form for normalize_unicode
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NormalForm {
+    Nfc,
+    Nfd,
+    Nfkc,
+    Nfkd,
+}
+
+impl FromStr for NormalForm {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "nfc" => Ok(NormalForm::Nfc),
+            "nfd" => Ok(NormalForm::Nfd),
+            "nfkc" => Ok(NormalForm::Nfkc),
+            "nfkd" => Ok(NormalForm::Nfkd),
+            _ => Err(format!(
+                "Unknown normalization form '{}' (expected nfc, nfd, nfkc or nfkd)",
+                s
+            )),
+        }
+    }
+}
+
+/// Classify a character that is suspicious wherever it appears
+/// Returns (kind, name, replacement)
+fn classify(c: char) -> Option<(IssueKind, &'static str, Option<char>)> {
+    use IssueKind::*;
+
+    let found = match c {
+        '\t' | '\n' | '\r' => return None,
+        '\u{200B}' => (Invisible, "ZERO WIDTH SPACE", None),
+        '\u{200C}' => (Invisible, "ZERO WIDTH NON-JOINER", None),
+        '\u{200D}' => (Invisible, "ZERO WIDTH JOINER", None),
+        '\u{2060}' => (Invisible, "WORD JOINER", None),
+        '\u{2061}'..='\u{2064}' => (Invisible, "INVISIBLE OPERATOR", None),
+        '\u{FEFF}' => (Invisible, "ZERO WIDTH NO-BREAK SPACE (BOM)", None),
+        '\u{00AD}' => (Invisible, "SOFT HYPHEN", None),
+        '\u{180E}' => (Invisible, "MONGOLIAN VOWEL SEPARATOR", None),
+        '\u{00A0}' => (Whitespace, "NO-BREAK SPACE", Some(' ')),
+        '\u{2000}'..='\u{200A}' => (Whitespace, "TYPOGRAPHIC SPACE", Some(' ')),
+        '\u{202F}' => (Whitespace, "NARROW NO-BREAK SPACE", Some(' ')),
+        '\u{205F}' => (Whitespace, "MEDIUM MATHEMATICAL SPACE", Some(' ')),
+        '\u{3000}' => (Whitespace, "IDEOGRAPHIC SPACE", Some(' ')),
+        '\u{2028}' => (Whitespace, "LINE SEPARATOR", Some(' ')),
+        '\u{2029}' => (Whitespace, "PARAGRAPH SEPARATOR", Some(' ')),
+        '\u{202A}'..='\u{202E}' => (Bidi, "BIDI EMBEDDING/OVERRIDE", None),
+        '\u{2066}'..='\u{2069}' => (Bidi, "BIDI ISOLATE", None),
+        '\u{200E}' => (Bidi, "LEFT-TO-RIGHT MARK", None),
+        '\u{200F}' => (Bidi, "RIGHT-TO-LEFT MARK", None),
+        '\u{061C}' => (Bidi, "ARABIC LETTER MARK", None),
+        '\u{0000}'..='\u{001F}' | '\u{007F}'..='\u{009F}' => (Control, "CONTROL CHARACTER", None),
+        _ => return None,
+    };
+    Some(found)
+}
+
+/// Latin look-alike of a Cyrillic, Greek or fullwidth letter
+fn confusable(c: char) -> Option<(&'static str, char)> {
+    let found = match c {
+        'а' => ("CYRILLIC SMALL LETTER A", 'a'),
+        'е' => ("CYRILLIC SMALL LETTER IE", 'e'),
+        'о' => ("CYRILLIC SMALL LETTER O", 'o'),
+        'р' => ("CYRILLIC SMALL LETTER ER", 'p'),
+        'с' => ("CYRILLIC SMALL LETTER ES", 'c'),
+        'у' => ("CYRILLIC SMALL LETTER U", 'y'),
+        'х' => ("CYRILLIC SMALL LETTER HA", 'x'),
+        'і' => ("CYRILLIC SMALL LETTER BYELORUSSIAN-UKRAINIAN I", 'i'),
+        'ј' => ("CYRILLIC SMALL LETTER JE", 'j'),
+        'ѕ' => ("CYRILLIC SMALL LETTER DZE", 's'),
+        'һ' => ("CYRILLIC SMALL LETTER SHHA", 'h'),
+        'ԁ' => ("CYRILLIC SMALL LETTER KOMI DE", 'd'),
+        'А' => ("CYRILLIC CAPITAL LETTER A", 'A'),
+        'В' => ("CYRILLIC CAPITAL LETTER VE", 'B'),
+        'Е' => ("CYRILLIC CAPITAL LETTER IE", 'E'),
+        'К' => ("CYRILLIC CAPITAL LETTER KA", 'K'),
+        'М' => ("CYRILLIC CAPITAL LETTER EM", 'M'),
+        'Н' => ("CYRILLIC CAPITAL LETTER EN", 'H'),
+        'О' => ("CYRILLIC CAPITAL LETTER O", 'O'),
+        'Р' => ("CYRILLIC CAPITAL LETTER ER", 'P'),
+        'С' => ("CYRILLIC CAPITAL LETTER ES", 'C'),
+        'Т' => ("CYRILLIC CAPITAL LETTER TE", 'T'),
+        'Х' => ("CYRILLIC CAPITAL LETTER HA", 'X'),
+        'І' => ("CYRILLIC CAPITAL LETTER BYELORUSSIAN-UKRAINIAN I", 'I'),
+        'Ј' => ("CYRILLIC CAPITAL LETTER JE", 'J'),
+        'Ѕ' => ("CYRILLIC CAPITAL LETTER DZE", 'S'),
+        'ο' => ("GREEK SMALL LETTER OMICRON", 'o'),
+        'α' => ("GREEK SMALL LETTER ALPHA", 'a'),
+        'ν' => ("GREEK SMALL LETTER NU", 'v'),
+        'Α' => ("GREEK CAPITAL LETTER ALPHA", 'A'),
+        'Β' => ("GREEK CAPITAL LETTER BETA", 'B'),
+        'Ε' => ("GREEK CAPITAL LETTER EPSILON", 'E'),
+        'Η' => ("GREEK CAPITAL LETTER ETA", 'H'),
+        'Ι' => ("GREEK CAPITAL LETTER IOTA", 'I'),
+        'Κ' => ("GREEK CAPITAL LETTER KAPPA", 'K'),
+        'Μ' => ("GREEK CAPITAL LETTER MU", 'M'),
+        'Ν' => ("GREEK CAPITAL LETTER NU", 'N'),
+        'Ο' => ("GREEK CAPITAL LETTER OMICRON", 'O'),
+        'Ρ' => ("GREEK CAPITAL LETTER RHO", 'P'),
+        'Τ' => ("GREEK CAPITAL LETTER TAU", 'T'),
+        'Χ' => ("GREEK CAPITAL LETTER CHI", 'X'),
+        'Ζ' => ("GREEK CAPITAL LETTER ZETA", 'Z'),
+        '\u{FF01}'..='\u{FF5E}' => (
+            "FULLWIDTH FORM",
+            char::from_u32(c as u32 - 0xFF01 + 0x21).unwrap_or(c),
+        ),
+        _ => return None,
+    };
+    Some(found)
+}
+
+/// Suspicious characters of one line, with positions
+/// Confusables only count inside words that also contain ASCII letters, so
+/// ordinary Cyrillic or Greek text is not flagged.
+fn scan_line(text: &str, line: usize, line_start: usize) -> Vec<UnicodeIssue> {
+    let mut issues = Vec::new();
+    let chars: Vec<(usize, char)> = text.char_indices().collect();
+
+    let mut word_start = 0;
+    for (column, &(offset, c)) in chars.iter().enumerate() {
+        if !c.is_alphanumeric() {
+            word_start = column + 1;
+        }
+
+        let found = classify(c).or_else(|| {
+            let (name, latin) = confusable(c)?;
+            let word_end = chars[column..]
+                .iter()
+                .position(|(_, c)| !c.is_alphanumeric())
+                .map_or(chars.len(), |i| column + i);
+            let mixed = chars[word_start..word_end]
+                .iter()
+                .any(|(_, c)| c.is_ascii_alphabetic());
+            mixed.then_some((IssueKind::Confusable, name, Some(latin)))
+        });
+
+        if let Some((kind, name, replacement)) = found {
+            issues.push(UnicodeIssue {
+                line,
+                column,
+                byte_offset: line_start + offset,
+                code_point: format!("U+{:04X}", c as u32),
+                name: name.to_string(),
+                kind,
+                replacement,
+            });
+        }
+    }
+
+    issues
+}
+
+/// Lines of the buffer as (line number, byte offset, text without line ending)
+/// Lines that are not valid UTF-8 are returned as Err
+fn lines(buffer: &FileBuffer) -> impl Iterator<Item = (usize, usize, Result<&str, ()>)> {
+    (1..=buffer.line_count()).map(move |line_num| {
+        let (start, end) = buffer.get_line_byte_range(line_num).unwrap_or((0, 0));
+        let raw = &buffer.content[start..end];
+        let raw = raw.strip_suffix(b"\n").unwrap_or(raw);
+        let raw = raw.strip_suffix(b"\r").unwrap_or(raw);
+        (line_num, start, std::str::from_utf8(raw).map_err(|_| ()))
+    })
+}
+
+/// Scan the buffer for suspicious characters and normalization problems
+pub fn scan(buffer: &FileBuffer, max_results: usize) -> UnicodeReport {
+    let mut report = UnicodeReport {
+        issues: Vec::new(),
+        counts: IssueCounts::default(),
+        truncated: false,
+        invalid_utf8_lines: 0,
+        is_nfc: true,
+        is_nfd: true,
+        is_nfkc: true,
+        non_nfc_lines: 0,
+    };
+
+    for (line_num, start, text) in lines(buffer) {
+        let Ok(text) = text else {
+            report.invalid_utf8_lines += 1;
+            continue;
+        };
+
+        if !is_nfc(text) {
+            report.is_nfc = false;
+            report.non_nfc_lines += 1;
+        }
+        report.is_nfd &= is_nfd(text);
+        report.is_nfkc &= is_nfkc(text);
+
+        for issue in scan_line(text, line_num, start) {
+            let count = match issue.kind {
+                IssueKind::Invisible => &mut report.counts.invisible,
+                IssueKind::Whitespace => &mut report.counts.whitespace,
+                IssueKind::Control => &mut report.counts.control,
+                IssueKind::Bidi => &mut report.counts.bidi,
+                IssueKind::Confusable => &mut report.counts.confusable,
+            };
+            *count += 1;
+
+            if report.issues.len() < max_results {
+                report.issues.push(issue);
+            } else {
+                report.truncated = true;
+            }
+        }
+    }
+
+    report
+}
+
+/// Line text with the flagged characters removed
+/// With `replace`, characters that have a replacement get it and the rest
+/// are removed; otherwise every flagged character is removed
+fn cleaned(text: &str, issues: &[UnicodeIssue], line_start: usize, replace: bool) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut last = 0;
+    for issue in issues {
+        let offset = issue.byte_offset - line_start;
+        out.push_str(&text[last..offset]);
+        let c = text[offset..].chars().next().unwrap_or_default();
+        if replace {
+            if let Some(replacement) = issue.replacement {
+                out.push(replacement);
+            }
+        }
+        last = offset + c.len_utf8();
+    }
+    out.push_str(&text[last..]);
+    out
+}
+
+/// Strip (or replace, see `cleaned`) characters of the given kinds as one
+/// undoable edit. Returns the number of characters changed.
+pub fn clean(buffer: &mut FileBuffer, kinds: &[IssueKind], replace: bool) -> Result<usize, String> {
+    let mut output = Vec::with_capacity(buffer.content.len());
+    let mut changed = 0;
+
+    for (line_num, start, text) in lines(buffer) {
+        let (_, end) = buffer.get_line_byte_range(line_num)?;
+        let raw = &buffer.content[start..end];
+        let text = match text {
+            Ok(text) => text,
+            Err(()) => {
+                output.extend_from_slice(raw);
+                continue;
+            }
+        };
+
+        let issues: Vec<UnicodeIssue> = scan_line(text, line_num, start)
+            .into_iter()
+            .filter(|i| kinds.contains(&i.kind))
+            .collect();
+        changed += issues.len();
+
+        output.extend_from_slice(cleaned(text, &issues, start, replace).as_bytes());
+        output.extend_from_slice(&raw[text.len()..]);
+    }
+
+    buffer.replace_content(&output)?;
+    Ok(changed)
+}
+
+/// Convert the buffer to a normalization form as one undoable edit
+/// Returns whether anything changed
+pub fn normalize(buffer: &mut FileBuffer, form: NormalForm) -> Result<bool, String> {
+    let text = std::str::from_utf8(&buffer.content)
+        .map_err(|e| format!("Buffer is not valid UTF-8: {}", e))?;
+
+    let normalized: String = match form {
+        NormalForm::Nfc => text.nfc().collect(),
+        NormalForm::Nfd => text.nfd().collect(),
+        NormalForm::Nfkc => text.nfkc().collect(),
+        NormalForm::Nfkd => text.nfkd().collect(),
+    };
+
+    buffer.replace_content(normalized.as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn buffer(text: &str) -> FileBuffer {
+        FileBuffer::new(text.as_bytes().to_vec()).unwrap()
+    }
+
+    #[test]
+    fn test_scan_positions_and_kinds() {
+        let report = scan(
+            &buffer("key:\u{00A0}value\nto\u{200B}ken\u{202E}\npаypal = привет\n"),
+            100,
+        );
+
+        let found: Vec<(usize, usize, IssueKind)> = report
+            .issues
+            .iter()
+            .map(|i| (i.line, i.column, i.kind))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (1, 4, IssueKind::Whitespace),
+                (2, 2, IssueKind::Invisible),
+                (2, 6, IssueKind::Bidi),
+                (3, 1, IssueKind::Confusable),
+            ]
+        );
+        assert_eq!(report.issues[1].code_point, "U+200B");
+        assert_eq!(report.issues[1].byte_offset, 14);
+        assert_eq!(report.issues[3].replacement, Some('a'));
+        assert!(report.is_nfc);
+    }
+
+    #[test]
+    fn test_clean_and_normalize() {
+        let mut buf = buffer("a\u{00A0}b\u{200B}c раypal\n");
+        let kinds = [
+            IssueKind::Whitespace,
+            IssueKind::Invisible,
+            IssueKind::Confusable,
+        ];
+        assert_eq!(clean(&mut buf, &kinds, true).unwrap(), 4);
+        assert_eq!(buf.content, "a bc paypal\n".as_bytes());
+
+        // "é" as e + combining acute
+        let mut buf = buffer("cafe\u{0301}\n");
+        let report = scan(&buf, 10);
+        assert!(!report.is_nfc && report.is_nfd);
+        assert_eq!(report.non_nfc_lines, 1);
+
+        assert!(normalize(&mut buf, NormalForm::Nfc).unwrap());
+        assert_eq!(buf.content, "café\n".as_bytes());
+        assert!(!normalize(&mut buf, NormalForm::Nfc).unwrap());
+    }
+}
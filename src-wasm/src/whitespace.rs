@@ -0,0 +1,263 @@
+use crate::analysis::{IndentDetector, IndentStyle, Indentation};
+use crate::file_buffer::FileBuffer;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// Default number of per-line changes included in a report
+pub const DEFAULT_MAX_CHANGES: usize = 10_000;
+
+/// Indentation to convert to
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IndentTarget {
+    Tabs,
+    Spaces(usize),
+}
+
+/// What to normalize; every step is optional
+pub struct NormalizeOptions {
+    pub indent: Option<IndentTarget>, // Re-indent leading whitespace
+    pub tab_width: usize,             // Columns per tab when measuring indentation
+    pub trim_trailing: bool,
+    pub final_newline: bool,
+}
+
+/// What happened to one line (1-based)
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct LineChange {
+    pub line: usize,
+    pub reindented: bool,
+    pub trimmed: usize, // Trailing whitespace bytes removed
+}
+
+/// Summary of a normalization pass
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct NormalizeReport {
+    pub detected: Indentation,
+    pub lines_changed: usize,
+    pub final_newline_added: bool,
+    pub changes: Vec<LineChange>, // At most max_changes entries
+    pub truncated: bool,          // More lines changed than are listed
+    pub applied: bool,            // False for a dry run or when nothing changed
+}
+
+impl FromStr for IndentTarget {
+    type Err = String;
+
+    /// "tabs", "spaces" (4 wide) or "spaces:N"
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            None if s == "tabs" => Ok(IndentTarget::Tabs),
+            None if s == "spaces" => Ok(IndentTarget::Spaces(4)),
+            Some(("spaces", width)) => width
+                .parse()
+                .ok()
+                .filter(|&w| w > 0)
+                .map(IndentTarget::Spaces)
+                .ok_or_else(|| format!("Invalid indent width '{}'", width)),
+            _ => Err(format!(
+                "Unknown indent style '{}' (expected tabs, spaces or spaces:N)",
+                s
+            )),
+        }
+    }
+}
+
+/// Detect the indentation of a buffer
+pub fn detect_indentation(buffer: &FileBuffer) -> Indentation {
+    let mut detector = IndentDetector::default();
+    for line_num in 1..=buffer.line_count() {
+        if let Ok((start, end)) = buffer.get_line_byte_range(line_num) {
+            let line = trim_eol(&buffer.content[start..end]);
+            if !line.iter().all(|b| b.is_ascii_whitespace()) {
+                detector.observe(line);
+            }
+        }
+    }
+    detector.finish()
+}
+
+fn trim_eol(line: &[u8]) -> &[u8] {
+    let line = line.strip_suffix(b"\n").unwrap_or(line);
+    line.strip_suffix(b"\r").unwrap_or(line)
+}
+
+/// Rewrite leading whitespace measuring `unit` columns per level
+/// Columns left over after whole levels are kept as alignment spaces
+fn reindent(leading: &[u8], unit: usize, tab_width: usize, target: IndentTarget) -> Vec<u8> {
+    let columns = leading.iter().fold(0, |col, &b| {
+        if b == b'\t' {
+            (col / tab_width + 1) * tab_width
+        } else {
+            col + 1
+        }
+    });
+    let (levels, alignment) = (columns / unit, columns % unit);
+
+    let mut indent = match target {
+        IndentTarget::Tabs => vec![b'\t'; levels],
+        IndentTarget::Spaces(width) => vec![b' '; levels * width],
+    };
+    indent.resize(indent.len() + alignment, b' ');
+    indent
+}
+
+/// Normalize whitespace across the buffer
+/// Unless `dry_run`, the result is applied as one undoable edit
+pub fn normalize(
+    buffer: &mut FileBuffer,
+    options: &NormalizeOptions,
+    max_changes: usize,
+    dry_run: bool,
+) -> Result<NormalizeReport, String> {
+    let detected = detect_indentation(buffer);
+    let tab_width = options.tab_width.max(1);
+    let unit = match detected.style {
+        IndentStyle::Spaces | IndentStyle::Mixed => detected.width.unwrap_or(tab_width),
+        _ => tab_width,
+    };
+
+    let content = &buffer.content;
+    let mut output = Vec::with_capacity(content.len());
+    let mut changes = Vec::new();
+    let mut lines_changed = 0;
+
+    for line_num in 1..=buffer.line_count() {
+        let (start, end) = buffer.get_line_byte_range(line_num)?;
+        let raw = &content[start..end];
+        let body = trim_eol(raw);
+        let eol = &raw[body.len()..];
+
+        let leading_len = body
+            .iter()
+            .take_while(|&&b| b == b' ' || b == b'\t')
+            .count();
+        let (leading, mut rest) = body.split_at(leading_len);
+
+        let mut trimmed = 0;
+        let mut leading = leading.to_vec();
+        if options.trim_trailing {
+            let kept = rest
+                .iter()
+                .rposition(|&b| b != b' ' && b != b'\t')
+                .map_or(0, |i| i + 1);
+            trimmed = rest.len() - kept;
+            rest = &rest[..kept];
+            if rest.is_empty() {
+                trimmed += leading.len();
+                leading.clear();
+            }
+        }
+
+        let mut reindented = false;
+        if let Some(target) = options.indent {
+            if !rest.is_empty() {
+                let indent = reindent(&leading, unit, tab_width, target);
+                reindented = indent != leading;
+                leading = indent;
+            }
+        }
+
+        output.extend_from_slice(&leading);
+        output.extend_from_slice(rest);
+        output.extend_from_slice(eol);
+
+        if reindented || trimmed > 0 {
+            lines_changed += 1;
+            if changes.len() < max_changes {
+                changes.push(LineChange {
+                    line: line_num,
+                    reindented,
+                    trimmed,
+                });
+            }
+        }
+    }
+
+    let final_newline_added =
+        options.final_newline && !output.is_empty() && !output.ends_with(b"\n");
+    if final_newline_added {
+        let crlf = content
+            .iter()
+            .position(|&b| b == b'\n')
+            .is_some_and(|i| i > 0 && content[i - 1] == b'\r');
+        output.extend_from_slice(if crlf { b"\r\n" } else { b"\n" });
+    }
+
+    let applied = !dry_run && buffer.replace_content(&output)?;
+
+    Ok(NormalizeReport {
+        detected,
+        lines_changed,
+        final_newline_added,
+        truncated: lines_changed > changes.len(),
+        changes,
+        applied,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(indent: Option<IndentTarget>) -> NormalizeOptions {
+        NormalizeOptions {
+            indent,
+            tab_width: 4,
+            trim_trailing: true,
+            final_newline: true,
+        }
+    }
+
+    #[test]
+    fn test_spaces_to_tabs_and_reindent() {
+        let text = b"a:\n  b:\n    c: 1  \n     d\n".to_vec();
+
+        let mut buffer = FileBuffer::new(text.clone()).unwrap();
+        let report =
+            normalize(&mut buffer, &options(Some(IndentTarget::Tabs)), 100, false).unwrap();
+        assert_eq!(report.detected.width, Some(2));
+        assert_eq!(buffer.content, b"a:\n\tb:\n\t\tc: 1\n\t\t d\n");
+        assert_eq!(report.lines_changed, 3);
+        assert_eq!(
+            report.changes[1],
+            LineChange {
+                line: 3,
+                reindented: true,
+                trimmed: 2
+            }
+        );
+
+        let mut buffer = FileBuffer::new(text).unwrap();
+        let target = Some(IndentTarget::Spaces(4));
+        normalize(&mut buffer, &options(target), 100, false).unwrap();
+        assert_eq!(buffer.content, b"a:\n    b:\n        c: 1\n         d\n");
+
+        // One undo restores the original
+        buffer.undo();
+        assert_eq!(buffer.content, b"a:\n  b:\n    c: 1  \n     d\n");
+    }
+
+    #[test]
+    fn test_tabs_to_spaces_trim_and_final_newline() {
+        let mut buffer = FileBuffer::new(b"x\r\n\ty \r\n \t \r\n\t\tz".to_vec()).unwrap();
+        let target = Some(IndentTarget::Spaces(2));
+        let report = normalize(&mut buffer, &options(target), 1, false).unwrap();
+
+        assert_eq!(buffer.content, b"x\r\n  y\r\n\r\n    z\r\n");
+        assert!(report.final_newline_added);
+        assert_eq!(report.lines_changed, 3);
+        assert!(report.truncated);
+    }
+
+    #[test]
+    fn test_dry_run_leaves_buffer() {
+        let mut buffer = FileBuffer::new(b"a \nb".to_vec()).unwrap();
+        let report = normalize(&mut buffer, &options(None), 100, true).unwrap();
+
+        assert!(!report.applied);
+        assert_eq!(report.lines_changed, 1);
+        assert_eq!(buffer.content, b"a \nb");
+        assert_eq!("spaces:3".parse(), Ok(IndentTarget::Spaces(3)));
+        assert!("spaces:0".parse::<IndentTarget>().is_err());
+    }
+}